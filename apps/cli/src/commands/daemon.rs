@@ -1,7 +1,17 @@
-use std::{env, fs, path::PathBuf};
+use std::{
+    env, fs,
+    io::{self, IsTerminal},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
 
+use anyhow::anyhow;
 use clap::{command, Args, Subcommand};
-use daemon::{daemon::Daemon, service::DaemonServer};
+use daemon::{
+    config::DaemonConfig, daemon::Daemon, protocol, service::DaemonServer,
+    succession::SuccessionChain,
+};
+use plugin::tls;
 
 #[derive(Debug, Args)]
 pub struct DaemonArgs {
@@ -11,7 +21,15 @@ pub struct DaemonArgs {
 
 #[derive(Debug, Subcommand)]
 pub enum DaemonCommands {
-    Init {},
+    Init {
+        path: PathBuf,
+        #[arg(short, long)]
+        template: Option<PathBuf>,
+        // 以全新的身份覆盖已有的 Daemon 身份，原有的 daemon.json 与 successions.json
+        // 保留为带时间戳的 .bak 备份；需要保留轮换历史时应使用 rotate
+        #[arg(short, long)]
+        force: bool,
+    },
     Tar {
         path: std::path::PathBuf,
         #[arg(short, long)]
@@ -26,43 +44,183 @@ pub enum DaemonCommands {
         #[arg(short, long)]
         path: Option<PathBuf>,
         #[arg(short, long)]
+        config: Option<PathBuf>,
+        #[arg(long)]
+        host: Option<String>,
+        #[arg(long)]
         port: Option<u16>,
     },
+    Rotate {
+        #[arg(short, long)]
+        path: PathBuf,
+    },
+    ExportMnemonic {
+        #[arg(short, long)]
+        path: PathBuf,
+    },
+    // 助记词从标准输入读取，避免出现在 shell 历史与进程列表中
+    Import {
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+    // 导出 /api/connect 协议的 JSON Schema 与 TypeScript 定义
+    Protocol {
+        #[arg(short, long)]
+        output: PathBuf,
+    },
 }
 
 impl DaemonArgs {
     pub async fn work(&self) -> anyhow::Result<()> {
         match &self.command {
-            Some(DaemonCommands::Serve { path, port }) => {
-                let port = match port.as_ref() {
-                    Some(val) => val.clone(),
-                    None => 0,
-                };
-
+            Some(DaemonCommands::Serve {
+                path,
+                config,
+                host,
+                port,
+            }) => {
                 let path = path.as_ref().unwrap();
                 let daemon: Daemon = serde_json::from_slice(&fs::read(path)?)?;
-                let service = DaemonServer::new(
-                    daemon,
-                    env::current_dir()?
-                        .join(path)
-                        .parent()
-                        .unwrap()
-                        .to_path_buf(),
-                    port,
-                )
-                .await?;
+                let root_path = env::current_dir()?
+                    .join(path)
+                    .parent()
+                    .unwrap()
+                    .to_path_buf();
+
+                // 命令行参数优先于配置文件
+                let mut daemon_config = match config {
+                    Some(config) => DaemonConfig::from_file(config)?,
+                    None => DaemonConfig::load(&root_path)?,
+                };
+                if let Some(host) = host {
+                    daemon_config.host = host.clone();
+                }
+                if let Some(port) = port {
+                    daemon_config.port = *port;
+                }
+                super::init_tracing(daemon_config.log.level()?.into());
+
+                let service = DaemonServer::new(daemon, root_path, daemon_config).await?;
                 println!("start daemon success.");
                 println!("daemon address: {}", &service.address);
                 service.wait().await?;
                 Ok(())
             }
+            Some(DaemonCommands::Init {
+                path,
+                template,
+                force,
+            }) => {
+                let daemon = init_daemon_dir(path, template.as_ref(), *force)?;
+                println!("init daemon success.");
+                println!("public key: {}", daemon.public_key);
+                Ok(())
+            }
+            Some(DaemonCommands::Rotate { path }) => {
+                let (daemon, record) = Daemon::rotate_file(&env::current_dir()?.join(path))?;
+                println!("rotate daemon key success.");
+                println!("previous public key: {}", record.previous_public_key);
+                println!("public key: {}", daemon.public_key);
+                Ok(())
+            }
+            Some(DaemonCommands::ExportMnemonic { path }) => {
+                let daemon: Daemon = serde_json::from_slice(&fs::read(path)?)?;
+                println!("{}", daemon.to_mnemonic()?);
+                Ok(())
+            }
+            Some(DaemonCommands::Import { output }) => {
+                if output.exists() {
+                    return Err(anyhow!("目标文件已存在：{}", output.display()));
+                }
+
+                let daemon = Daemon::from_mnemonic(&read_mnemonic()?)?;
+                if let Some(parent) = output.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                tls::write_private(output, serde_json::to_string(&daemon)?.as_bytes())?;
+                println!("import daemon success.");
+                println!("public key: {}", daemon.public_key);
+                Ok(())
+            }
+            Some(DaemonCommands::Protocol { output }) => {
+                protocol::export(output)?;
+                println!("export protocol success.");
+                Ok(())
+            }
             Some(DaemonCommands::Tar { path, output }) => {
                 bundler::daemon::tar(path.clone(), output.clone())
             }
             Some(DaemonCommands::Untar { path, output }) => {
                 bundler::daemon::untar(path.clone(), output.clone())
             }
-            _ => Ok(()),
+            None => Ok(()),
         }
     }
 }
+
+// 在终端中以不回显的方式提示输入，否则从管道读取一行
+fn read_mnemonic() -> anyhow::Result<String> {
+    if io::stdin().is_terminal() {
+        return Ok(rpassword::prompt_password("mnemonic: ")?);
+    }
+
+    let mut mnemonic = String::new();
+    io::stdin().read_line(&mut mnemonic)?;
+    Ok(mnemonic)
+}
+
+fn init_daemon_dir(path: &Path, template: Option<&PathBuf>, force: bool) -> anyhow::Result<Daemon> {
+    let config_path = path.join(DaemonConfig::FILE_NAME);
+    let config = DaemonConfig::load(path)?;
+    let daemon_path = path.join("daemon.json");
+    let successions_path = SuccessionChain::path(path);
+    let assets_path = path.join(&config.paths.assets);
+
+    // 修改任何文件之前先检查，避免未使用 --force 时只完成一部分
+    if !force {
+        for existing in [&daemon_path, &successions_path] {
+            if existing.exists() {
+                return Err(anyhow!(
+                    "目录中已存在 Daemon 身份：{}，如需覆盖请使用 --force",
+                    existing.display()
+                ));
+            }
+        }
+        if template.is_some() && assets_path.exists() {
+            return Err(anyhow!(
+                "目录中已存在 assets：{}，如需覆盖请使用 --force",
+                assets_path.display()
+            ));
+        }
+    }
+
+    fs::create_dir_all(path.join(&config.paths.plugins))?;
+
+    // 总是生成全新的身份且不读取原有文件，原有文件损坏时同样可以覆盖；
+    // 原有的身份与轮换记录不再有效，以带时间戳的名称备份，多次覆盖不会丢失之前的备份
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+    for existing in [&daemon_path, &successions_path] {
+        if existing.exists() {
+            let backup = existing.with_extension(format!("json.{}.bak", timestamp));
+            if backup.exists() {
+                return Err(anyhow!("备份文件已存在：{}", backup.display()));
+            }
+            fs::rename(existing, backup)?;
+        }
+    }
+    let daemon = Daemon::new_random()?;
+    tls::write_private(&daemon_path, serde_json::to_string(&daemon)?.as_bytes())?;
+
+    if let Some(template) = template {
+        if assets_path.exists() {
+            fs::remove_dir_all(&assets_path)?;
+        }
+        bundler::daemon::untar(template.clone(), assets_path)?;
+    }
+
+    if !config_path.exists() {
+        fs::write(config_path, config.to_toml_string()?)?;
+    }
+
+    Ok(daemon)
+}
@@ -1,6 +1,10 @@
+use std::io::IsTerminal;
+
 use clap::Subcommand;
 use daemon::DaemonArgs;
 use plugin::PluginArgs;
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::prelude::*;
 
 mod daemon;
 mod plugin;
@@ -10,3 +14,19 @@ pub enum Commands {
     Plugin(PluginArgs),
     Daemon(DaemonArgs),
 }
+
+// 在终端输出 Daemon 与 Plugin 的诊断信息，依赖库只输出警告与错误
+fn init_tracing(level: tracing::Level) {
+    let filter = Targets::new()
+        .with_target("daemon", level)
+        .with_target("plugin", level)
+        .with_default(tracing::Level::WARN);
+    tracing_subscriber::registry()
+        .with(
+            tracing_subscriber::fmt::layer()
+                .pretty()
+                .with_ansi(std::io::stdout().is_terminal()),
+        )
+        .with(filter)
+        .init();
+}
@@ -1,6 +1,12 @@
+use std::path::PathBuf;
+
 use anyhow::anyhow;
 use clap::{command, Args, Subcommand};
-use plugin::{Options, PluginServer};
+use plugin::{
+    regist::RegistKey,
+    tls::{self, ServerTls},
+    Options, PluginServer, ServerLimits,
+};
 
 #[derive(Debug, Args)]
 pub struct PluginArgs {
@@ -28,9 +34,65 @@ pub enum PluginCommands {
         regist_address: Option<String>,
         #[arg(short, long)]
         port: Option<u16>,
+        #[arg(long, requires = "tls_key")]
+        tls_cert: Option<PathBuf>,
+        #[arg(long, requires = "tls_cert")]
+        tls_key: Option<PathBuf>,
+        #[arg(long)]
+        daemon_fingerprint: Option<String>,
+        #[arg(long, conflicts_with = "regist_key")]
+        regist_token: Option<String>,
+        #[arg(long)]
+        regist_key: Option<PathBuf>,
+        #[command(flatten)]
+        limits: Box<LimitArgs>,
+        // 错误响应中包含错误详情与 wasm 调用栈
+        #[arg(long)]
+        debug: bool,
+    },
+    Keygen {
+        #[arg(short, long)]
+        output: PathBuf,
     },
 }
 
+// Plugin 服务的连接与请求限制，未指定时使用默认值；只作用于 plugin serve 启动的服务
+#[derive(Debug, Args)]
+pub struct LimitArgs {
+    // 同时保持的最大连接数
+    #[arg(long)]
+    max_connections: Option<usize>,
+    // 连接空闲超时，单位为秒
+    #[arg(long)]
+    idle_timeout: Option<u64>,
+    // 请求首部的最大字节数
+    #[arg(long)]
+    max_header_size: Option<usize>,
+    // 请求体的最大字节数
+    #[arg(long)]
+    max_body_size: Option<u64>,
+}
+
+impl LimitArgs {
+    fn limits(&self) -> anyhow::Result<ServerLimits> {
+        let mut limits = ServerLimits::default();
+        if let Some(max_connections) = self.max_connections {
+            limits.max_connections = max_connections;
+        }
+        if let Some(idle_timeout) = self.idle_timeout {
+            limits.idle_timeout = std::time::Duration::from_secs(idle_timeout);
+        }
+        if let Some(max_header_size) = self.max_header_size {
+            limits.max_header_size = max_header_size;
+        }
+        if let Some(max_body_size) = self.max_body_size {
+            limits.max_body_size = max_body_size;
+        }
+        limits.validate()?;
+        Ok(limits)
+    }
+}
+
 impl PluginArgs {
     pub async fn work(&self) -> anyhow::Result<()> {
         match self.command.as_ref() {
@@ -49,6 +111,13 @@ impl PluginArgs {
                 daemon_address,
                 regist_address,
                 port,
+                tls_cert,
+                tls_key,
+                daemon_fingerprint,
+                regist_token,
+                regist_key,
+                limits,
+                debug,
             }) => {
                 let port = match port {
                     Some(val) => *val,
@@ -67,6 +136,16 @@ impl PluginArgs {
                     return Err(anyhow!("未找到指定的 Plugin 配置文件"));
                 }
 
+                let tls = match (tls_cert, tls_key) {
+                    (Some(cert), Some(key)) => Some(ServerTls::load(cert, key)?),
+                    _ => None,
+                };
+
+                super::init_tracing(match debug {
+                    true => tracing::Level::DEBUG,
+                    false => tracing::Level::INFO,
+                });
+
                 // 启动 Plugin
                 let service = PluginServer::new(
                     plugin_path,
@@ -74,6 +153,15 @@ impl PluginArgs {
                         port,
                         daemon_address: daemon_address.clone(),
                         regist_address: regist_address.clone(),
+                        tls,
+                        daemon_fingerprint: daemon_fingerprint.clone(),
+                        regist_token: regist_token.clone(),
+                        regist_key: match regist_key {
+                            Some(path) => Some(RegistKey::from_file(path)?),
+                            None => None,
+                        },
+                        limits: limits.limits()?,
+                        debug: *debug,
                     },
                 )
                 .await?;
@@ -86,6 +174,16 @@ impl PluginArgs {
                 service.wait().await;
                 Ok(())
             }
+            Some(PluginCommands::Keygen { output }) => {
+                if output.exists() {
+                    return Err(anyhow!("文件已存在：{}", output.display()));
+                }
+                let key = RegistKey::generate();
+                tls::write_private(output, serde_json::to_string(&key)?.as_bytes())?;
+                println!("generate regist key success.");
+                println!("public key: {}", key.public_key);
+                Ok(())
+            }
             None => Ok(()),
         }
     }
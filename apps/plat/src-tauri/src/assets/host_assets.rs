@@ -1,5 +1,6 @@
 use std::{collections::HashMap, fs, path::PathBuf};
 
+use anyhow::anyhow;
 use daemon::daemon::Daemon;
 use tauri::{AppHandle, Manager};
 use tokio::sync::Mutex;
@@ -100,6 +101,9 @@ impl HostAssets {
         for daemon in self.local_daemons.lock().await.values() {
             daemon.up().await?;
         }
+        for daemon in self.remote_daemons.lock().await.values() {
+            daemon.up().await?;
+        }
 
         Ok(())
     }
@@ -108,11 +112,23 @@ impl HostAssets {
         for daemon in self.local_daemons.lock().await.values() {
             daemon.down().await?;
         }
+        for daemon in self.remote_daemons.lock().await.values() {
+            daemon.down().await?;
+        }
 
         Ok(())
     }
 
     pub async fn append_local_daemon(&self, plugin_daemon: Daemon) -> anyhow::Result<()> {
+        if self
+            .local_daemons
+            .lock()
+            .await
+            .contains_key(&plugin_daemon.public_key)
+        {
+            return Err(anyhow!("已存在相同公钥的 Local Daemon"));
+        }
+
         let daemon_dir = self
             .path
             .join("daemons")
@@ -175,21 +191,41 @@ impl HostAssets {
         fs::write(&daemon_file_path, serde_json::to_string(&remote_daemon)?)?;
 
         let remote_daemon_asset = RemoteDaemonAsset::new_from_path(daemon_dir).await?;
+        remote_daemon_asset.up().await?;
         let daemon_key = urlencoding::encode(&remote_daemon.address);
-        self.remote_daemons
+        // 重新添加同一地址时替换原有资产，原有代理随之关闭
+        let previous = self
+            .remote_daemons
             .lock()
             .await
             .insert(daemon_key.to_string(), remote_daemon_asset);
+        if let Some(previous) = previous {
+            previous.down().await?;
+        }
 
         Ok(())
     }
 
+    // 之前添加该地址时记录的公钥
+    pub async fn remote_daemon_public_key(&self, address: &str) -> Option<String> {
+        let daemon_key = urlencoding::encode(address);
+        self.remote_daemons
+            .lock()
+            .await
+            .get(daemon_key.as_ref())
+            .and_then(|asset| asset.remote_daemon.public_key.clone())
+    }
+
     pub async fn delete_remote_daemon(&self, address: String) -> anyhow::Result<()> {
         let daemon_key = urlencoding::encode(&address);
-        self.remote_daemons
+        if let Some(asset) = self
+            .remote_daemons
             .lock()
             .await
-            .remove(&daemon_key.to_string());
+            .remove(&daemon_key.to_string())
+        {
+            asset.down().await?;
+        }
 
         let daemon_dir = self
             .path
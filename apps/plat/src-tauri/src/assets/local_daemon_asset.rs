@@ -1,7 +1,7 @@
 use std::{fs, ops::DerefMut, path::PathBuf, sync::Arc};
 
 use anyhow::anyhow;
-use daemon::{daemon::Daemon, service::DaemonServer};
+use daemon::{config::DaemonConfig, daemon::Daemon, service::DaemonServer};
 use serde_json::{json, Value};
 use tokio::sync::Mutex;
 
@@ -44,8 +44,12 @@ impl LocalDaemonAsset {
             return Ok(());
         }
 
-        let plugin_daemon_service =
-            DaemonServer::new(self.plugin_daemon.clone(), self.path.clone(), 0).await?;
+        let plugin_daemon_service = DaemonServer::new(
+            self.plugin_daemon.clone(),
+            self.path.clone(),
+            DaemonConfig::load(&self.path)?,
+        )
+        .await?;
         plugin_daemon_service_option.replace(plugin_daemon_service);
 
         Ok(())
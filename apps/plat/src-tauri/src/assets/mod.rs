@@ -1,4 +1,5 @@
 pub mod host_assets;
 pub mod local_daemon_asset;
 pub mod remote_daemon_asset;
+pub mod remote_daemon_proxy;
 pub mod template_asset;
@@ -1,12 +1,16 @@
-use std::{fs, path::PathBuf};
+use std::{fs, ops::DerefMut, path::PathBuf};
 
 use serde_json::{json, Value};
+use tokio::sync::Mutex;
 
 use crate::typings::RemoteDaemon;
 
+use super::remote_daemon_proxy::RemoteDaemonProxy;
+
 pub struct RemoteDaemonAsset {
     pub path: PathBuf,
     pub remote_daemon: RemoteDaemon,
+    proxy: Mutex<Option<RemoteDaemonProxy>>,
 }
 
 impl RemoteDaemonAsset {
@@ -17,15 +21,43 @@ impl RemoteDaemonAsset {
         let daemon_asset = RemoteDaemonAsset {
             path,
             remote_daemon,
+            proxy: Mutex::new(None),
         };
 
         Ok(daemon_asset)
     }
 
     pub async fn to_json_string(&self) -> anyhow::Result<Value> {
+        let proxy = self.proxy.lock().await;
         let value = json!({
             "address": &self.remote_daemon.address,
+            "public_key": &self.remote_daemon.public_key,
+            "tls_fingerprint": &self.remote_daemon.tls_fingerprint,
+            // 界面应通过该地址访问固定了证书的远程 Daemon
+            "proxy_address": proxy.as_ref().map(|proxy| &proxy.address),
         });
         Ok(value)
     }
+
+    // 记录了证书指纹的远程 Daemon 需要经由本地代理访问
+    pub async fn up(&self) -> anyhow::Result<()> {
+        let mut proxy = self.proxy.lock().await;
+        if proxy.is_some() {
+            return Ok(());
+        }
+
+        if let Some(tls_fingerprint) = self.remote_daemon.tls_fingerprint.as_ref() {
+            proxy.replace(
+                RemoteDaemonProxy::start(&self.remote_daemon.address, tls_fingerprint).await?,
+            );
+        }
+
+        Ok(())
+    }
+
+    pub async fn down(&self) -> anyhow::Result<()> {
+        *self.proxy.lock().await.deref_mut() = None;
+
+        Ok(())
+    }
 }
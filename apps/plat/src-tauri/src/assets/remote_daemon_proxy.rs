@@ -0,0 +1,252 @@
+use std::{borrow::Cow, net::SocketAddr, sync::Arc};
+
+use anyhow::Context;
+use axum::{
+    body::Body,
+    extract::{
+        ws::{CloseFrame, Message, WebSocket},
+        Request, State, WebSocketUpgrade,
+    },
+    http::{
+        header::{self, HeaderName},
+        HeaderMap, StatusCode,
+    },
+    response::{IntoResponse, Response},
+    Router,
+};
+use futures_util::{SinkExt, StreamExt};
+use plugin::tls::{self, rustls::ClientConfig};
+use reqwest::{redirect::Policy, Client};
+use tokio::task::JoinHandle;
+use tokio_tungstenite::{
+    tungstenite::{self, client::IntoClientRequest, protocol::frame::coding::CloseCode},
+    Connector,
+};
+
+// 逐跳首部不应被代理转发
+const HOP_BY_HOP_HEADERS: [HeaderName; 8] = [
+    header::CONNECTION,
+    HeaderName::from_static("keep-alive"),
+    header::PROXY_AUTHENTICATE,
+    header::PROXY_AUTHORIZATION,
+    header::TE,
+    header::TRAILER,
+    header::TRANSFER_ENCODING,
+    header::UPGRADE,
+];
+
+// WebView 无法固定远程 Daemon 的自签名证书，因此在本机回环地址上启动代理，
+// 由代理按添加时记录的证书指纹校验证书后再转发请求
+pub struct RemoteDaemonProxy {
+    pub address: String,
+    task: JoinHandle<()>,
+}
+
+struct Upstream {
+    address: String,
+    tls: Arc<ClientConfig>,
+    client: Client,
+}
+
+impl RemoteDaemonProxy {
+    pub async fn start(address: &str, tls_fingerprint: &str) -> anyhow::Result<Self> {
+        let tls = tls::client_config(Some(tls_fingerprint))?;
+        let client = Client::builder()
+            .use_preconfigured_tls(tls.as_ref().clone())
+            .redirect(Policy::none())
+            .build()?;
+        let upstream = Arc::new(Upstream {
+            address: address.trim_end_matches('/').to_string(),
+            tls,
+            client,
+        });
+
+        let listener = tokio::net::TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0)))
+            .await
+            .context("监听本地代理端口失败")?;
+        let local_address = format!("http://{}", listener.local_addr()?);
+        let app = Router::new().fallback(proxy_handler).with_state(upstream);
+        let task = tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, app).await {
+                tracing::error!("远程 Daemon 代理异常退出：{:?}", e);
+            }
+        });
+
+        Ok(RemoteDaemonProxy {
+            address: local_address,
+            task,
+        })
+    }
+}
+
+impl Drop for RemoteDaemonProxy {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn proxy_handler(
+    State(upstream): State<Arc<Upstream>>,
+    ws: Option<WebSocketUpgrade>,
+    req: Request,
+) -> Response {
+    let result = match ws {
+        Some(ws) => proxy_websocket(&upstream, ws, req).await,
+        None => proxy_http(&upstream, req).await,
+    };
+    match result {
+        Ok(response) => response,
+        Err(e) => {
+            tracing::warn!(daemon = %upstream.address, "代理远程 Daemon 请求失败：{:?}", e);
+            (StatusCode::BAD_GATEWAY, format!("proxy error: {:?}", e)).into_response()
+        }
+    }
+}
+
+fn upstream_uri(upstream: &Upstream, req: &Request) -> String {
+    let path_and_query = req
+        .uri()
+        .path_and_query()
+        .map(|path_and_query| path_and_query.as_str())
+        .unwrap_or("/");
+    format!("{}{}", upstream.address, path_and_query)
+}
+
+async fn proxy_http(upstream: &Upstream, req: Request) -> anyhow::Result<Response> {
+    let uri = upstream_uri(upstream, &req);
+    let (parts, body) = req.into_parts();
+    let mut headers = forward_headers(&parts.headers);
+    headers.remove(header::HOST);
+
+    let upstream_response = upstream
+        .client
+        .request(parts.method, uri)
+        .headers(headers)
+        .body(reqwest::Body::wrap_stream(body.into_data_stream()))
+        .send()
+        .await
+        .context("请求远程 Daemon 失败")?;
+
+    let mut response = Response::builder().status(upstream_response.status());
+    *response.headers_mut().unwrap() = forward_headers(upstream_response.headers());
+    Ok(response.body(Body::from_stream(upstream_response.bytes_stream()))?)
+}
+
+async fn proxy_websocket(
+    upstream: &Upstream,
+    ws: WebSocketUpgrade,
+    req: Request,
+) -> anyhow::Result<Response> {
+    let uri = upstream_uri(upstream, &req)
+        .replacen("http://", "ws://", 1)
+        .replacen("https://", "wss://", 1);
+    let mut upstream_request = uri.into_client_request()?;
+    if let Some(protocol) = req.headers().get(header::SEC_WEBSOCKET_PROTOCOL) {
+        upstream_request
+            .headers_mut()
+            .insert(header::SEC_WEBSOCKET_PROTOCOL, protocol.clone());
+    }
+
+    let connector = Connector::Rustls(upstream.tls.clone());
+    let (upstream_socket, upstream_response) = tokio_tungstenite::connect_async_tls_with_config(
+        upstream_request,
+        None,
+        false,
+        Some(connector),
+    )
+    .await
+    .context("连接远程 Daemon WebSocket 失败")?;
+
+    let ws = match upstream_response
+        .headers()
+        .get(header::SEC_WEBSOCKET_PROTOCOL)
+        .and_then(|value| value.to_str().ok())
+    {
+        Some(protocol) => ws.protocols([protocol.to_string()]),
+        None => ws,
+    };
+
+    Ok(ws.on_upgrade(move |socket| pipe_websocket(socket, upstream_socket)))
+}
+
+async fn pipe_websocket<S>(socket: WebSocket, upstream: tokio_tungstenite::WebSocketStream<S>)
+where
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+{
+    let (mut client_write, mut client_read) = socket.split();
+    let (mut upstream_write, mut upstream_read) = upstream.split();
+
+    loop {
+        tokio::select! {
+            message = client_read.next() => {
+                let message = match message {
+                    Some(Ok(message)) => message,
+                    _ => break,
+                };
+                let close = matches!(message, Message::Close(_));
+                if upstream_write.send(to_upstream_message(message)).await.is_err() || close {
+                    break;
+                }
+            },
+            message = upstream_read.next() => {
+                let message = match message {
+                    Some(Ok(message)) => message,
+                    _ => break,
+                };
+                let message = match to_client_message(message) {
+                    Some(message) => message,
+                    None => continue,
+                };
+                let close = matches!(message, Message::Close(_));
+                if client_write.send(message).await.is_err() || close {
+                    break;
+                }
+            },
+        }
+    }
+}
+
+fn to_upstream_message(message: Message) -> tungstenite::Message {
+    match message {
+        Message::Text(text) => tungstenite::Message::Text(text),
+        Message::Binary(data) => tungstenite::Message::Binary(data),
+        Message::Ping(data) => tungstenite::Message::Ping(data),
+        Message::Pong(data) => tungstenite::Message::Pong(data),
+        Message::Close(frame) => {
+            tungstenite::Message::Close(frame.map(|frame| tungstenite::protocol::CloseFrame {
+                code: CloseCode::from(frame.code),
+                reason: Cow::Owned(frame.reason.into_owned()),
+            }))
+        }
+    }
+}
+
+fn to_client_message(message: tungstenite::Message) -> Option<Message> {
+    match message {
+        tungstenite::Message::Text(text) => Some(Message::Text(text)),
+        tungstenite::Message::Binary(data) => Some(Message::Binary(data)),
+        tungstenite::Message::Ping(data) => Some(Message::Ping(data)),
+        tungstenite::Message::Pong(data) => Some(Message::Pong(data)),
+        tungstenite::Message::Close(frame) => Some(Message::Close(frame.map(|frame| CloseFrame {
+            code: frame.code.into(),
+            reason: Cow::Owned(frame.reason.into_owned()),
+        }))),
+        tungstenite::Message::Frame(_) => None,
+    }
+}
+
+// 同时移除 Connection 首部中列出的首部
+fn forward_headers(headers: &HeaderMap) -> HeaderMap {
+    let mut forwarded = headers.clone();
+    let listed = headers
+        .get_all(header::CONNECTION)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|name| HeaderName::from_bytes(name.trim().as_bytes()).ok())
+        .collect::<Vec<_>>();
+    for name in HOP_BY_HOP_HEADERS.iter().chain(listed.iter()) {
+        forwarded.remove(name);
+    }
+    forwarded
+}
@@ -1,20 +1,32 @@
 use anyhow::anyhow;
-use daemon::daemon::Daemon;
+use daemon::{daemon::Daemon, tls::fetch_remote_identity};
 use tauri::Emitter;
 
 use crate::typings::{HostState, RemoteDaemon};
 
 #[tauri::command]
+#[tracing::instrument(skip(state, app_handle, mnemonic))]
 pub async fn append_daemon(
     state: HostState<'_>,
     app_handle: tauri::AppHandle,
     variant: &str,
     remote_address: &str,
+    mnemonic: Option<&str>,
+    public_key: Option<&str>,
 ) -> Result<(), ()> {
-    match append_daemon_inner(state, app_handle, variant, remote_address).await {
+    match append_daemon_inner(
+        state,
+        app_handle,
+        variant,
+        remote_address,
+        mnemonic,
+        public_key,
+    )
+    .await
+    {
         Ok(val) => Ok(val),
         Err(e) => {
-            println!("append command error: {}", e);
+            tracing::error!("添加 Daemon 失败：{:#}", e);
             Err(())
         }
     }
@@ -25,6 +37,8 @@ async fn append_daemon_inner(
     app_handle: tauri::AppHandle,
     variant: &str,
     remote_address: &str,
+    mnemonic: Option<&str>,
+    public_key: Option<&str>,
 ) -> anyhow::Result<()> {
     match variant {
         "local-generate" => {
@@ -37,9 +51,39 @@ async fn append_daemon_inner(
                 .await?;
             app_handle.emit("update-daemons", ())?;
         }
+        "local-restore" => {
+            let mnemonic = match mnemonic {
+                Some(mnemonic) => mnemonic,
+                None => return Err(anyhow!("恢复账号需要提供助记词")),
+            };
+            let plugin_daemon = Daemon::from_mnemonic(mnemonic)?;
+            state
+                .host_assets
+                .read()
+                .await
+                .append_local_daemon(plugin_daemon)
+                .await?;
+            app_handle.emit("update-daemons", ())?;
+        }
         "remote" => {
+            // 优先使用用户提供的公钥，其次使用之前添加该地址时记录的公钥
+            let pinned_public_key = match public_key.filter(|key| !key.is_empty()) {
+                Some(key) => Some(key.to_string()),
+                None => {
+                    state
+                        .host_assets
+                        .read()
+                        .await
+                        .remote_daemon_public_key(remote_address)
+                        .await
+                }
+            };
+            let identity =
+                fetch_remote_identity(remote_address, pinned_public_key.as_deref()).await?;
             let remote_daemon = RemoteDaemon {
                 address: remote_address.to_string(),
+                public_key: Some(identity.public_key),
+                tls_fingerprint: identity.tls_fingerprint,
             };
             state
                 .host_assets
@@ -2,11 +2,12 @@ use crate::typings::HostState;
 use serde_json::json;
 
 #[tauri::command]
+#[tracing::instrument(skip(state))]
 pub async fn get_daemons(state: HostState<'_>) -> Result<String, ()> {
     match get_daemons_inner(state).await {
         Ok(val) => Ok(val),
         Err(e) => {
-            println!("get daemons error: {}", e);
+            tracing::error!("读取 Daemon 列表失败：{:#}", e);
             Err(())
         }
     }
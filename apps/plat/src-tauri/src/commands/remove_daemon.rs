@@ -3,6 +3,7 @@ use tauri::Emitter;
 use crate::typings::HostState;
 
 #[tauri::command]
+#[tracing::instrument(skip(state, app_handle))]
 pub async fn remove_daemon(
     state: HostState<'_>,
     app_handle: tauri::AppHandle,
@@ -12,7 +13,7 @@ pub async fn remove_daemon(
     match remove_daemon_inner(state, app_handle, public_key, address).await {
         Ok(val) => Ok(val),
         Err(e) => {
-            println!("remove daemons error: {}", e);
+            tracing::error!("删除 Daemon 失败：{:#}", e);
             Err(())
         }
     }
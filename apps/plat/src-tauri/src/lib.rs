@@ -6,10 +6,12 @@ use typings::HostStateInner;
 
 pub mod assets;
 pub mod commands;
+mod logging;
 pub mod typings;
 
 fn setup<'a>(app: &'a mut tauri::App) -> Result<(), Box<dyn std::error::Error>> {
     let handle = app.handle();
+    logging::init(&handle.path().app_log_dir()?);
     handle.manage(HostStateInner {
         host_assets: RwLock::new(HostAssets::empty()),
     });
@@ -0,0 +1,37 @@
+use std::path::Path;
+
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::prelude::*;
+
+// 按天轮转后保留的日志文件数量
+const MAX_LOG_FILES: usize = 14;
+
+// 将应用、Daemon 与 Plugin 的诊断信息以 JSON 行写入日志目录，按天轮转
+pub fn init(log_dir: &Path) {
+    let filter = Targets::new()
+        .with_target("plat_lib", tracing::Level::INFO)
+        .with_target("daemon", tracing::Level::INFO)
+        .with_target("plugin", tracing::Level::INFO)
+        .with_default(tracing::Level::WARN);
+    // 每条事件输出一行 JSON，包含时间、级别、字段以及从根开始的 span 链
+    let layer = tracing_subscriber::fmt::layer()
+        .json()
+        .with_current_span(false)
+        .with_span_list(true)
+        .with_ansi(false);
+    let registry = tracing_subscriber::registry().with(filter);
+    match RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix("plat.log")
+        .max_log_files(MAX_LOG_FILES)
+        .build(log_dir)
+    {
+        Ok(writer) => registry.with(layer.with_writer(writer)).init(),
+        // 无法创建日志文件时输出到标准错误
+        Err(e) => {
+            registry.with(layer.with_writer(std::io::stderr)).init();
+            tracing::error!("创建日志文件失败：{:?}", e);
+        }
+    }
+}
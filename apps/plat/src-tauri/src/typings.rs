@@ -12,4 +12,9 @@ pub type HostState<'a> = State<'a, HostStateInner>;
 #[derive(Debug, Serialize, Deserialize)]
 pub struct RemoteDaemon {
     pub address: String,
+    // 添加时从 /api 获取并固定的身份信息
+    #[serde(default)]
+    pub public_key: Option<String>,
+    #[serde(default)]
+    pub tls_fingerprint: Option<String>,
 }
@@ -12,4 +12,7 @@ export interface Daemon {
 export interface RemoteDaemon {
   address: string;
   password: string;
+  public_key?: string;
+  tls_fingerprint?: string;
+  proxy_address?: string;
 }
@@ -8,6 +8,7 @@ import {
   ModalFooter,
   Button,
   Input,
+  Textarea,
 } from "@nextui-org/react";
 import { invoke, InvokeArgs } from "@tauri-apps/api/core";
 import { useEffect, useState } from "react";
@@ -20,17 +21,22 @@ interface Props {
 enum Variant {
   Null = "",
   LocalGenerate = "local-generate",
+  LocalRestore = "local-restore",
   Remote = "remote",
 }
 
 interface CreateDaemonData {
   variant: Variant;
   remoteAddress: string;
+  publicKey: string;
+  mnemonic: string;
 }
 
 const DEFAULT_VALUE: CreateDaemonData = {
   variant: Variant.Null,
   remoteAddress: "",
+  publicKey: "",
+  mnemonic: "",
 } as const;
 
 export default function CreateDaemonModal({ isOpen, onClose }: Props) {
@@ -62,8 +68,21 @@ export default function CreateDaemonModal({ isOpen, onClose }: Props) {
             }
           >
             <SelectItem key={Variant.LocalGenerate}>本地生成</SelectItem>
+            <SelectItem key={Variant.LocalRestore}>助记词恢复</SelectItem>
             <SelectItem key={Variant.Remote}>远程服务</SelectItem>
           </Select>
+          {form.variant === Variant.LocalRestore && (
+            <Textarea
+              label="助记词"
+              value={form.mnemonic}
+              onChange={(e) =>
+                setForm((prev) => ({
+                  ...prev,
+                  mnemonic: e.target.value,
+                }))
+              }
+            />
+          )}
           {form.variant === Variant.Remote && (
             <>
               <Input
@@ -76,6 +95,16 @@ export default function CreateDaemonModal({ isOpen, onClose }: Props) {
                   }))
                 }
               />
+              <Input
+                label="远程服务公钥（可选）"
+                value={form.publicKey}
+                onChange={(e) =>
+                  setForm((prev) => ({
+                    ...prev,
+                    publicKey: e.target.value,
+                  }))
+                }
+              />
             </>
           )}
         </ModalBody>
@@ -0,0 +1,366 @@
+use std::{
+    fs,
+    net::{IpAddr, SocketAddr},
+    path::Path,
+    time::Duration,
+};
+
+use anyhow::{anyhow, bail, Context};
+use axum::http::HeaderValue;
+use base64::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// Daemon 配置，对应 Daemon 文件夹中的 daemon.toml
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct DaemonConfig {
+    pub host: String,
+    pub port: u16,
+    pub heartbeat: HeartbeatConfig,
+    pub cors: CorsConfig,
+    pub paths: PathsConfig,
+    pub plugin: PluginLimitConfig,
+    pub log: LogConfig,
+    pub tls: TlsConfig,
+    pub regist: RegistConfig,
+    pub health: HealthConfig,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(default, deny_unknown_fields)]
+pub struct HeartbeatConfig {
+    // /api/regist 连接的 Ping 间隔与超时时间（秒）
+    pub regist_ping_interval: u64,
+    pub regist_timeout: u64,
+    // /api/connect 连接的 Ping 间隔与超时时间（秒）
+    pub connect_ping_interval: u64,
+    pub connect_timeout: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct CorsConfig {
+    // 为空时允许任意来源
+    pub allow_origins: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct PathsConfig {
+    // 相对于 Daemon 文件夹的路径
+    pub assets: String,
+    pub plugins: String,
+    // 宿主为各 Plugin 管理的数据（日志等），与插件目录分开存放，重新安装时保留
+    pub data: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct PluginLimitConfig {
+    // 允许同时注册的 Plugin 数量上限，为空时不限制
+    pub max_plugins: Option<usize>,
+    // 插件安装包的大小上限（字节）
+    pub max_upload_size: usize,
+    // 发往进程内 Plugin 的请求体的大小上限（字节），超出时返回 413
+    pub max_body_size: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    pub level: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct TlsConfig {
+    // PEM 格式的证书与私钥路径，相对于 Daemon 文件夹
+    pub cert: Option<String>,
+    pub key: Option<String>,
+    // 未指定证书时自动生成自签名证书，其指纹由 Daemon 身份签名
+    pub self_signed: bool,
+    // 自签名证书额外包含的域名或 IP
+    pub subject_alt_names: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct RegistConfig {
+    // 允许远程 Plugin 通过 /api/regist 注册的令牌
+    pub tokens: Vec<String>,
+    // 已授权的 Plugin 注册公钥，Plugin 使用对应私钥签名 Challenge
+    pub authorized_keys: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(default, deny_unknown_fields)]
+pub struct HealthConfig {
+    // 探测 Plugin 健康状态的间隔（秒），为 0 时不探测
+    pub interval: u64,
+    // 单次探测的超时时间（秒）
+    pub timeout: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl Default for DaemonConfig {
+    fn default() -> Self {
+        DaemonConfig {
+            host: String::from("127.0.0.1"),
+            port: 0,
+            heartbeat: HeartbeatConfig::default(),
+            cors: CorsConfig::default(),
+            paths: PathsConfig::default(),
+            plugin: PluginLimitConfig::default(),
+            log: LogConfig::default(),
+            tls: TlsConfig::default(),
+            regist: RegistConfig::default(),
+            health: HealthConfig::default(),
+        }
+    }
+}
+
+impl Default for HeartbeatConfig {
+    fn default() -> Self {
+        HeartbeatConfig {
+            regist_ping_interval: 4,
+            regist_timeout: 10,
+            connect_ping_interval: 5,
+            connect_timeout: 12,
+        }
+    }
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        HealthConfig {
+            interval: 30,
+            timeout: 5,
+        }
+    }
+}
+
+impl Default for PathsConfig {
+    fn default() -> Self {
+        PathsConfig {
+            assets: String::from("assets"),
+            plugins: String::from("plugins"),
+            data: String::from("data"),
+        }
+    }
+}
+
+impl Default for PluginLimitConfig {
+    fn default() -> Self {
+        PluginLimitConfig {
+            max_plugins: None,
+            max_upload_size: 64 * 1024 * 1024,
+            max_body_size: 64 * 1024 * 1024,
+        }
+    }
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        LogConfig {
+            level: String::from("info"),
+        }
+    }
+}
+
+impl DaemonConfig {
+    pub const FILE_NAME: &'static str = "daemon.toml";
+
+    // 读取 Daemon 文件夹中的 daemon.toml，文件不存在时使用默认配置
+    pub fn load(root_path: &Path) -> anyhow::Result<Self> {
+        let path = root_path.join(Self::FILE_NAME);
+        if !path.exists() {
+            return Ok(DaemonConfig::default());
+        }
+
+        Self::from_file(&path)
+    }
+
+    pub fn from_file(path: &Path) -> anyhow::Result<Self> {
+        let content =
+            fs::read_to_string(path).with_context(|| format!("读取 {} 失败", path.display()))?;
+        toml::from_str(&content).with_context(|| format!("解析 {} 失败", path.display()))
+    }
+
+    pub fn to_toml_string(&self) -> anyhow::Result<String> {
+        Ok(toml::to_string_pretty(self)?)
+    }
+
+    // 监听地址，host 为 IPv6 地址时同样适用
+    pub fn socket_addr(&self) -> anyhow::Result<SocketAddr> {
+        let host = self
+            .host
+            .parse::<IpAddr>()
+            .map_err(|_| anyhow!("host 不是合法的 IP 地址：{}", self.host))?;
+        Ok(SocketAddr::new(host, self.port))
+    }
+
+    pub fn validate(&self) -> anyhow::Result<()> {
+        self.socket_addr()?;
+
+        let heartbeat = &self.heartbeat;
+        if heartbeat.regist_ping_interval == 0 || heartbeat.connect_ping_interval == 0 {
+            bail!("heartbeat 中的 Ping 间隔必须大于 0");
+        }
+        if heartbeat.regist_timeout <= heartbeat.regist_ping_interval {
+            bail!("heartbeat.regist_timeout 必须大于 heartbeat.regist_ping_interval");
+        }
+        if heartbeat.connect_timeout <= heartbeat.connect_ping_interval {
+            bail!("heartbeat.connect_timeout 必须大于 heartbeat.connect_ping_interval");
+        }
+
+        for origin in self.cors.allow_origins.iter() {
+            HeaderValue::from_str(origin)
+                .map_err(|_| anyhow!("cors.allow_origins 中存在非法的来源：{}", origin))?;
+        }
+
+        if self.paths.assets.is_empty()
+            || self.paths.plugins.is_empty()
+            || self.paths.data.is_empty()
+        {
+            bail!("paths 中的路径不能为空");
+        }
+
+        if self.plugin.max_plugins == Some(0) {
+            bail!("plugin.max_plugins 必须大于 0");
+        }
+        if self.plugin.max_body_size == 0 {
+            bail!("plugin.max_body_size 必须大于 0");
+        }
+
+        self.log.level()?;
+
+        if self.health.interval > 0 && self.health.timeout == 0 {
+            bail!("health.timeout 必须大于 0");
+        }
+
+        match (&self.tls.cert, &self.tls.key) {
+            (Some(_), None) | (None, Some(_)) => bail!("tls.cert 与 tls.key 必须同时配置"),
+            (Some(_), Some(_)) if self.tls.self_signed => {
+                bail!("tls.self_signed 不能与 tls.cert、tls.key 同时配置")
+            }
+            _ => (),
+        }
+
+        if self.regist.tokens.iter().any(|token| token.is_empty()) {
+            bail!("regist.tokens 中不能包含空令牌");
+        }
+        for key in self.regist.authorized_keys.iter() {
+            let valid = BASE64_URL_SAFE
+                .decode(key)
+                .map(|bytes| bytes.len() == 32)
+                .unwrap_or(false);
+            if !valid {
+                bail!("regist.authorized_keys 中存在非法的公钥：{}", key);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl TlsConfig {
+    pub fn enabled(&self) -> bool {
+        self.self_signed || self.cert.is_some()
+    }
+}
+
+impl HeartbeatConfig {
+    pub fn regist_ping_interval(&self) -> Duration {
+        Duration::from_secs(self.regist_ping_interval)
+    }
+
+    pub fn regist_timeout(&self) -> Duration {
+        Duration::from_secs(self.regist_timeout)
+    }
+
+    pub fn connect_ping_interval(&self) -> Duration {
+        Duration::from_secs(self.connect_ping_interval)
+    }
+
+    pub fn connect_timeout(&self) -> Duration {
+        Duration::from_secs(self.connect_timeout)
+    }
+}
+
+impl HealthConfig {
+    pub fn interval(&self) -> Option<Duration> {
+        match self.interval {
+            0 => None,
+            interval => Some(Duration::from_secs(interval)),
+        }
+    }
+
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout)
+    }
+}
+
+impl LogConfig {
+    pub fn level(&self) -> anyhow::Result<LogLevel> {
+        match self.level.as_str() {
+            "error" => Ok(LogLevel::Error),
+            "warn" => Ok(LogLevel::Warn),
+            "info" => Ok(LogLevel::Info),
+            "debug" => Ok(LogLevel::Debug),
+            "trace" => Ok(LogLevel::Trace),
+            level => Err(anyhow!(
+                "log.level 必须为 error、warn、info、debug 或 trace，但它的值为：{}",
+                level
+            )),
+        }
+    }
+
+    pub fn enabled(&self, level: LogLevel) -> bool {
+        self.level().map(|current| level <= current).unwrap_or(true)
+    }
+}
+
+impl From<LogLevel> for tracing::Level {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Error => tracing::Level::ERROR,
+            LogLevel::Warn => tracing::Level::WARN,
+            LogLevel::Info => tracing::Level::INFO,
+            LogLevel::Debug => tracing::Level::DEBUG,
+            LogLevel::Trace => tracing::Level::TRACE,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn socket_addr_accepts_ipv6_host() {
+        let config = DaemonConfig {
+            host: String::from("::1"),
+            port: 8080,
+            ..DaemonConfig::default()
+        };
+
+        assert_eq!(config.socket_addr().unwrap().to_string(), "[::1]:8080");
+        config.validate().unwrap();
+    }
+
+    #[test]
+    fn validate_rejects_zero_plugin_body_size() {
+        let mut config = DaemonConfig::default();
+        config.plugin.max_body_size = 0;
+
+        assert!(config.validate().is_err());
+    }
+}
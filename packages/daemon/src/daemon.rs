@@ -1,8 +1,24 @@
-use anyhow::anyhow;
+use anyhow::{anyhow, Context};
 use base64::prelude::*;
+use bip39::Mnemonic;
 use ed25519_dalek::{ed25519::signature::SignerMut, Signature, SigningKey, VerifyingKey};
 use rand::rngs::OsRng;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+// Daemon 私钥为轮换记录签名时在数据前加上的前缀
+pub const SUCCESSION_DOMAIN: &[u8] = b"plat-succession-v1\0";
+// Daemon 私钥为 TLS 证书指纹签名时在指纹前加上的前缀
+pub const TLS_FINGERPRINT_DOMAIN: &[u8] = b"plat-tls-fingerprint-v1\0";
+// /api/sig 拒绝签名以这些前缀开头的数据，避免借此伪造 Daemon 的内部签名
+const RESERVED_DOMAINS: [&[u8]; 2] = [SUCCESSION_DOMAIN, TLS_FINGERPRINT_DOMAIN];
+
+pub fn is_reserved(data: &[u8]) -> bool {
+    RESERVED_DOMAINS
+        .iter()
+        .any(|domain| data.starts_with(domain))
+}
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Daemon {
@@ -21,15 +37,47 @@ impl Daemon {
     pub fn new_random() -> anyhow::Result<Self> {
         let mut csprng = OsRng;
         let signing_key: SigningKey = SigningKey::generate(&mut csprng);
-        let verifying_key = signing_key.verifying_key();
 
-        Ok(Daemon {
+        Ok(Self::from_signing_key(&signing_key))
+    }
+
+    // 从 24 个单词的 BIP39 助记词恢复 Daemon 身份
+    pub fn from_mnemonic(phrase: &str) -> anyhow::Result<Self> {
+        let mnemonic = Mnemonic::parse_normalized(phrase.trim()).context("解析助记词失败")?;
+        let seed: [u8; 32] = mnemonic
+            .to_entropy()
+            .as_slice()
+            .try_into()
+            .map_err(|_| anyhow!("助记词必须为 24 个单词"))?;
+
+        Ok(Self::from_signing_key(&SigningKey::from_bytes(&seed)))
+    }
+
+    // 将 32 字节私钥种子导出为 BIP39 助记词
+    pub fn to_mnemonic(&self) -> anyhow::Result<String> {
+        let seed = BASE64_URL_SAFE.decode(self.private_key.clone())?;
+        let mnemonic = Mnemonic::from_entropy(&seed).context("生成助记词失败")?;
+
+        Ok(mnemonic.to_string())
+    }
+
+    fn from_signing_key(signing_key: &SigningKey) -> Self {
+        Daemon {
             private_key: BASE64_URL_SAFE.encode(signing_key.as_bytes()),
-            public_key: BASE64_URL_SAFE.encode(verifying_key.as_bytes()),
-        })
+            public_key: BASE64_URL_SAFE.encode(signing_key.verifying_key().as_bytes()),
+        }
     }
 
     pub fn sign(&self, base64_url_data_string: String) -> anyhow::Result<SignBox> {
+        self.sign_bytes(&BASE64_URL_SAFE.decode(base64_url_data_string)?)
+    }
+
+    // 对 domain || data 签名
+    pub fn sign_in_domain(&self, domain: &[u8], data: &[u8]) -> anyhow::Result<SignBox> {
+        self.sign_bytes(&[domain, data].concat())
+    }
+
+    fn sign_bytes(&self, data_bytes: &[u8]) -> anyhow::Result<SignBox> {
         let mut signing_key = SigningKey::from_bytes(
             BASE64_URL_SAFE
                 .decode(self.private_key.clone())?
@@ -37,8 +85,7 @@ impl Daemon {
                 .try_into()?,
         );
 
-        let data_bytes = BASE64_URL_SAFE.decode(base64_url_data_string)?;
-        let sig = signing_key.sign(&data_bytes);
+        let sig = signing_key.sign(data_bytes);
 
         Ok(SignBox {
             public_key: self.public_key.clone(),
@@ -47,7 +94,7 @@ impl Daemon {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, TS)]
 pub struct SignBox {
     pub public_key: String,
     pub signature: String,
@@ -55,6 +102,14 @@ pub struct SignBox {
 
 impl SignBox {
     pub fn verify(&self, base64_url_data_string: String) -> anyhow::Result<()> {
+        self.verify_bytes(&BASE64_URL_SAFE.decode(base64_url_data_string)?)
+    }
+
+    pub fn verify_in_domain(&self, domain: &[u8], data: &[u8]) -> anyhow::Result<()> {
+        self.verify_bytes(&[domain, data].concat())
+    }
+
+    fn verify_bytes(&self, data_bytes: &[u8]) -> anyhow::Result<()> {
         let verifying_key = VerifyingKey::from_bytes(
             BASE64_URL_SAFE
                 .decode(self.public_key.clone())?
@@ -65,11 +120,79 @@ impl SignBox {
         let signature: Signature =
             Signature::from_slice(&BASE64_URL_SAFE.decode(&self.signature)?)?;
 
-        match verifying_key
-            .verify_strict(&BASE64_URL_SAFE.decode(base64_url_data_string)?, &signature)
-        {
+        match verifying_key.verify_strict(data_bytes, &signature) {
             Ok(()) => Ok(()),
             Err(_) => Err(anyhow!("签名校验不通过")),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data(bytes: &[u8]) -> String {
+        BASE64_URL_SAFE.encode(bytes)
+    }
+
+    #[test]
+    fn sign_and_verify_round_trip() {
+        let daemon = Daemon::new_random().unwrap();
+        let sign_box = daemon.sign(data(b"hello")).unwrap();
+
+        assert_eq!(sign_box.public_key, daemon.public_key);
+        sign_box.verify(data(b"hello")).unwrap();
+    }
+
+    #[test]
+    fn verify_rejects_other_data() {
+        let daemon = Daemon::new_random().unwrap();
+        let sign_box = daemon.sign(data(b"hello")).unwrap();
+
+        assert!(sign_box.verify(data(b"hello!")).is_err());
+    }
+
+    #[test]
+    fn verify_rejects_other_public_key() {
+        let daemon = Daemon::new_random().unwrap();
+        let mut sign_box = daemon.sign(data(b"hello")).unwrap();
+        sign_box.public_key = Daemon::new_random().unwrap().public_key;
+
+        assert!(sign_box.verify(data(b"hello")).is_err());
+    }
+
+    #[test]
+    fn domain_signature_differs_from_plain_signature() {
+        let daemon = Daemon::new_random().unwrap();
+        let sign_box = daemon.sign_in_domain(SUCCESSION_DOMAIN, b"hello").unwrap();
+
+        sign_box
+            .verify_in_domain(SUCCESSION_DOMAIN, b"hello")
+            .unwrap();
+        assert!(sign_box.verify(data(b"hello")).is_err());
+        assert!(is_reserved(&[SUCCESSION_DOMAIN, b"hello"].concat()));
+        assert!(!is_reserved(b"hello"));
+    }
+
+    #[test]
+    fn mnemonic_round_trip() {
+        let daemon = Daemon::new_random().unwrap();
+        let mnemonic = daemon.to_mnemonic().unwrap();
+        let restored = Daemon::from_mnemonic(&format!("  {}\n", mnemonic)).unwrap();
+
+        assert_eq!(mnemonic.split_whitespace().count(), 24);
+        assert_eq!(restored.public_key, daemon.public_key);
+        assert_eq!(restored.private_key, daemon.private_key);
+    }
+
+    #[test]
+    fn from_mnemonic_rejects_invalid_phrase() {
+        let mnemonic = Daemon::new_random().unwrap().to_mnemonic().unwrap();
+        let mut words: Vec<&str> = mnemonic.split_whitespace().collect();
+
+        words.pop();
+        assert!(Daemon::from_mnemonic(&words.join(" ")).is_err());
+        words.push("notaword");
+        assert!(Daemon::from_mnemonic(&words.join(" ")).is_err());
+    }
+}
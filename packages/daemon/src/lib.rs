@@ -1,2 +1,6 @@
+pub mod config;
 pub mod daemon;
+pub mod protocol;
 pub mod service;
+pub mod succession;
+pub mod tls;
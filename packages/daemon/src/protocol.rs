@@ -0,0 +1,372 @@
+use std::{fs, path::Path};
+
+use plugin::models::{LogRecord, Plugin};
+
+use schemars::{schema_for, JsonSchema};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+// /api/connect 协议版本，不兼容的修改需要增加版本号
+// 版本 1：Plugin 变化时发送完整的 daemon 快照
+// 版本 2：Plugin 变化时发送带序号的增量事件，重连时可以从上次的序号继续
+pub const PROTOCOL_VERSION: u32 = 2;
+pub const SUPPORTED_VERSIONS: [u32; 2] = [1, PROTOCOL_VERSION];
+
+// Daemon 发送给客户端的消息
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema, TS)]
+#[serde(tag = "type", content = "payload")]
+pub enum ServerMessage {
+    // 连接建立后首先发送，列出 Daemon 支持的协议版本
+    #[serde(rename = "hello")]
+    Hello(Hello),
+    // 版本协商完成，之后才会发送其他消息
+    #[serde(rename = "ready")]
+    Ready(Ready),
+    #[serde(rename = "daemon")]
+    Daemon(DaemonSnapshot),
+    #[serde(rename = "plugin/added")]
+    PluginAdded(PluginAdded),
+    #[serde(rename = "plugin/removed")]
+    PluginRemoved(PluginRemoved),
+    #[serde(rename = "plugin/updated")]
+    PluginUpdated(PluginUpdated),
+    #[serde(rename = "plugin/status")]
+    PluginStatus(PluginStatusChanged),
+    #[serde(rename = "confirm/install-plugin")]
+    ConfirmInstallPlugin(InstallPluginRequest),
+    #[serde(rename = "confirm/delete-plugin")]
+    ConfirmDeletePlugin(DeletePluginRequest),
+    // 对客户端 request 的回应，通过 id 对应
+    #[serde(rename = "response")]
+    Response(RpcResponse),
+    // Plugin 推送的消息，只发送给订阅了对应主题的连接
+    #[serde(rename = "notification")]
+    Notification(PluginNotification),
+    #[serde(rename = "error")]
+    Error(ProtocolError),
+}
+
+// 客户端发送给 Daemon 的消息
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema, TS)]
+#[serde(tag = "type", content = "payload")]
+pub enum ClientMessage {
+    // 回应 Daemon 的 hello，列出客户端支持的协议版本
+    #[serde(rename = "hello")]
+    Hello(Hello),
+    #[serde(rename = "confirm/install-plugin")]
+    ConfirmInstallPlugin(InstallPluginResponse),
+    #[serde(rename = "confirm/delete-plugin")]
+    ConfirmDeletePlugin(DeletePluginResponse),
+    // 调用 Daemon 的操作，结果以相同 id 的 response 返回
+    #[serde(rename = "request")]
+    Request(RpcRequest),
+    // 取消尚未完成的 request
+    #[serde(rename = "cancel")]
+    Cancel(RpcCancel),
+    // 订阅 Plugin 推送的消息
+    #[serde(rename = "subscribe")]
+    Subscribe(Subscription),
+    #[serde(rename = "unsubscribe")]
+    Unsubscribe(Subscription),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema, TS)]
+pub struct Hello {
+    pub versions: Vec<u32>,
+    // 客户端重连时携带上次收到的事件位置，Daemon 会尽量只补发之后的事件
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub resume: Option<Resume>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema, TS)]
+pub struct Resume {
+    pub epoch: String,
+    #[ts(type = "number")]
+    pub seq: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema, TS)]
+pub struct Ready {
+    pub version: u32,
+    // Daemon 进程的标识，Daemon 重启后事件序号重新计数
+    pub epoch: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema, TS)]
+pub struct DaemonSnapshot {
+    // 快照对应的事件序号
+    #[ts(type = "number")]
+    pub seq: u64,
+    pub public_key: String,
+    pub plugins: Vec<Plugin>,
+    // 最近一次健康探测的结果，尚未探测的 Plugin 不在其中
+    #[serde(default)]
+    pub health: Vec<PluginHealth>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema, TS)]
+pub struct PluginAdded {
+    #[ts(type = "number")]
+    pub seq: u64,
+    pub plugin: Plugin,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema, TS)]
+pub struct PluginRemoved {
+    #[ts(type = "number")]
+    pub seq: u64,
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema, TS)]
+pub struct PluginUpdated {
+    #[ts(type = "number")]
+    pub seq: u64,
+    pub plugin: Plugin,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema, TS)]
+pub struct PluginStatusChanged {
+    #[ts(type = "number")]
+    pub seq: u64,
+    pub name: String,
+    pub status: PluginStatus,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, JsonSchema, TS)]
+#[serde(rename_all = "kebab-case")]
+pub enum PluginStatus {
+    Online,
+    Offline,
+    // 仍然在线，但健康探测失败
+    Unhealthy,
+}
+
+// Daemon 最近一次探测 Plugin 健康状态的结果
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema, TS)]
+pub struct PluginHealth {
+    pub name: String,
+    pub healthy: bool,
+    // 不健康的原因
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub message: Option<String>,
+    // 探测时间，Unix 时间戳（毫秒）
+    #[ts(type = "number")]
+    pub checked_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema, TS)]
+pub struct InstallPluginRequest {
+    // 上传的安装包文件名，用于匹配用户的确认结果
+    pub name: String,
+    pub plugin: Plugin,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema, TS)]
+pub struct InstallPluginResponse {
+    pub name: String,
+    pub allow: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema, TS)]
+pub struct DeletePluginRequest {
+    pub plugin: Plugin,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema, TS)]
+pub struct DeletePluginResponse {
+    pub name: String,
+    pub allow: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema, TS)]
+pub struct Subscription {
+    pub plugin: String,
+    // 为空时表示该 Plugin 的全部主题
+    #[serde(default)]
+    pub topics: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema, TS)]
+pub struct PluginNotification {
+    pub plugin: String,
+    pub topic: String,
+    pub payload: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema, TS)]
+pub struct RpcRequest {
+    // 由客户端生成，同一连接中未完成的 request 不能重复
+    pub id: String,
+    #[serde(flatten)]
+    pub call: RpcCall,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema, TS)]
+#[serde(tag = "method", content = "params")]
+pub enum RpcCall {
+    #[serde(rename = "plugin/list")]
+    ListPlugins,
+    #[serde(rename = "plugin/install")]
+    InstallPlugin(InstallPluginParams),
+    #[serde(rename = "plugin/delete")]
+    DeletePlugin(DeletePluginParams),
+    #[serde(rename = "logs/query")]
+    QueryLogs(QueryLogsParams),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema, TS)]
+pub struct InstallPluginParams {
+    // 安装包文件名，与 HTTP 上传时的文件名作用相同
+    pub file_name: String,
+    // Base64 编码的安装包内容
+    pub package: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema, TS)]
+pub struct DeletePluginParams {
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema, TS)]
+pub struct QueryLogsParams {
+    pub plugin: String,
+    // 返回最后的若干行
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub limit: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema, TS)]
+pub struct RpcCancel {
+    pub id: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema, TS)]
+pub struct RpcResponse {
+    pub id: String,
+    #[serde(flatten)]
+    pub outcome: RpcOutcome,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema, TS)]
+#[serde(rename_all = "kebab-case")]
+pub enum RpcOutcome {
+    Result(RpcResult),
+    Error(RpcError),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema, TS)]
+#[serde(tag = "method", content = "data")]
+pub enum RpcResult {
+    #[serde(rename = "plugin/list")]
+    ListPlugins(PluginList),
+    #[serde(rename = "plugin/install")]
+    InstallPlugin(PluginInstalled),
+    #[serde(rename = "plugin/delete")]
+    DeletePlugin(PluginDeleted),
+    #[serde(rename = "logs/query")]
+    QueryLogs(PluginLogs),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema, TS)]
+pub struct PluginList {
+    pub plugins: Vec<Plugin>,
+    #[serde(default)]
+    pub health: Vec<PluginHealth>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema, TS)]
+pub struct PluginInstalled {
+    pub plugin: Box<Plugin>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema, TS)]
+pub struct PluginDeleted {
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema, TS)]
+pub struct PluginLogs {
+    // 按时间顺序排列
+    pub records: Vec<LogRecord>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema, TS)]
+pub struct RpcError {
+    pub code: RpcErrorCode,
+    pub message: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, JsonSchema, TS)]
+#[serde(rename_all = "kebab-case")]
+pub enum RpcErrorCode {
+    // 参数错误或 id 重复
+    InvalidRequest,
+    NotFound,
+    // 用户拒绝了确认请求
+    Rejected,
+    Cancelled,
+    Unsupported,
+    Internal,
+}
+
+impl RpcError {
+    pub fn new(code: RpcErrorCode, message: impl ToString) -> Self {
+        RpcError {
+            code,
+            message: message.to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema, TS)]
+pub struct ProtocolError {
+    pub code: ProtocolErrorCode,
+    pub message: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, JsonSchema, TS)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProtocolErrorCode {
+    UnsupportedVersion,
+    HandshakeRequired,
+    InvalidMessage,
+}
+
+impl ServerMessage {
+    pub fn error(code: ProtocolErrorCode, message: impl ToString) -> Self {
+        ServerMessage::Error(ProtocolError {
+            code,
+            message: message.to_string(),
+        })
+    }
+}
+
+// 选择双方都支持的最高版本
+pub fn negotiate(versions: &[u32]) -> Option<u32> {
+    versions
+        .iter()
+        .filter(|version| SUPPORTED_VERSIONS.contains(version))
+        .max()
+        .copied()
+}
+
+// 导出 JSON Schema 与 TypeScript 定义，供前端使用
+pub fn export(out_dir: &Path) -> anyhow::Result<()> {
+    fs::create_dir_all(out_dir)?;
+    fs::write(
+        out_dir.join("server-message.schema.json"),
+        serde_json::to_string_pretty(&schema_for!(ServerMessage))?,
+    )?;
+    fs::write(
+        out_dir.join("client-message.schema.json"),
+        serde_json::to_string_pretty(&schema_for!(ClientMessage))?,
+    )?;
+    ServerMessage::export_all_to(out_dir)?;
+    ClientMessage::export_all_to(out_dir)?;
+
+    Ok(())
+}
@@ -0,0 +1,39 @@
+use std::collections::{HashMap, VecDeque};
+
+use plugin::models::GuestError;
+
+// 每个 Plugin 最多保留的错误记录数量
+const ERROR_CAPACITY: usize = 100;
+
+// Guest 处理请求失败的记录，按 Plugin 分别保存
+pub struct ErrorLog {
+    errors: HashMap<String, VecDeque<GuestError>>,
+}
+
+impl ErrorLog {
+    pub fn new() -> Self {
+        ErrorLog {
+            errors: HashMap::new(),
+        }
+    }
+
+    pub fn push(&mut self, plugin: &str, error: GuestError) {
+        let errors = self.errors.entry(plugin.to_string()).or_default();
+        errors.push_back(error);
+        if errors.len() > ERROR_CAPACITY {
+            errors.pop_front();
+        }
+    }
+
+    // 按发生顺序返回 Plugin 的错误记录
+    pub fn list(&self, plugin: &str) -> Vec<GuestError> {
+        match self.errors.get(plugin) {
+            Some(errors) => errors.iter().cloned().collect(),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn remove(&mut self, plugin: &str) {
+        self.errors.remove(plugin);
+    }
+}
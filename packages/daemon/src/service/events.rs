@@ -0,0 +1,68 @@
+use std::collections::VecDeque;
+
+use base64::prelude::*;
+use rand::RngCore;
+
+use crate::protocol::ServerMessage;
+
+// 最多保留的事件数量，更早的事件需要客户端重新获取快照
+const EVENT_CAPACITY: usize = 256;
+
+// Plugin 变化事件的记录，用于客户端重连时补发
+pub struct EventLog {
+    // Daemon 进程的标识，序号只在同一个 epoch 内有意义
+    pub epoch: String,
+    seq: u64,
+    events: VecDeque<(u64, ServerMessage)>,
+}
+
+impl EventLog {
+    pub fn new() -> Self {
+        let mut epoch = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut epoch);
+
+        EventLog {
+            epoch: BASE64_URL_SAFE_NO_PAD.encode(epoch),
+            seq: 0,
+            events: VecDeque::new(),
+        }
+    }
+
+    pub fn seq(&self) -> u64 {
+        self.seq
+    }
+
+    pub fn push(&mut self, event: impl FnOnce(u64) -> ServerMessage) -> ServerMessage {
+        self.seq += 1;
+        let message = event(self.seq);
+        self.events.push_back((self.seq, message.clone()));
+        if self.events.len() > EVENT_CAPACITY {
+            self.events.pop_front();
+        }
+
+        message
+    }
+
+    // 返回 seq 之后的全部事件，所需事件已被丢弃或 seq 无效时返回 None
+    pub fn since(&self, seq: u64) -> Option<Vec<ServerMessage>> {
+        if seq > self.seq {
+            return None;
+        }
+        let first = self
+            .events
+            .front()
+            .map(|(seq, _)| *seq)
+            .unwrap_or(self.seq + 1);
+        if seq + 1 < first {
+            return None;
+        }
+
+        Some(
+            self.events
+                .iter()
+                .filter(|(event_seq, _)| *event_seq > seq)
+                .map(|(_, message)| message.clone())
+                .collect(),
+        )
+    }
+}
@@ -1,36 +1,80 @@
-use std::time::Duration;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 
 use anyhow::Context;
 use axum::extract::ws::{Message, WebSocket};
-use plugin::models::Plugin;
-use serde_json::json;
-use tokio::{sync::broadcast::Sender, time};
+use tokio::{
+    sync::{
+        broadcast::Sender,
+        mpsc::{self, error::TrySendError, Receiver},
+        Mutex,
+    },
+    task::AbortHandle,
+    time,
+};
+use tracing::Instrument;
 
-use crate::service::DaemonServer;
+use crate::{
+    protocol::{
+        ClientMessage, ProtocolErrorCode, RpcError, RpcErrorCode, RpcOutcome, RpcRequest,
+        RpcResponse, ServerMessage, Subscription,
+    },
+    service::DaemonServer,
+};
+
+use super::rpc;
+
+// 每个连接最多缓存的待发送消息数量
+const SEND_QUEUE_CAPACITY: usize = 1024;
 
 pub struct Connection {
     terminate: Sender<()>,
-    sender_channel: Sender<Message>,
-    pub receive_channel: Sender<Message>,
+    // 待发送的消息，在 handle 之前发送的消息会被保留
+    sender_channel: Outbox,
+    sender_receiver: Mutex<Option<Receiver<Message>>>,
+    pub receive_channel: Sender<ClientMessage>,
+    // 订阅的 Plugin 推送主题，None 表示该 Plugin 的全部主题
+    subscriptions: std::sync::Mutex<HashMap<String, Option<HashSet<String>>>>,
+    // 尚未完成的 request，用于取消
+    requests: Arc<std::sync::Mutex<HashMap<String, AbortHandle>>>,
+    // 握手时协商的协议版本
+    pub version: u32,
 }
 
 impl Connection {
-    pub fn new() -> Self {
+    pub fn new(version: u32) -> Self {
+        let (sender, sender_receiver) = mpsc::channel(SEND_QUEUE_CAPACITY);
+        let terminate = Sender::new(4);
         Connection {
-            terminate: Sender::new(4),
-            sender_channel: Sender::new(4),
+            sender_channel: Outbox {
+                sender,
+                terminate: terminate.clone(),
+            },
+            terminate,
+            sender_receiver: Mutex::new(Some(sender_receiver)),
             receive_channel: Sender::new(4),
+            subscriptions: std::sync::Mutex::new(HashMap::new()),
+            requests: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            version,
         }
     }
 
     pub async fn handle(
         &self,
         websocket: &mut WebSocket,
-        server: &DaemonServer,
+        server: &Arc<DaemonServer>,
     ) -> anyhow::Result<&str> {
         let mut terminate_sub = self.terminate.subscribe();
-        let mut sender_sub = self.sender_channel.subscribe();
+        let mut sender_sub = self
+            .sender_receiver
+            .lock()
+            .await
+            .take()
+            .context("Connection 已在处理中")?;
 
+        let heartbeat = server.config.heartbeat;
         tokio::spawn({
             let terminate = self.terminate.clone();
             let sender_channel = self.sender_channel.clone();
@@ -38,8 +82,10 @@ impl Connection {
                 let mut terminate_sub = terminate.subscribe();
                 loop {
                     tokio::select! {
-                        _ = time::sleep(Duration::from_secs(5)) => {
-                            sender_channel.send(Message::Ping(Vec::new())).expect("failed to send Ping message");
+                        _ = time::sleep(heartbeat.connect_ping_interval()) => {
+                            if sender_channel.send(Message::Ping(Vec::new())).is_err() {
+                                break;
+                            }
                         },
                         _ = terminate_sub.recv() => break,
                     }
@@ -47,8 +93,6 @@ impl Connection {
             }
         });
 
-        self.send_daemon(server).await?;
-
         loop {
             tokio::select! {
                 recv = websocket.recv() => {
@@ -58,42 +102,169 @@ impl Connection {
                     };
                     match message {
                         Message::Close(_) => anyhow::bail!("接收到关闭请求"),
-                        _ => ()
-                    };
-                    let _ = self.receive_channel.send(message);
+                        Message::Text(text) => match serde_json::from_str::<ClientMessage>(&text) {
+                            Ok(ClientMessage::Hello(_)) => self.send(&ServerMessage::error(
+                                ProtocolErrorCode::InvalidMessage,
+                                "握手已完成",
+                            ))?,
+                            Ok(ClientMessage::Request(request)) => self.request(server.clone(), request)?,
+                            Ok(ClientMessage::Cancel(cancel)) => self.cancel(&cancel.id)?,
+                            Ok(ClientMessage::Subscribe(subscription)) => self.subscribe(subscription),
+                            Ok(ClientMessage::Unsubscribe(subscription)) => self.unsubscribe(subscription),
+                            Ok(message) => {
+                                let _ = self.receive_channel.send(message);
+                            }
+                            Err(e) => self.send(&ServerMessage::error(
+                                ProtocolErrorCode::InvalidMessage,
+                                format!("解析消息失败：{}", e),
+                            ))?,
+                        },
+                        _ => (),
+                    }
                 },
-                _ = time::sleep(Duration::from_secs(12)) => anyhow::bail!("连接超时"),
+                _ = time::sleep(heartbeat.connect_timeout()) => anyhow::bail!("连接超时"),
                 _ = terminate_sub.recv() => anyhow::bail!("连接从内部关闭"),
                 message = sender_sub.recv() => {
-                    websocket.send(message?).await.context("发送消息失败")?;
+                    let message = message.context("发送通道已关闭")?;
+                    websocket.send(message).await.context("发送消息失败")?;
                 },
             }
         }
     }
 
-    pub async fn send_daemon(&self, server: &DaemonServer) -> anyhow::Result<()> {
-        self.sender_channel
-            .send(Message::Text(
-                serde_json::to_string(&json!({
-                    "type": "daemon",
-                    "payload": {
-                        "public_key": server.daemon.public_key,
-                        "plugins": server.plugins.lock().await.values().collect::<Vec<&Plugin>>(),
-                    },
-                }))
-                .context("serilize daemon json failed")?,
-            ))
-            .context("send message by channel failed")?;
+    pub fn send(&self, message: &ServerMessage) -> anyhow::Result<()> {
+        send_message(&self.sender_channel, message)
+    }
 
-        Ok(())
+    fn subscribe(&self, subscription: Subscription) {
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        if subscription.topics.is_empty() {
+            subscriptions.insert(subscription.plugin, None);
+            return;
+        }
+        let topics = subscriptions
+            .entry(subscription.plugin)
+            .or_insert_with(|| Some(HashSet::new()));
+        if let Some(topics) = topics {
+            topics.extend(subscription.topics);
+        }
+    }
+
+    // 取消订阅指定主题，topics 为空时取消该 Plugin 的全部订阅；
+    // 已订阅全部主题时只能整体取消
+    fn unsubscribe(&self, subscription: Subscription) {
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        if subscription.topics.is_empty() {
+            subscriptions.remove(&subscription.plugin);
+            return;
+        }
+        if let Some(Some(topics)) = subscriptions.get_mut(&subscription.plugin) {
+            for topic in subscription.topics.iter() {
+                topics.remove(topic);
+            }
+            if topics.is_empty() {
+                subscriptions.remove(&subscription.plugin);
+            }
+        }
     }
 
-    pub fn send_message(&self, message: Message) -> anyhow::Result<()> {
-        self.sender_channel.send(message)?;
+    pub fn is_subscribed(&self, plugin: &str, topic: &str) -> bool {
+        match self.subscriptions.lock().unwrap().get(plugin) {
+            Some(None) => true,
+            Some(Some(topics)) => topics.contains(topic),
+            None => false,
+        }
+    }
+
+    // 在后台执行 request，完成后发送相同 id 的 response
+    fn request(&self, server: Arc<DaemonServer>, request: RpcRequest) -> anyhow::Result<()> {
+        let RpcRequest { id, call } = request;
+        let mut requests = self.requests.lock().unwrap();
+        if requests.contains_key(&id) {
+            return self.respond(
+                id.clone(),
+                RpcOutcome::Error(RpcError::new(
+                    RpcErrorCode::InvalidRequest,
+                    format!("request id 重复：{}", id),
+                )),
+            );
+        }
+
+        let task = tokio::spawn({
+            let span = tracing::info_span!("rpc", id = %id);
+            let id = id.clone();
+            let requests = self.requests.clone();
+            let sender_channel = self.sender_channel.clone();
+            async move {
+                let outcome = match rpc::call(server, call).await {
+                    Ok(result) => RpcOutcome::Result(result),
+                    Err(error) => {
+                        tracing::info!(code = ?error.code, "request 失败：{}", error.message);
+                        RpcOutcome::Error(error)
+                    }
+                };
+                requests.lock().unwrap().remove(&id);
+                let _ = send_message(
+                    &sender_channel,
+                    &ServerMessage::Response(RpcResponse { id, outcome }),
+                );
+            }
+            .instrument(span)
+        });
+        requests.insert(id, task.abort_handle());
+
         Ok(())
     }
 
+    // 取消尚未完成的 request，已完成或不存在的 request 会被忽略
+    fn cancel(&self, id: &str) -> anyhow::Result<()> {
+        let task = self.requests.lock().unwrap().remove(id);
+        match task {
+            Some(task) => {
+                task.abort();
+                self.respond(
+                    id.to_string(),
+                    RpcOutcome::Error(RpcError::new(RpcErrorCode::Cancelled, "request 已取消")),
+                )
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn respond(&self, id: String, outcome: RpcOutcome) -> anyhow::Result<()> {
+        self.send(&ServerMessage::Response(RpcResponse { id, outcome }))
+    }
+
     pub async fn stop(&self) {
         let _ = self.terminate.send(());
+        for (_, task) in self.requests.lock().unwrap().drain() {
+            task.abort();
+        }
     }
 }
+
+// 待发送消息的队列，客户端读取过慢导致队列已满时断开连接，而不是丢弃消息，
+// 客户端重连后可以通过 resume 补发事件或重新获取快照
+#[derive(Clone)]
+struct Outbox {
+    sender: mpsc::Sender<Message>,
+    terminate: Sender<()>,
+}
+
+impl Outbox {
+    fn send(&self, message: Message) -> anyhow::Result<()> {
+        match self.sender.try_send(message) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(_)) => {
+                let _ = self.terminate.send(());
+                anyhow::bail!("待发送的消息过多，连接已关闭")
+            }
+            Err(TrySendError::Closed(_)) => anyhow::bail!("send message by channel failed"),
+        }
+    }
+}
+
+fn send_message(sender_channel: &Outbox, message: &ServerMessage) -> anyhow::Result<()> {
+    let message = serde_json::to_string(message).context("serilize message failed")?;
+    sender_channel.send(Message::Text(message))
+}
@@ -1,5 +1,6 @@
 use std::{borrow::Cow, ops::Deref, ptr, sync::Arc};
 
+use anyhow::bail;
 use axum::{
     extract::{
         ws::{CloseFrame, Message, WebSocket},
@@ -7,9 +8,16 @@ use axum::{
     },
     response::IntoResponse,
 };
-use serde_json::json;
 
-use crate::service::DaemonServer;
+use tracing::Instrument;
+
+use crate::{
+    protocol::{
+        negotiate, ClientMessage, Hello, ProtocolErrorCode, Ready, Resume, ServerMessage,
+        SUPPORTED_VERSIONS,
+    },
+    service::DaemonServer,
+};
 
 use super::Connection;
 
@@ -17,40 +25,65 @@ pub async fn connect_handler(
     State(server): State<Arc<DaemonServer>>,
     ws: WebSocketUpgrade,
 ) -> impl IntoResponse {
-    ws.on_upgrade(|mut socket| async move {
-        match handle_connection(&mut socket, server).await {
-            Ok(()) => (),
-            Err(e) => {
-                let _ = socket
-                    .send(Message::Close(Some(CloseFrame {
-                        code: 1000,
-                        reason: Cow::from(e.to_string()),
-                    })))
-                    .await;
+    // request 中的安装包以 Base64 编码，需要放宽单条消息的大小
+    let max_message_size = server.config.plugin.max_upload_size / 3 * 4 + 64 * 1024;
+    // 连接在升级后的任务中处理，span 挂在发起升级的 HTTP 请求之下
+    let span = tracing::info_span!("connect", version = tracing::field::Empty);
+    ws.max_message_size(max_message_size)
+        .max_frame_size(max_message_size)
+        .on_upgrade(|mut socket| {
+            async move {
+                match handle_connection(&mut socket, server).await {
+                    Ok(()) => (),
+                    Err(e) => {
+                        tracing::info!("Connection 握手失败：{}", e);
+                        let _ = socket
+                            .send(Message::Close(Some(CloseFrame {
+                                code: 1000,
+                                reason: Cow::from(e.to_string()),
+                            })))
+                            .await;
+                    }
+                };
             }
-        };
-    })
+            .instrument(span)
+        })
 }
 
 async fn handle_connection(
     socket: &mut WebSocket,
     server: Arc<DaemonServer>,
 ) -> anyhow::Result<()> {
+    let (version, resume) = handshake(socket, &server).await?;
+
     // 创建 Connection
-    socket
-        .send(Message::Text(serde_json::to_string(
-            &json!({"type": "ok"}),
-        )?))
-        .await?;
+    let connection = Arc::new(Connection::new(version));
+    tracing::Span::current().record("version", connection.version);
+    tracing::info!("Connection 已建立");
 
-    let connection = Arc::new(Connection::new());
-    server.connections.lock().await.push(connection.clone());
+    // 持有事件锁完成初始同步并登记连接，保证之后的事件不会遗漏或重复
+    {
+        let events = server.events.lock().await;
+        let replay = match resume {
+            Some(resume) if version >= 2 && resume.epoch == events.epoch => {
+                events.since(resume.seq)
+            }
+            _ => None,
+        };
+        match replay {
+            Some(messages) => {
+                for message in messages.iter() {
+                    connection.send(message)?;
+                }
+            }
+            None => connection.send(&ServerMessage::Daemon(server.snapshot(events.seq()).await))?,
+        }
+        server.connections.lock().await.push(connection.clone());
+    }
 
     match connection.handle(socket, &server).await {
         Ok(_) => (),
-        Err(e) => {
-            println!("Connection 已断开，原因：{:?}", e);
-        }
+        Err(e) => tracing::info!("Connection 已断开，原因：{:?}", e),
     }
     connection.stop().await;
 
@@ -70,3 +103,81 @@ async fn handle_connection(
 
     Ok(())
 }
+
+// 交换 hello 消息并协商协议版本，失败时先发送 error 消息再关闭连接；
+// 返回协商的版本与客户端请求的补发位置
+async fn handshake(
+    socket: &mut WebSocket,
+    server: &DaemonServer,
+) -> anyhow::Result<(u32, Option<Resume>)> {
+    send(
+        socket,
+        &ServerMessage::Hello(Hello {
+            versions: SUPPORTED_VERSIONS.to_vec(),
+            resume: None,
+        }),
+    )
+    .await?;
+
+    let message = tokio::time::timeout(server.config.heartbeat.connect_timeout(), async {
+        loop {
+            match socket.recv().await {
+                Some(Ok(Message::Text(text))) => break Ok(text),
+                Some(Ok(Message::Close(_))) | None => bail!("接收到关闭请求"),
+                Some(Ok(_)) => continue,
+                Some(Err(e)) => break Err(e.into()),
+            }
+        }
+    })
+    .await
+    .map_err(|_| anyhow::anyhow!("等待 hello 消息超时"))??;
+
+    let Hello { versions, resume } = match serde_json::from_str::<ClientMessage>(&message) {
+        Ok(ClientMessage::Hello(hello)) => hello,
+        Ok(_) => {
+            let error = "连接建立后需要先发送 hello 消息";
+            send(
+                socket,
+                &ServerMessage::error(ProtocolErrorCode::HandshakeRequired, error),
+            )
+            .await?;
+            bail!(error);
+        }
+        Err(e) => {
+            let error = format!("解析 hello 消息失败：{}", e);
+            send(
+                socket,
+                &ServerMessage::error(ProtocolErrorCode::InvalidMessage, &error),
+            )
+            .await?;
+            bail!(error);
+        }
+    };
+
+    match negotiate(&versions) {
+        Some(version) => {
+            let epoch = server.events.lock().await.epoch.clone();
+            send(socket, &ServerMessage::Ready(Ready { version, epoch })).await?;
+            Ok((version, resume))
+        }
+        None => {
+            let error = format!(
+                "不支持的协议版本：{:?}，Daemon 支持的版本：{:?}",
+                versions, SUPPORTED_VERSIONS
+            );
+            send(
+                socket,
+                &ServerMessage::error(ProtocolErrorCode::UnsupportedVersion, &error),
+            )
+            .await?;
+            bail!(error);
+        }
+    }
+}
+
+async fn send(socket: &mut WebSocket, message: &ServerMessage) -> anyhow::Result<()> {
+    socket
+        .send(Message::Text(serde_json::to_string(message)?))
+        .await?;
+    Ok(())
+}
@@ -1,5 +1,6 @@
 mod connection;
 mod handler;
+mod rpc;
 
 pub use connection::Connection;
 pub use handler::connect_handler;
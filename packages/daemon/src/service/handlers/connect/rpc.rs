@@ -0,0 +1,88 @@
+use std::sync::Arc;
+
+use base64::prelude::*;
+
+use crate::{
+    protocol::{
+        PluginDeleted, PluginInstalled, PluginList, PluginLogs, RpcCall, RpcError, RpcErrorCode,
+        RpcResult,
+    },
+    service::{
+        handlers::plugin::{
+            delete_plugin, install_plugin, is_valid_file_name, DEFAULT_TAIL, MAX_TAIL,
+        },
+        DaemonServer,
+    },
+};
+
+// 执行客户端通过 /api/connect 发起的调用
+pub async fn call(server: Arc<DaemonServer>, call: RpcCall) -> Result<RpcResult, RpcError> {
+    match call {
+        RpcCall::ListPlugins => {
+            let plugins = server.plugins.lock().await.values().cloned().collect();
+            let health = server.health.lock().await.list();
+            Ok(RpcResult::ListPlugins(PluginList { plugins, health }))
+        }
+        RpcCall::InstallPlugin(params) => {
+            if !is_valid_file_name(&params.file_name) {
+                return Err(invalid_request(format!(
+                    "安装包文件名无效：{}",
+                    params.file_name
+                )));
+            }
+            let package = BASE64_STANDARD
+                .decode(&params.package)
+                .map_err(|e| invalid_request(format!("安装包不是有效的 Base64：{}", e)))?;
+            if package.len() > server.config.plugin.max_upload_size {
+                return Err(invalid_request(format!(
+                    "安装包超过大小上限：{}",
+                    server.config.plugin.max_upload_size
+                )));
+            }
+
+            match install_plugin(server, params.file_name, &mut package.as_slice()).await {
+                Ok(Some(plugin)) => Ok(RpcResult::InstallPlugin(PluginInstalled {
+                    plugin: Box::new(plugin),
+                })),
+                Ok(None) => Err(RpcError::new(RpcErrorCode::Rejected, "用户拒绝安装插件")),
+                Err(e) => Err(internal(e)),
+            }
+        }
+        RpcCall::DeletePlugin(params) => {
+            if !server.plugins.lock().await.contains_key(&params.name) {
+                return Err(RpcError::new(
+                    RpcErrorCode::NotFound,
+                    format!("Plugin 不存在：{}", params.name),
+                ));
+            }
+
+            match delete_plugin(server, params.name.clone()).await {
+                Ok(true) => Ok(RpcResult::DeletePlugin(PluginDeleted { name: params.name })),
+                Ok(false) => Err(RpcError::new(RpcErrorCode::Rejected, "用户拒绝删除插件")),
+                Err(e) => Err(internal(e)),
+            }
+        }
+        RpcCall::QueryLogs(params) => {
+            let limit = params
+                .limit
+                .map(|limit| limit as usize)
+                .unwrap_or(DEFAULT_TAIL)
+                .min(MAX_TAIL);
+            match server.tail_logs(&params.plugin, limit).await {
+                Some(records) => Ok(RpcResult::QueryLogs(PluginLogs { records })),
+                None => Err(RpcError::new(
+                    RpcErrorCode::NotFound,
+                    format!("Plugin 不存在：{}", params.plugin),
+                )),
+            }
+        }
+    }
+}
+
+fn invalid_request(message: String) -> RpcError {
+    RpcError::new(RpcErrorCode::InvalidRequest, message)
+}
+
+fn internal(e: anyhow::Error) -> RpcError {
+    RpcError::new(RpcErrorCode::Internal, format!("{:#}", e))
+}
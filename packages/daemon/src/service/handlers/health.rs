@@ -0,0 +1,32 @@
+use std::sync::{atomic::Ordering, Arc};
+
+use axum::{extract::State, http::StatusCode, Json};
+use serde_json::{json, Value};
+
+use crate::service::DaemonServer;
+
+// 存活检查，Daemon 能够处理请求即返回成功
+pub async fn health_handler() -> Json<Value> {
+    Json(json!({ "status": "ok" }))
+}
+
+// 就绪检查：本地 Plugin 已全部启动，且前端资源存在
+pub async fn ready_handler(State(server): State<Arc<DaemonServer>>) -> (StatusCode, Json<Value>) {
+    let plugins = server.ready.load(Ordering::Acquire);
+    let assets = server.assets_path().join("index.html").is_file();
+    let status = match plugins && assets {
+        true => StatusCode::OK,
+        false => StatusCode::SERVICE_UNAVAILABLE,
+    };
+
+    (
+        status,
+        Json(json!({
+            "ready": plugins && assets,
+            "checks": {
+                "plugins": plugins,
+                "assets": assets,
+            },
+        })),
+    )
+}
@@ -0,0 +1,46 @@
+use std::sync::Arc;
+
+use axum::{extract::State, http::header, response::IntoResponse};
+use plugin::metrics::{self, Family};
+
+use crate::service::DaemonServer;
+
+// 以 Prometheus 文本格式输出 Daemon 与本地 Plugin 的指标；
+// 远程 Plugin 的指标由其 Plugin 服务的 /.plat/metrics 提供
+pub async fn metrics_handler(State(server): State<Arc<DaemonServer>>) -> impl IntoResponse {
+    let mut out = String::new();
+
+    let connections = server.connections.lock().await.len();
+    Family::new(
+        &mut out,
+        "plat_connections",
+        "gauge",
+        "当前活跃的 /api/connect 连接数量",
+    )
+    .sample(&[], connections);
+
+    let local_plugins: Vec<(String, metrics::MetricsSnapshot)> = server
+        .local_plugins
+        .lock()
+        .await
+        .iter()
+        .map(|(name, local_plugin)| (name.clone(), local_plugin.metrics()))
+        .collect();
+    let registered = server.plugins.lock().await.len();
+    let mut family = Family::new(
+        &mut out,
+        "plat_plugins",
+        "gauge",
+        "已注册的 Plugin 数量，kind 为 local 或 remote",
+    );
+    family.sample(&[("kind", "local")], local_plugins.len());
+    family.sample(
+        &[("kind", "remote")],
+        registered.saturating_sub(local_plugins.len()),
+    );
+
+    server.proxy_metrics.lock().await.encode(&mut out);
+    metrics::encode_plugins(&mut out, &local_plugins);
+
+    ([(header::CONTENT_TYPE, metrics::CONTENT_TYPE)], out)
+}
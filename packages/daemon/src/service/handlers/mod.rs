@@ -1,10 +1,19 @@
 mod connect;
+mod health;
+mod metrics;
 mod plugin;
+mod proxy;
 mod regist;
 mod sig;
 mod verify;
 
 pub use connect::{connect_handler, Connection};
-pub use plugin::{delete_plugin_handler, install_plugin_handler, list_plugin_handler};
+pub use health::{health_handler, ready_handler};
+pub use metrics::metrics_handler;
+pub use plugin::{
+    delete_plugin_handler, install_plugin_handler, list_plugin_handler, plugin_errors_handler,
+    plugin_logs_handler, plugin_schedules_handler,
+};
+pub use proxy::{plugin_prefix, proxy_handler, proxy_redirect_handler};
 pub use regist::regist_handler;
 pub use sig::sig_handler;
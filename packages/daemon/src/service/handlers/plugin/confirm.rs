@@ -0,0 +1,23 @@
+use tokio::task::AbortHandle;
+
+// 等待用户确认的监听任务，离开作用域时结束这些任务，
+// 安装或删除的调用被取消时监听任务不会遗留
+pub struct ConfirmListeners(Vec<AbortHandle>);
+
+impl ConfirmListeners {
+    pub fn new() -> Self {
+        ConfirmListeners(Vec::new())
+    }
+
+    pub fn push(&mut self, handle: AbortHandle) {
+        self.0.push(handle);
+    }
+}
+
+impl Drop for ConfirmListeners {
+    fn drop(&mut self) {
+        for handle in self.0.iter() {
+            handle.abort();
+        }
+    }
+}
@@ -1,79 +1,62 @@
 use std::{collections::HashMap, fs, sync::Arc};
 
+use anyhow::{bail, Context};
 use axum::{
-    extract::{ws::Message, Query, State},
+    extract::{Query, State},
     Json,
 };
-use plugin::models::Plugin;
-use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use tokio::sync::broadcast::Sender;
+use tracing::Instrument;
 
-use crate::service::{
-    typings::{AppError, ConnectionMessage},
-    DaemonServer,
+use crate::{
+    protocol::{ClientMessage, DeletePluginRequest, ServerMessage},
+    service::{typings::AppError, DaemonServer},
 };
 
+use super::confirm::ConfirmListeners;
+
 pub async fn delete_plugin_handler(
     State(server): State<Arc<DaemonServer>>,
     Query(query): Query<HashMap<String, String>>,
 ) -> Result<Json<Value>, AppError> {
     let name = query.get("name").unwrap().clone();
-    try_delete_plugin_handler(server, name).await?;
+    delete_plugin(server, name).await?;
     Ok(Json(json!({"complete": true})))
 }
 
-async fn try_delete_plugin_handler(
-    server: Arc<DaemonServer>,
-    name: String,
-) -> anyhow::Result<bool> {
+// 删除插件，需要用户确认；用户拒绝时返回 false
+#[tracing::instrument(skip(server), err)]
+pub async fn delete_plugin(server: Arc<DaemonServer>, name: String) -> anyhow::Result<bool> {
     // 从 Plugins 中读取需要删除的插件信息并发送给用户
-    let plugin = { server.plugins.lock().await.get(&name).unwrap().clone() };
-    {
-        let event = ConnectionMessage {
-            ty: String::from("confirm/delete-plugin"),
-            payload: serde_json::to_value(DeletePluginRequest {
-                plugin: plugin.clone(),
-            })?,
-        };
-        let message = Message::Text(serde_json::to_string(&event)?);
-        for connection in server.connections.lock().await.iter() {
-            connection.send_message(message.clone())?;
-        }
+    let plugin = match server.plugins.lock().await.get(&name) {
+        Some(plugin) => plugin.clone(),
+        None => bail!("Plugin 不存在：{}", name),
+    };
+    let request = ServerMessage::ConfirmDeletePlugin(DeletePluginRequest { plugin });
+    for connection in server.connections.lock().await.iter() {
+        connection.send(&request)?;
     }
 
     let allow_chan: Sender<bool> = Sender::new(4);
     let mut allow_result = allow_chan.subscribe();
+    let mut listeners = ConfirmListeners::new();
     for connection in server.connections.lock().await.iter() {
         let connection = connection.clone();
         let allow_chan = allow_chan.clone();
         let name = name.clone();
-        tokio::spawn(async move {
+        let listener = tokio::spawn(async move {
             let mut receive_sub = connection.receive_channel.subscribe();
             let mut allow_sub = allow_chan.subscribe();
             loop {
                 tokio::select! {
                     _ = allow_sub.recv() => break,
                     message = receive_sub.recv() => {
-                        let message = match message {
-                            Ok(value) => value,
+                        let delete_plugin_response = match message {
+                            Ok(ClientMessage::ConfirmDeletePlugin(value)) => value,
+                            Ok(_) => continue,
                             Err(_) => break,
                         };
-                        let content = match message {
-                            Message::Text(content) => content,
-                            _ => continue,
-                        };
-                        let connection_message: ConnectionMessage = match serde_json::from_str(&content) {
-                            Ok(value) => value,
-                            Err(_) => continue,
-                        };
-                        if connection_message.ty != "confirm/delete-plugin" {
-                            continue;
-                        }
-                        let delete_plugin_response: DeletePluginResponse = match serde_json::from_value(connection_message.payload) {
-                            Ok(value) => value,
-                            Err(_) => continue,
-                        };
                         if delete_plugin_response.name != name {
                             continue;
                         }
@@ -82,35 +65,40 @@ async fn try_delete_plugin_handler(
                 }
             }
         });
+        listeners.push(listener.abort_handle());
     }
 
-    if !allow_result.recv().await? {
+    let allow = allow_result.recv().await?;
+    drop(listeners);
+    if !allow {
+        tracing::info!("用户拒绝删除");
         return Ok(false);
     }
 
-    let _ = server.plugins.lock().await.remove(&name);
-    match server.plugin_servers.lock().await.remove(&name) {
-        None => (),
-        Some(plugin_server) => {
-            plugin_server.stop().await;
-            fs::remove_dir_all(plugin_server.path)?;
-        }
-    }
-
-    for connection in server.connections.lock().await.iter() {
-        connection.send_daemon(&server).await?;
-    }
+    // 与安装相同，用户确认后的删除在独立的任务中完成，调用被取消时不会只删除一部分
+    let task = tokio::spawn(finish_delete(server, name).instrument(tracing::Span::current()));
+    task.await.context("删除插件的任务异常退出")??;
 
     Ok(true)
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct DeletePluginRequest {
-    plugin: Plugin,
-}
+async fn finish_delete(server: Arc<DaemonServer>, name: String) -> anyhow::Result<()> {
+    server.remove_plugin(&name).await;
+    server.errors.lock().await.remove(&name);
+    server.logs.lock().await.remove(&name);
+    server.proxy_metrics.lock().await.remove(&name);
+    server.health.lock().await.remove(&name);
+    server.schedules.lock().await.remove(&name);
+    let local_plugin = server.local_plugins.lock().await.remove(&name);
+    if let Some(local_plugin) = local_plugin {
+        local_plugin.stop().await;
+        fs::remove_dir_all(&local_plugin.path)?;
+    }
+    let data_path = server.plugin_data_path(&name);
+    if data_path.exists() {
+        fs::remove_dir_all(data_path)?;
+    }
+    tracing::info!("插件已删除");
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct DeletePluginResponse {
-    name: String,
-    allow: bool,
+    Ok(())
 }
@@ -0,0 +1,24 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+
+use crate::service::DaemonServer;
+
+// 返回 Plugin 最近的 Guest 错误记录
+pub async fn plugin_errors_handler(
+    State(server): State<Arc<DaemonServer>>,
+    Path(name): Path<String>,
+) -> Response {
+    if !server.plugins.lock().await.contains_key(&name) {
+        return (StatusCode::NOT_FOUND, "未找到指定的 Plugin").into_response();
+    }
+    let errors = server.errors.lock().await.list(&name);
+
+    Json(json!({ "errors": errors })).into_response()
+}
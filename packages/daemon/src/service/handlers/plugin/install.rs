@@ -1,29 +1,32 @@
 use std::{
     fs::{self},
+    path::PathBuf,
     sync::Arc,
 };
 
 use anyhow::{bail, Context};
 use axum::{
-    extract::{ws::Message, Multipart, State},
+    extract::{Multipart, State},
     Json,
 };
 use futures::TryStreamExt;
-use plugin::{models::Plugin, Options, PluginServer};
-use serde::{Deserialize, Serialize};
+use plugin::models::Plugin;
 use serde_json::{json, Value};
 use tokio::{
     fs::File,
-    io::{self, BufWriter},
+    io::{self, AsyncRead, AsyncWriteExt, BufWriter},
     sync::broadcast::Sender,
 };
 use tokio_util::io::StreamReader;
+use tracing::Instrument;
 
-use crate::service::{
-    typings::{AppError, ConnectionMessage},
-    DaemonServer,
+use crate::{
+    protocol::{ClientMessage, InstallPluginRequest, ServerMessage},
+    service::{typings::AppError, DaemonServer},
 };
 
+use super::confirm::ConfirmListeners;
+
 pub async fn install_plugin_handler(
     State(server): State<Arc<DaemonServer>>,
     multipart: Multipart,
@@ -47,67 +50,84 @@ async fn try_install_plugin(
         None => bail!("读取Plugin文件名称失败"),
     };
 
-    // 将用户上传的插件复制至 cache 文件夹，并解压到对应目录
+    let mut reader =
+        StreamReader::new(field.map_err(|err| io::Error::new(io::ErrorKind::Other, err)));
+    let plugin = install_plugin(server, file_name, &mut reader).await?;
+
+    Ok(Json(json!({"complete": plugin.is_some()})))
+}
+
+// 安装包文件名会作为 cache 下的目录名，不能包含路径
+pub fn is_valid_file_name(file_name: &str) -> bool {
+    !file_name.contains(['/', '\\']) && !matches!(file_name, "" | "." | "..")
+}
+
+// 安装插件包并在 Daemon 进程内启动，需要用户确认；用户拒绝时返回 None
+#[tracing::instrument(skip(server, package), err)]
+pub async fn install_plugin<R>(
+    server: Arc<DaemonServer>,
+    file_name: String,
+    package: &mut R,
+) -> anyhow::Result<Option<Plugin>>
+where
+    R: AsyncRead + Unpin,
+{
+    if !is_valid_file_name(&file_name) {
+        bail!("插件安装包文件名无效：{}", file_name);
+    }
+
+    // 将用户上传的插件复制至 cache 文件夹，并解压到对应目录；
+    // 清理上次被取消的安装留下的文件
     let cache_dir = server.root_path.join(".cache").join(&file_name);
-    if !cache_dir.exists() {
-        fs::create_dir_all(&cache_dir)?;
+    if cache_dir.exists() {
+        fs::remove_dir_all(&cache_dir)?;
     }
+    fs::create_dir_all(&cache_dir)?;
 
     let tar_file_path = cache_dir.join("plugin.tar.gz");
     let mut tar_file = BufWriter::new(File::create(&tar_file_path).await?);
-    let mut reader =
-        StreamReader::new(field.map_err(|err| io::Error::new(io::ErrorKind::Other, err)));
-    io::copy(&mut reader, &mut tar_file).await?;
+    io::copy(package, &mut tar_file).await?;
+    tar_file.flush().await?;
     let out_dir = cache_dir.join("out");
     bundler::plugin::untar(tar_file_path, out_dir.clone())?;
 
     // 读取插件信息
     let plugin: Plugin = serde_json::from_slice(&fs::read(out_dir.join("plugin.json"))?)?;
-    let connection_message = ConnectionMessage {
-        ty: String::from("confirm/install-plugin"),
-        payload: serde_json::to_value(InstallPluginRequest {
-            name: file_name.clone(),
-            plugin: plugin.clone(),
-        })?,
-    };
+    if !server.plugins.lock().await.contains_key(&plugin.name) {
+        if let Err(e) = server.check_plugin_limit().await {
+            fs::remove_dir_all(&cache_dir)?;
+            return Err(e);
+        }
+    }
+    tracing::info!(plugin = %plugin.name, "等待用户确认安装");
+    let request = ServerMessage::ConfirmInstallPlugin(InstallPluginRequest {
+        name: file_name.clone(),
+        plugin: plugin.clone(),
+    });
 
     // 获取 Connection，请求用户确认
-    let message = Message::Text(serde_json::to_string(&connection_message)?);
     for connection in server.connections.lock().await.iter() {
-        connection.send_message(message.clone())?;
+        connection.send(&request)?;
     }
     let allow_chan: Sender<bool> = Sender::new(4);
     let mut allow_result = allow_chan.subscribe();
+    let mut listeners = ConfirmListeners::new();
     for connection in server.connections.lock().await.iter() {
         let connection = connection.clone();
         let allow_chan = allow_chan.clone();
         let name = file_name.clone();
-        tokio::spawn(async move {
+        let listener = tokio::spawn(async move {
             let mut receive_sub = connection.receive_channel.subscribe();
             let mut allow_sub = allow_chan.subscribe();
             loop {
                 tokio::select! {
                     _ = allow_sub.recv() => break,
                     message = receive_sub.recv() => {
-                        let message = match message {
-                            Ok(value) => value,
+                        let install_plugin_response = match message {
+                            Ok(ClientMessage::ConfirmInstallPlugin(value)) => value,
+                            Ok(_) => continue,
                             Err(_) => break,
                         };
-                        let content = match message {
-                            Message::Text(content) => content,
-                            _ => continue,
-                        };
-                        let connection_message: ConnectionMessage = match serde_json::from_str(&content) {
-                            Ok(value) => value,
-                            Err(_) => continue,
-                        };
-                        if connection_message.ty != "confirm/install-plugin" {
-                            continue;
-                        }
-                        let install_plugin_response: InstallPluginResponse = match serde_json::from_value(connection_message.payload) {
-                            Ok(value) => value,
-                            Err(_) => continue,
-                        };
                         if install_plugin_response.name != name {
                             continue;
                         }
@@ -116,23 +136,43 @@ async fn try_install_plugin(
                 }
             }
         });
+        listeners.push(listener.abort_handle());
     }
 
     // 根据返回的结果安装或取消安装插件
-    if !allow_result.recv().await? {
+    let allow = allow_result.recv().await?;
+    drop(listeners);
+    if !allow {
+        tracing::info!(plugin = %plugin.name, "用户拒绝安装");
         fs::remove_dir_all(&cache_dir)?;
-        return Ok(Json(json!({"complete": false})));
+        return Ok(None);
     }
 
+    // 用户确认后在独立的任务中移动并启动插件，调用被取消时安装仍会完成，
+    // 不会留下只移动了一半的插件目录或未注册的插件
+    let task = tokio::spawn(
+        finish_install(server, plugin, cache_dir, out_dir).instrument(tracing::Span::current()),
+    );
+    let plugin = task.await.context("安装插件的任务异常退出")??;
+
+    Ok(Some(plugin))
+}
+
+async fn finish_install(
+    server: Arc<DaemonServer>,
+    plugin: Plugin,
+    cache_dir: PathBuf,
+    out_dir: PathBuf,
+) -> anyhow::Result<Plugin> {
     let plugin_dir = server
-        .root_path
-        .join("plugins")
-        .join(&urlencoding::encode(&plugin.name).to_string());
+        .plugins_path()
+        .join(urlencoding::encode(&plugin.name).to_string());
     let plugins_dir = plugin_dir.parent().unwrap();
     if !plugins_dir.exists() {
         fs::create_dir_all(plugins_dir)?;
     }
-    if plugin_dir.exists() {
+    let upgrade = plugin_dir.exists();
+    if upgrade {
         let storage_dir = plugin_dir.join("storage");
         if storage_dir.exists() {
             fs::rename(storage_dir, out_dir.join("storage")).context("移动 Storage 目录失败")?
@@ -142,37 +182,19 @@ async fn try_install_plugin(
     fs::rename(&out_dir, &plugin_dir).context("移动插件至插件目录失败")?;
     fs::remove_dir_all(&cache_dir)?;
 
-    // 启动插件
-    let plugin_server = PluginServer::new(
-        plugin_dir.join("plugin.json"),
-        Options {
-            port: 0,
-            daemon_address: server.address.clone(),
-            regist_address: None,
-        },
-    )
-    .await
-    .context("启动插件失败")?;
-    server
-        .plugin_servers
-        .lock()
-        .await
-        .insert(plugin_server.plugin().name.clone(), plugin_server);
-    for connection in server.connections.lock().await.iter() {
-        connection.send_daemon(&server).await?;
+    // 在 Daemon 进程内启动插件
+    let local_plugin = server
+        .create_local_plugin(plugin_dir.clone())
+        .context("启动插件失败")?;
+    let plugin = local_plugin.plugin().clone();
+    // 登记时才最终确认名称冲突与数量上限，新安装的插件被拒绝时移除其目录
+    if let Err(e) = server.register_local_plugin(local_plugin).await {
+        if !upgrade {
+            fs::remove_dir_all(&plugin_dir)?;
+        }
+        return Err(e);
     }
+    tracing::info!(plugin = %plugin.name, "插件安装完成");
 
-    Ok(Json(json!({"complete": true})))
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct InstallPluginRequest {
-    name: String,
-    plugin: Plugin,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct InstallPluginResponse {
-    name: String,
-    allow: bool,
+    Ok(plugin)
 }
@@ -9,8 +9,10 @@ use crate::service::DaemonServer;
 pub async fn list_plugin_handler(State(service): State<Arc<DaemonServer>>) -> Json<Value> {
     let registed_plugins = service.plugins.lock().await;
     let plugins: Vec<&Plugin> = registed_plugins.values().collect();
+    let health = service.health.lock().await.list();
     let plugins = json!({
         "plugins": &plugins,
+        "health": health,
     });
 
     Json(plugins)
@@ -0,0 +1,67 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    Json,
+};
+use futures::StreamExt;
+use serde::Deserialize;
+use serde_json::json;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::service::DaemonServer;
+
+// 未指定时返回的日志数量
+pub const DEFAULT_TAIL: usize = 100;
+// 单次最多返回的日志数量
+pub const MAX_TAIL: usize = 10000;
+
+#[derive(Deserialize)]
+pub struct LogsQuery {
+    // 返回最后的若干条日志
+    tail: Option<usize>,
+    // 返回历史日志后以 SSE 持续推送新日志
+    #[serde(default)]
+    follow: bool,
+}
+
+pub async fn plugin_logs_handler(
+    State(server): State<Arc<DaemonServer>>,
+    Path(name): Path<String>,
+    Query(query): Query<LogsQuery>,
+) -> Response {
+    let limit = query.tail.unwrap_or(DEFAULT_TAIL).min(MAX_TAIL);
+    // 先订阅再读取历史日志，避免两者之间的日志丢失
+    let (Some(receiver), Some(records)) = (
+        server.subscribe_logs(&name).await,
+        server.tail_logs(&name, limit).await,
+    ) else {
+        return (StatusCode::NOT_FOUND, "未找到指定的 Plugin").into_response();
+    };
+    if !query.follow {
+        return Json(json!({ "records": records })).into_response();
+    }
+
+    let history = futures::stream::iter(records);
+    let live = futures::stream::unfold(receiver, |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(record) => return Some((record, receiver)),
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+    let events = history
+        .chain(live)
+        .map(|record| Event::default().json_data(record));
+
+    Sse::new(events)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
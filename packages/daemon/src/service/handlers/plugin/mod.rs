@@ -1,7 +1,14 @@
+mod confirm;
 mod delete;
+mod errors;
 mod install;
 mod list;
+mod logs;
+mod schedules;
 
-pub use delete::delete_plugin_handler;
-pub use install::install_plugin_handler;
+pub use delete::{delete_plugin, delete_plugin_handler};
+pub use errors::plugin_errors_handler;
+pub use install::{install_plugin, install_plugin_handler, is_valid_file_name};
 pub use list::list_plugin_handler;
+pub use logs::{plugin_logs_handler, DEFAULT_TAIL, MAX_TAIL};
+pub use schedules::plugin_schedules_handler;
@@ -0,0 +1,41 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::{json, Value};
+
+use crate::service::DaemonServer;
+
+// 返回 Plugin 声明的定时任务及其执行状态
+pub async fn plugin_schedules_handler(
+    State(server): State<Arc<DaemonServer>>,
+    Path(name): Path<String>,
+) -> Response {
+    let Some(plugin) = server.plugins.lock().await.get(&name).cloned() else {
+        return (StatusCode::NOT_FOUND, "未找到指定的 Plugin").into_response();
+    };
+    let states = server.schedules.lock().await;
+    let schedules: Vec<Value> = plugin
+        .schedules
+        .unwrap_or_default()
+        .into_iter()
+        .map(|schedule| {
+            let state = states.get(&name, &schedule.name);
+            json!({
+                "name": schedule.name,
+                "cron": schedule.cron,
+                "interval": schedule.interval,
+                "timeout": schedule.timeout,
+                "running": state.running_since.is_some(),
+                "running_since": state.running_since,
+                "last_run": state.last_run,
+            })
+        })
+        .collect();
+
+    Json(json!({ "schedules": schedules })).into_response()
+}
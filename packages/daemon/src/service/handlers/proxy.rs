@@ -0,0 +1,391 @@
+use std::{borrow::Cow, collections::HashMap, sync::Arc, time::Instant};
+
+use anyhow::Context;
+use axum::{
+    body::Body,
+    extract::{
+        ws::{CloseFrame, Message, WebSocket},
+        FromRequestParts, Path, Request, State, WebSocketUpgrade,
+    },
+    http::{
+        header::{self, HeaderName},
+        HeaderMap, HeaderValue, StatusCode, Uri,
+    },
+    response::{IntoResponse, Redirect, Response},
+};
+use futures::{SinkExt, StreamExt};
+use plugin::LocalPlugin;
+use tokio_tungstenite::{
+    tungstenite::{self, client::IntoClientRequest, protocol::frame::coding::CloseCode},
+    Connector,
+};
+
+use crate::service::{proxy::ProxyClient, DaemonServer};
+
+// 逐跳首部不应被代理转发
+const HOP_BY_HOP_HEADERS: [HeaderName; 8] = [
+    header::CONNECTION,
+    HeaderName::from_static("keep-alive"),
+    header::PROXY_AUTHENTICATE,
+    header::PROXY_AUTHORIZATION,
+    header::TE,
+    header::TRAILER,
+    header::TRANSFER_ENCODING,
+    header::UPGRADE,
+];
+
+pub async fn proxy_redirect_handler(Path(name): Path<String>) -> Redirect {
+    Redirect::permanent(&format!("{}/", plugin_prefix(&name)))
+}
+
+pub async fn proxy_handler(
+    State(server): State<Arc<DaemonServer>>,
+    Path(params): Path<HashMap<String, String>>,
+    req: Request,
+) -> Response {
+    let name = params.get("name").cloned().unwrap_or_default();
+    let path = params.get("path").cloned().unwrap_or_default();
+
+    let start = Instant::now();
+    let response = proxy_request(&server, &name, &path, req).await;
+    // 只记录已注册的 Plugin，避免任意名称产生新的指标
+    if server.plugins.lock().await.contains_key(&name) {
+        server.proxy_metrics.lock().await.observe(
+            &name,
+            response.status().as_u16(),
+            start.elapsed(),
+        );
+    }
+    response
+}
+
+async fn proxy_request(
+    server: &Arc<DaemonServer>,
+    name: &str,
+    path: &str,
+    req: Request,
+) -> Response {
+    // 进程内的 Plugin 直接分发请求，无需经过网络
+    let local_plugin = server.local_plugins.lock().await.get(name).cloned();
+    if let Some(local_plugin) = local_plugin {
+        return match dispatch_local(server, name, &local_plugin, path, req).await {
+            Ok(response) => response,
+            Err(e) => {
+                tracing::warn!(plugin = %name, "分发请求失败：{:?}", e);
+                (StatusCode::BAD_GATEWAY, format!("plugin error: {:?}", e)).into_response()
+            }
+        };
+    }
+
+    let (address, fingerprint) = match server.plugins.lock().await.get(name) {
+        Some(plugin) => (plugin.address.clone(), plugin.tls_fingerprint.clone()),
+        None => (None, None),
+    };
+    let address = match address {
+        Some(address) => address,
+        None => return (StatusCode::NOT_FOUND, "未找到指定的 Plugin").into_response(),
+    };
+    let upstream = upstream_uri(&address, path, req.uri());
+    let client = match server.proxy.client(fingerprint.as_deref()) {
+        Ok(client) => client,
+        Err(e) => {
+            tracing::warn!(plugin = %name, "创建代理客户端失败：{:?}", e);
+            return (StatusCode::BAD_GATEWAY, format!("proxy error: {:?}", e)).into_response();
+        }
+    };
+
+    // 进程内的 Plugin 自行完成 WebSocket 握手，只有远程 Plugin 需要由 Daemon 接受升级
+    let (mut parts, body) = req.into_parts();
+    let ws = WebSocketUpgrade::from_request_parts(&mut parts, server)
+        .await
+        .ok();
+    let req = Request::from_parts(parts, body);
+    let result = match ws {
+        Some(ws) => proxy_websocket(server, &client, name, upstream, ws, req).await,
+        None => proxy_http(server, &client, name, upstream, req).await,
+    };
+    match result {
+        Ok(response) => response,
+        Err(e) => {
+            tracing::warn!(plugin = %name, "代理请求失败：{:?}", e);
+            (StatusCode::BAD_GATEWAY, format!("proxy error: {:?}", e)).into_response()
+        }
+    }
+}
+
+async fn dispatch_local(
+    server: &DaemonServer,
+    name: &str,
+    local_plugin: &LocalPlugin,
+    path: &str,
+    req: Request,
+) -> anyhow::Result<Response> {
+    let (mut parts, body) = req.into_parts();
+    let mut headers = forward_headers(&parts.headers);
+    insert_forwarded_headers(&mut headers, &parts.headers, name, server.tls.is_some());
+    // 升级请求保留 Upgrade 与 Connection 首部，由 Plugin 决定是否接受
+    if parts.headers.contains_key(header::UPGRADE) {
+        for key in [header::UPGRADE, header::CONNECTION] {
+            if let Some(value) = parts.headers.get(&key) {
+                headers.insert(key, value.clone());
+            }
+        }
+    }
+
+    // 去掉代理前缀，保留请求中的 authority 供 Plugin 读取
+    let mut uri = Uri::builder();
+    if let Some(authority) = parts.uri.authority() {
+        uri = uri
+            .scheme(parts.uri.scheme_str().unwrap_or("http"))
+            .authority(authority.clone());
+    }
+    let path_and_query = match parts.uri.query() {
+        Some(query) => format!("/{}?{}", path, query),
+        None => format!("/{}", path),
+    };
+    parts.uri = uri.path_and_query(path_and_query).build()?;
+    parts.headers = headers;
+
+    let response = local_plugin
+        .handle_request(Request::from_parts(parts, body))
+        .await?;
+
+    let (mut parts, body) = response.into_parts();
+    if let Some(location) = parts.headers.get(header::LOCATION) {
+        let location = rewrite_location(name, location);
+        parts.headers.insert(header::LOCATION, location);
+    }
+    Ok(Response::from_parts(parts, Body::new(body)))
+}
+
+async fn proxy_http(
+    server: &DaemonServer,
+    client: &ProxyClient,
+    name: &str,
+    upstream: String,
+    req: Request,
+) -> anyhow::Result<Response> {
+    let (parts, body) = req.into_parts();
+    let mut headers = forward_headers(&parts.headers);
+    headers.remove(header::HOST);
+    insert_forwarded_headers(&mut headers, &parts.headers, name, server.tls.is_some());
+
+    let upstream_response = client
+        .client()
+        .request(parts.method, upstream)
+        .headers(headers)
+        .body(reqwest::Body::wrap_stream(body.into_data_stream()))
+        .send()
+        .await
+        .context("请求 Plugin 服务失败")?;
+
+    let mut response = Response::builder().status(upstream_response.status());
+    let response_headers = response.headers_mut().unwrap();
+    for (key, value) in forward_headers(upstream_response.headers()).iter() {
+        // 跨域首部统一由 Daemon 处理
+        if key.as_str().starts_with("access-control-") {
+            continue;
+        }
+        if key == header::LOCATION {
+            response_headers.append(key, rewrite_location(name, value));
+            continue;
+        }
+        response_headers.append(key, value.clone());
+    }
+
+    Ok(response.body(Body::from_stream(upstream_response.bytes_stream()))?)
+}
+
+async fn proxy_websocket(
+    server: &DaemonServer,
+    client: &ProxyClient,
+    name: &str,
+    upstream: String,
+    ws: WebSocketUpgrade,
+    req: Request,
+) -> anyhow::Result<Response> {
+    let upstream = upstream
+        .replacen("http://", "ws://", 1)
+        .replacen("https://", "wss://", 1);
+    let connector = match upstream.starts_with("wss://") {
+        true => Some(Connector::Rustls(client.websocket_tls())),
+        false => None,
+    };
+
+    let mut upstream_request = upstream.into_client_request()?;
+    let protocol = req.headers().get(header::SEC_WEBSOCKET_PROTOCOL).cloned();
+    if let Some(protocol) = protocol.as_ref() {
+        upstream_request
+            .headers_mut()
+            .insert(header::SEC_WEBSOCKET_PROTOCOL, protocol.clone());
+    }
+    insert_forwarded_headers(
+        upstream_request.headers_mut(),
+        req.headers(),
+        name,
+        server.tls.is_some(),
+    );
+
+    let (upstream_socket, upstream_response) =
+        tokio_tungstenite::connect_async_tls_with_config(upstream_request, None, false, connector)
+            .await
+            .context("连接 Plugin WebSocket 失败")?;
+
+    let ws = match upstream_response
+        .headers()
+        .get(header::SEC_WEBSOCKET_PROTOCOL)
+        .and_then(|value| value.to_str().ok())
+    {
+        Some(protocol) => ws.protocols([protocol.to_string()]),
+        None => ws,
+    };
+
+    Ok(ws.on_upgrade(move |socket| pipe_websocket(socket, upstream_socket)))
+}
+
+async fn pipe_websocket<S>(socket: WebSocket, upstream: tokio_tungstenite::WebSocketStream<S>)
+where
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+{
+    let (mut client_write, mut client_read) = socket.split();
+    let (mut upstream_write, mut upstream_read) = upstream.split();
+
+    loop {
+        tokio::select! {
+            message = client_read.next() => {
+                let message = match message {
+                    Some(Ok(message)) => message,
+                    _ => break,
+                };
+                let close = matches!(message, Message::Close(_));
+                if upstream_write.send(to_upstream_message(message)).await.is_err() || close {
+                    break;
+                }
+            },
+            message = upstream_read.next() => {
+                let message = match message {
+                    Some(Ok(message)) => message,
+                    _ => break,
+                };
+                let message = match to_client_message(message) {
+                    Some(message) => message,
+                    None => continue,
+                };
+                let close = matches!(message, Message::Close(_));
+                if client_write.send(message).await.is_err() || close {
+                    break;
+                }
+            },
+        }
+    }
+}
+
+fn to_upstream_message(message: Message) -> tungstenite::Message {
+    match message {
+        Message::Text(text) => tungstenite::Message::Text(text),
+        Message::Binary(data) => tungstenite::Message::Binary(data),
+        Message::Ping(data) => tungstenite::Message::Ping(data),
+        Message::Pong(data) => tungstenite::Message::Pong(data),
+        Message::Close(frame) => {
+            tungstenite::Message::Close(frame.map(|frame| tungstenite::protocol::CloseFrame {
+                code: CloseCode::from(frame.code),
+                reason: Cow::Owned(frame.reason.into_owned()),
+            }))
+        }
+    }
+}
+
+fn to_client_message(message: tungstenite::Message) -> Option<Message> {
+    match message {
+        tungstenite::Message::Text(text) => Some(Message::Text(text)),
+        tungstenite::Message::Binary(data) => Some(Message::Binary(data)),
+        tungstenite::Message::Ping(data) => Some(Message::Ping(data)),
+        tungstenite::Message::Pong(data) => Some(Message::Pong(data)),
+        tungstenite::Message::Close(frame) => Some(Message::Close(frame.map(|frame| CloseFrame {
+            code: frame.code.into(),
+            reason: Cow::Owned(frame.reason.into_owned()),
+        }))),
+        tungstenite::Message::Frame(_) => None,
+    }
+}
+
+pub fn plugin_prefix(name: &str) -> String {
+    format!("/plugins/{}", urlencoding::encode(name))
+}
+
+fn upstream_uri(address: &str, path: &str, uri: &Uri) -> String {
+    let mut upstream = format!("{}/{}", address.trim_end_matches('/'), path);
+    if let Some(query) = uri.query() {
+        upstream.push('?');
+        upstream.push_str(query);
+    }
+    upstream
+}
+
+// 同时移除 Connection 首部中列出的首部，它们同样只对当前连接有效
+fn forward_headers(headers: &HeaderMap) -> HeaderMap {
+    let mut forwarded = headers.clone();
+    let listed = headers
+        .get_all(header::CONNECTION)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|name| HeaderName::from_bytes(name.trim().as_bytes()).ok())
+        .collect::<Vec<_>>();
+    for name in HOP_BY_HOP_HEADERS.iter().chain(listed.iter()) {
+        forwarded.remove(name);
+    }
+    forwarded
+}
+
+fn insert_forwarded_headers(headers: &mut HeaderMap, origin: &HeaderMap, name: &str, https: bool) {
+    if let Ok(prefix) = HeaderValue::from_str(&plugin_prefix(name)) {
+        headers.insert("x-forwarded-prefix", prefix);
+    }
+    if let Some(host) = origin.get(header::HOST) {
+        headers.insert("x-forwarded-host", host.clone());
+    }
+    headers.insert(
+        "x-forwarded-proto",
+        HeaderValue::from_static(match https {
+            true => "https",
+            false => "http",
+        }),
+    );
+}
+
+// Plugin 返回的绝对路径重定向需要加上代理前缀
+fn rewrite_location(name: &str, location: &HeaderValue) -> HeaderValue {
+    let rewritten = location
+        .to_str()
+        .ok()
+        .filter(|location| location.starts_with('/') && !location.starts_with("//"))
+        .map(|location| format!("{}{}", plugin_prefix(name), location))
+        .and_then(|location| HeaderValue::from_str(&location).ok());
+
+    rewritten.unwrap_or(location.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn forward_headers_strips_connection_listed_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::CONNECTION,
+            HeaderValue::from_static("keep-alive, X-Secret"),
+        );
+        headers.append(header::CONNECTION, HeaderValue::from_static("x-other"));
+        headers.insert("x-secret", HeaderValue::from_static("1"));
+        headers.insert("x-other", HeaderValue::from_static("1"));
+        headers.insert(header::TE, HeaderValue::from_static("trailers"));
+        headers.insert(header::ACCEPT, HeaderValue::from_static("text/html"));
+
+        let forwarded = forward_headers(&headers);
+
+        assert_eq!(forwarded.len(), 1);
+        assert_eq!(forwarded.get(header::ACCEPT).unwrap(), "text/html");
+    }
+}
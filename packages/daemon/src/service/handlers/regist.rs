@@ -1,123 +1,460 @@
-use std::{borrow::Cow, sync::Arc, time::Duration};
+use std::{borrow::Cow, sync::Arc};
 
+use anyhow::{anyhow, bail};
 use axum::{
     extract::{
-        ws::{CloseFrame, Message},
+        ws::{CloseFrame, Message, WebSocket},
         State, WebSocketUpgrade,
     },
     response::IntoResponse,
 };
-use plugin::models::Plugin;
+use base64::prelude::*;
+use plugin::{
+    models::Plugin,
+    regist::{
+        challenge_data, RegistCredential, RegistPluginMessage, RegistRejectReason, RegistRequest,
+        RegistServerMessage,
+    },
+};
+use rand::RngCore;
+use tracing::Instrument;
 
-use crate::service::DaemonServer;
+use crate::{
+    config::RegistConfig,
+    daemon::SignBox,
+    protocol::PluginStatus,
+    service::{typings::InsertRejected, DaemonServer},
+};
+
+// 注册失败的原因，会以 Rejected 消息返回给 Plugin
+struct Rejection {
+    reason: RegistRejectReason,
+    message: String,
+}
+
+impl Rejection {
+    fn new(reason: RegistRejectReason, message: impl ToString) -> Self {
+        Rejection {
+            reason,
+            message: message.to_string(),
+        }
+    }
+}
 
 pub async fn regist_handler(
     State(service): State<Arc<DaemonServer>>,
     ws: WebSocketUpgrade,
 ) -> impl IntoResponse {
-    ws.on_upgrade(|mut socket| async move {
-        socket
-            .send(Message::Text(service.daemon.public_key.clone()))
-            .await
-            .unwrap();
+    let span = tracing::info_span!("regist", plugin = tracing::field::Empty);
+    ws.on_upgrade(|socket| handle_regist(service, socket).instrument(span))
+}
 
-        let data = match socket.recv().await.unwrap().unwrap() {
-            Message::Text(data) => data,
-            _ => panic!("Message failed"),
-        };
-        let plugin_config: Plugin = serde_json::from_str(&data).unwrap();
-        let name = plugin_config.name.clone();
-
-        if service.plugins.lock().await.contains_key(&name) {
-            socket
-                .send(Message::Close(Some(CloseFrame {
-                    code: 1000,
-                    reason: Cow::from("已存在相同名称的 Plugin"),
-                })))
-                .await
-                .unwrap();
+// 注册成功后转发 Plugin 发送的消息并保持心跳，连接断开时注销 Plugin
+async fn handle_regist(service: Arc<DaemonServer>, mut socket: WebSocket) {
+    let plugin_config = match accept_plugin(&service, &mut socket).await {
+        Ok(plugin_config) => plugin_config,
+        Err(rejection) => {
+            tracing::warn!(reason = ?rejection.reason, "拒绝 Plugin 注册：{}", rejection.message);
+            reject(socket, rejection).await;
             return;
         }
+    };
+    let name = plugin_config.name.clone();
+    tracing::Span::current().record("plugin", name.as_str());
+    tracing::info!("Plugin 已注册");
+
+    let heartbeat = service.config.heartbeat;
+    let (stop_sender, _rx) = tokio::sync::broadcast::channel::<()>(1);
+    let (send_sender, _rx) = tokio::sync::broadcast::channel::<Message>(16);
 
-        service
-            .plugins
-            .lock()
-            .await
-            .insert(name.clone(), plugin_config);
-
-        let (stop_sender, _rx) = tokio::sync::broadcast::channel::<()>(1);
-        let (send_sender, _rx) = tokio::sync::broadcast::channel::<Message>(16);
-
-        tokio::task::spawn({
-            let send_sender = send_sender.clone();
-            let stop_sender = stop_sender.clone();
-            async move {
-                let mut stop_sub = stop_sender.subscribe();
-                let mut send_sub = send_sender.subscribe();
-                loop {
-                    tokio::select! {
-                        message_option = socket.recv() => {
-                            match message_option {
-                                None => break,
-                                Some(message_result) => {
-                                    match message_result {
-                                        Err(_e) => break,
-                                        Ok(message) => {
-                                            match message {
-                                                Message::Close(_) => break,
-                                                _ => (),
+    tokio::task::spawn({
+        let service = service.clone();
+        let name = name.clone();
+        let send_sender = send_sender.clone();
+        let stop_sender = stop_sender.clone();
+        async move {
+            let mut stop_sub = stop_sender.subscribe();
+            let mut send_sub = send_sender.subscribe();
+            loop {
+                tokio::select! {
+                    message_option = socket.recv() => {
+                        match message_option {
+                            None => break,
+                            Some(message_result) => {
+                                match message_result {
+                                    Err(_e) => break,
+                                    Ok(Message::Close(_)) => break,
+                                    Ok(Message::Text(text)) => {
+                                        match serde_json::from_str::<RegistPluginMessage>(&text) {
+                                            Ok(RegistPluginMessage::Notify(notification)) => {
+                                                service.notify(&name, notification).await
+                                            }
+                                            Ok(RegistPluginMessage::GuestError(error)) => {
+                                                service.record_error(&name, error).await
+                                            }
+                                            Ok(RegistPluginMessage::Log(record)) => {
+                                                service.record_log(&name, record).await
                                             }
-                                        },
-                                    }
-                                },
-                            };
-                        },
-                        message_result = send_sub.recv() => {
-                            match message_result {
-                                Err(_) => break,
-                                Ok(message) => {
-                                    match socket.send(message).await {
-                                        Ok(_) => (),
-                                        Err(_e) => break,
-                                    }
+                                            Ok(RegistPluginMessage::ScheduleRun(run)) => {
+                                                service.record_schedule_run(&name, run).await
+                                            }
+                                            Err(_) => (),
+                                        }
+                                    },
+                                    Ok(_) => (),
+                                }
+                            },
+                        };
+                    },
+                    message_result = send_sub.recv() => {
+                        match message_result {
+                            Err(_) => break,
+                            Ok(message) => {
+                                match socket.send(message).await {
+                                    Ok(_) => (),
+                                    Err(_e) => break,
                                 }
                             }
-                        },
-                        _ = tokio::time::sleep(Duration::from_secs(10)) => break,
-                        _ = stop_sub.recv() => break,
-                    }
+                        }
+                    },
+                    _ = tokio::time::sleep(heartbeat.regist_timeout()) => break,
+                    _ = stop_sub.recv() => break,
                 }
-
-                let _ = stop_sender.send(());
             }
-        });
-        tokio::task::spawn({
-            let stop_sender = stop_sender.clone();
-            async move {
-                let mut sub = stop_sender.subscribe();
-                loop {
-                    tokio::select! {
-                        _ = tokio::time::sleep(Duration::from_secs(4)) => {
-                            send_sender.send(Message::Ping(Vec::new())).unwrap();
-                        },
-                        _ = sub.recv() => break,
-                    }
+
+            let _ = stop_sender.send(());
+        }
+    });
+    tokio::task::spawn({
+        let stop_sender = stop_sender.clone();
+        async move {
+            let mut sub = stop_sender.subscribe();
+            loop {
+                tokio::select! {
+                    _ = tokio::time::sleep(heartbeat.regist_ping_interval()) => {
+                        if send_sender.send(Message::Ping(Vec::new())).is_err() {
+                            break;
+                        }
+                    },
+                    _ = sub.recv() => break,
                 }
+            }
+
+            let _ = stop_sender.send(());
+        }
+    });
+
+    service
+        .publish_plugin_status(&name, PluginStatus::Online)
+        .await;
+
+    let _ = stop_sender.subscribe().recv().await;
+
+    if let Some(plugin) = service.remove_plugin(&name).await {
+        if let Some(fingerprint) = plugin.tls_fingerprint.as_deref() {
+            service.proxy.forget(fingerprint);
+        }
+    }
+    service.health.lock().await.remove(&name);
+    service.schedules.lock().await.interrupt(&name);
+    tracing::info!("Plugin 已断开");
+}
 
-                let _ = stop_sender.send(());
+// 完成注册握手：发送 Challenge，校验 Plugin 的注册凭证，并将其登记到 plugins
+async fn accept_plugin(
+    service: &DaemonServer,
+    socket: &mut WebSocket,
+) -> Result<Plugin, Rejection> {
+    let mut nonce = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut nonce);
+    let nonce = BASE64_URL_SAFE.encode(nonce);
+
+    send_message(
+        socket,
+        &RegistServerMessage::Challenge {
+            daemon_public_key: service.daemon.public_key.clone(),
+            nonce: nonce.clone(),
+            ping_interval: Some(service.config.heartbeat.regist_ping_interval),
+        },
+    )
+    .await
+    .map_err(|e| Rejection::new(RegistRejectReason::InvalidMessage, e))?;
+
+    let data = tokio::time::timeout(
+        service.config.heartbeat.regist_timeout(),
+        receive_text(socket),
+    )
+    .await
+    .map_err(|_| Rejection::new(RegistRejectReason::InvalidMessage, "等待注册请求超时"))?
+    .map_err(|e| Rejection::new(RegistRejectReason::InvalidMessage, e))?;
+    let request: RegistRequest = serde_json::from_str(&data).map_err(|e| {
+        Rejection::new(
+            RegistRejectReason::InvalidMessage,
+            format!("解析注册请求失败：{}", e),
+        )
+    })?;
+
+    let plugin_config = request.plugin;
+    authorize(
+        &service.config.regist,
+        &nonce,
+        &service.daemon.public_key,
+        &plugin_config,
+        request.credential,
+    )
+    .map_err(|e| Rejection::new(RegistRejectReason::Unauthorized, e))?;
+
+    let name = plugin_config.name.clone();
+    service
+        .insert_plugin(&plugin_config, false)
+        .await
+        .map_err(|e| match e {
+            InsertRejected::DuplicateName(_) => {
+                Rejection::new(RegistRejectReason::DuplicateName, e)
             }
-        });
+            InsertRejected::PluginLimit(_) => Rejection::new(RegistRejectReason::PluginLimit, e),
+        })?;
 
-        for connection in service.connections.lock().await.iter() {
-            let _ = connection.send_daemon(&service).await;
+    if let Err(e) = send_message(socket, &RegistServerMessage::Accepted).await {
+        service.remove_plugin(&name).await;
+        return Err(Rejection::new(RegistRejectReason::InvalidMessage, e));
+    }
+
+    Ok(plugin_config)
+}
+
+fn authorize(
+    config: &RegistConfig,
+    nonce: &str,
+    daemon_public_key: &str,
+    plugin: &Plugin,
+    credential: Option<RegistCredential>,
+) -> anyhow::Result<()> {
+    match credential {
+        None => bail!("缺少注册凭证"),
+        Some(RegistCredential::Token { token }) => {
+            let matched = config
+                .tokens
+                .iter()
+                .any(|expected| constant_time_eq(expected.as_bytes(), token.as_bytes()));
+            if !matched {
+                bail!("注册令牌无效");
+            }
+            Ok(())
         }
+        Some(RegistCredential::Signature {
+            public_key,
+            signature,
+        }) => {
+            if !config.authorized_keys.contains(&public_key) {
+                bail!("注册公钥未获授权：{}", public_key);
+            }
+            SignBox {
+                public_key,
+                signature,
+            }
+            .verify(challenge_data(nonce, daemon_public_key, plugin)?)
+        }
+    }
+}
 
-        let _ = stop_sender.subscribe().recv().await;
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
 
-        service.plugins.lock().await.remove(&name);
+async fn receive_text(socket: &mut WebSocket) -> anyhow::Result<String> {
+    loop {
+        match socket.recv().await {
+            Some(Ok(Message::Text(data))) => return Ok(data),
+            Some(Ok(Message::Ping(_))) | Some(Ok(Message::Pong(_))) => continue,
+            Some(Ok(Message::Binary(_))) => bail!("注册请求必须为文本消息"),
+            Some(Ok(Message::Close(_))) | None => bail!("Plugin 关闭了注册连接"),
+            Some(Err(e)) => return Err(anyhow!("读取注册请求失败：{}", e)),
+        }
+    }
+}
+
+async fn send_message(socket: &mut WebSocket, message: &RegistServerMessage) -> anyhow::Result<()> {
+    socket
+        .send(Message::Text(serde_json::to_string(message)?))
+        .await?;
+    Ok(())
+}
 
-        for connection in service.connections.lock().await.iter() {
-            let _ = connection.send_daemon(&service).await;
+// 发送 Rejected 消息后关闭连接，关闭原因为机器可读的拒绝原因
+async fn reject(mut socket: WebSocket, rejection: Rejection) {
+    let reason = serde_json::to_value(rejection.reason)
+        .ok()
+        .and_then(|value| value.as_str().map(String::from))
+        .unwrap_or_default();
+    let _ = send_message(
+        &mut socket,
+        &RegistServerMessage::Rejected {
+            reason: rejection.reason,
+            message: rejection.message,
+        },
+    )
+    .await;
+    let _ = socket
+        .send(Message::Close(Some(CloseFrame {
+            code: 1008,
+            reason: Cow::from(reason),
+        })))
+        .await;
+}
+
+#[cfg(test)]
+mod tests {
+    use plugin::regist::RegistKey;
+
+    use super::*;
+    use crate::daemon::Daemon;
+
+    fn nonce(byte: u8) -> String {
+        BASE64_URL_SAFE.encode([byte; 32])
+    }
+
+    fn plugin(name: &str) -> Plugin {
+        Plugin {
+            name: name.to_string(),
+            wasm_root: String::from("wasm"),
+            assets_root: String::from("assets"),
+            storage_root: String::from("storage"),
+            entries: Vec::new(),
+            address: Some(String::from("http://127.0.0.1:8000")),
+            tls_fingerprint: None,
+            static_files: None,
+            request_limits: None,
+            schedules: None,
+            sql_max_size: None,
+        }
+    }
+
+    fn config(key: &RegistKey) -> RegistConfig {
+        RegistConfig {
+            tokens: vec![String::from("token")],
+            authorized_keys: vec![key.public_key.clone()],
         }
-    })
+    }
+
+    #[test]
+    fn authorize_accepts_signature_for_same_daemon_and_plugin() {
+        let key = RegistKey::generate();
+        let daemon = Daemon::new_random().unwrap();
+        let credential = key
+            .credential(&nonce(0), &daemon.public_key, &plugin("demo"))
+            .unwrap();
+
+        authorize(
+            &config(&key),
+            &nonce(0),
+            &daemon.public_key,
+            &plugin("demo"),
+            Some(credential),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn authorize_rejects_signature_for_other_daemon() {
+        let key = RegistKey::generate();
+        let daemon = Daemon::new_random().unwrap();
+        let other = Daemon::new_random().unwrap();
+        let credential = key
+            .credential(&nonce(0), &other.public_key, &plugin("demo"))
+            .unwrap();
+
+        assert!(authorize(
+            &config(&key),
+            &nonce(0),
+            &daemon.public_key,
+            &plugin("demo"),
+            Some(credential),
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn authorize_rejects_signature_for_other_plugin_config() {
+        let key = RegistKey::generate();
+        let daemon = Daemon::new_random().unwrap();
+        let credential = key
+            .credential(&nonce(0), &daemon.public_key, &plugin("demo"))
+            .unwrap();
+        let mut tampered = plugin("demo");
+        tampered.address = Some(String::from("http://attacker.example"));
+
+        assert!(authorize(
+            &config(&key),
+            &nonce(0),
+            &daemon.public_key,
+            &tampered,
+            Some(credential),
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn authorize_rejects_signature_for_other_nonce() {
+        let key = RegistKey::generate();
+        let daemon = Daemon::new_random().unwrap();
+        let credential = key
+            .credential(&nonce(0), &daemon.public_key, &plugin("demo"))
+            .unwrap();
+
+        assert!(authorize(
+            &config(&key),
+            &nonce(1),
+            &daemon.public_key,
+            &plugin("demo"),
+            Some(credential),
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn authorize_rejects_unauthorized_key() {
+        let key = RegistKey::generate();
+        let daemon = Daemon::new_random().unwrap();
+        let credential = RegistKey::generate()
+            .credential(&nonce(0), &daemon.public_key, &plugin("demo"))
+            .unwrap();
+
+        assert!(authorize(
+            &config(&key),
+            &nonce(0),
+            &daemon.public_key,
+            &plugin("demo"),
+            Some(credential),
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn authorize_checks_token() {
+        let key = RegistKey::generate();
+        let daemon = Daemon::new_random().unwrap();
+        let authorize_token = |token: &str| {
+            authorize(
+                &config(&key),
+                &nonce(0),
+                &daemon.public_key,
+                &plugin("demo"),
+                Some(RegistCredential::Token {
+                    token: token.to_string(),
+                }),
+            )
+        };
+
+        authorize_token("token").unwrap();
+        assert!(authorize_token("other").is_err());
+        assert!(authorize(
+            &config(&key),
+            &nonce(0),
+            &daemon.public_key,
+            &plugin("demo"),
+            None
+        )
+        .is_err());
+    }
 }
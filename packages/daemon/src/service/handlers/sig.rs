@@ -1,9 +1,10 @@
 use std::sync::Arc;
 
 use axum::{extract::State, http::StatusCode, Json};
+use base64::prelude::*;
 
 use crate::{
-    daemon::SignBox,
+    daemon::{self, SignBox},
     service::{typings::SignRequest, DaemonServer},
 };
 
@@ -11,6 +12,17 @@ pub async fn sig_handler(
     State(state): State<Arc<DaemonServer>>,
     Json(payload): Json<SignRequest>,
 ) -> Result<Json<SignBox>, (StatusCode, String)> {
+    let data = BASE64_URL_SAFE
+        .decode(&payload.base64_url_data_string)
+        .map_err(|_| (StatusCode::BAD_REQUEST, "数据不是合法的 base64".to_string()))?;
+    // 保留前缀用于轮换记录等内部签名，不能通过该接口获得
+    if daemon::is_reserved(&data) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "不能签名以保留前缀开头的数据".to_string(),
+        ));
+    }
+
     let sign = state
         .daemon
         .sign(payload.base64_url_data_string.clone())
@@ -0,0 +1,143 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use futures::future::join_all;
+use serde_json::Value;
+
+use crate::{
+    protocol::{PluginHealth, PluginStatus},
+    service::DaemonServer,
+};
+
+// Plugin 最近一次健康探测的结果
+pub struct HealthLog {
+    plugins: HashMap<String, PluginHealth>,
+}
+
+impl HealthLog {
+    pub fn new() -> Self {
+        HealthLog {
+            plugins: HashMap::new(),
+        }
+    }
+
+    // 保存探测结果，返回健康状态是否发生变化；首次探测时只有不健康才算变化
+    pub fn update(&mut self, health: PluginHealth) -> bool {
+        let changed = match self.plugins.get(&health.name) {
+            Some(previous) => previous.healthy != health.healthy,
+            None => !health.healthy,
+        };
+        self.plugins.insert(health.name.clone(), health);
+        changed
+    }
+
+    pub fn list(&self) -> Vec<PluginHealth> {
+        let mut list: Vec<PluginHealth> = self.plugins.values().cloned().collect();
+        list.sort_by(|a, b| a.name.cmp(&b.name));
+        list
+    }
+
+    pub fn remove(&mut self, plugin: &str) {
+        self.plugins.remove(plugin);
+    }
+}
+
+// 按配置的间隔探测所有 Plugin，Daemon 停止时结束
+pub fn spawn_probe(server: &Arc<DaemonServer>) {
+    let Some(interval) = server.config.health.interval() else {
+        return;
+    };
+    let weak = Arc::downgrade(server);
+    let mut terminate = server.terminate.subscribe();
+    tokio::task::spawn(async move {
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep(interval) => (),
+                _ = terminate.recv() => break,
+            }
+            match weak.upgrade() {
+                Some(server) => probe_all(&server).await,
+                None => break,
+            }
+        }
+    });
+}
+
+// 并发探测所有 Plugin，健康状态变化时发布 plugin/status 事件
+async fn probe_all(server: &DaemonServer) {
+    let names: Vec<String> = server.plugins.lock().await.keys().cloned().collect();
+    let results = join_all(names.into_iter().map(|name| async move {
+        let result = probe(server, &name).await;
+        (name, result)
+    }))
+    .await;
+
+    for (name, result) in results {
+        // 探测期间 Plugin 可能已被删除或断开
+        if !server.plugins.lock().await.contains_key(&name) {
+            continue;
+        }
+        let healthy = result.is_ok();
+        if let Err(message) = result.as_ref() {
+            tracing::warn!(plugin = %name, "Plugin 健康探测失败：{}", message);
+        }
+        let changed = server.health.lock().await.update(PluginHealth {
+            name: name.clone(),
+            healthy,
+            message: result.err(),
+            checked_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|time| time.as_millis() as u64)
+                .unwrap_or_default(),
+        });
+        if changed {
+            let status = match healthy {
+                true => PluginStatus::Online,
+                false => PluginStatus::Unhealthy,
+            };
+            server.publish_plugin_status(&name, status).await;
+        }
+    }
+}
+
+// 本地 Plugin 直接调用健康检查，远程 Plugin 请求其 /.plat/health
+async fn probe(server: &DaemonServer, name: &str) -> Result<(), String> {
+    let timeout = server.config.health.timeout();
+    let local_plugin = server.local_plugins.lock().await.get(name).cloned();
+    if let Some(local_plugin) = local_plugin {
+        return local_plugin.check_health(timeout).await;
+    }
+
+    let (address, fingerprint) = match server.plugins.lock().await.get(name) {
+        Some(plugin) => (plugin.address.clone(), plugin.tls_fingerprint.clone()),
+        None => (None, None),
+    };
+    let Some(address) = address else {
+        return Err("Plugin 没有可访问的地址".to_string());
+    };
+    let client = server
+        .proxy
+        .client(fingerprint.as_deref())
+        .map_err(|e| format!("创建代理客户端失败：{}", e))?;
+    let response = client
+        .client()
+        .get(format!("{}/.plat/health", address.trim_end_matches('/')))
+        .timeout(timeout)
+        .send()
+        .await
+        .map_err(|e| format!("请求健康检查失败：{}", e))?;
+    if response.status().is_success() {
+        return Ok(());
+    }
+
+    let status = response.status();
+    let message = response
+        .json::<Value>()
+        .await
+        .ok()
+        .and_then(|body| body.get("message")?.as_str().map(String::from));
+    Err(message.unwrap_or_else(|| format!("健康检查返回 {}", status)))
+}
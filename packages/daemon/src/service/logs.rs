@@ -0,0 +1,67 @@
+use std::collections::{HashMap, VecDeque};
+
+use plugin::models::LogRecord;
+use tokio::sync::broadcast::{self, Receiver, Sender};
+
+// 每个远程 Plugin 在内存中保留的日志数量，本地 Plugin 的历史日志从日志文件读取
+const LOG_CAPACITY: usize = 1000;
+
+// Plugin 的日志记录，用于查询远程 Plugin 的历史日志以及跟随所有 Plugin 的新日志
+pub struct LogHub {
+    plugins: HashMap<String, PluginLogs>,
+}
+
+struct PluginLogs {
+    records: VecDeque<LogRecord>,
+    sender: Sender<LogRecord>,
+}
+
+impl LogHub {
+    pub fn new() -> Self {
+        LogHub {
+            plugins: HashMap::new(),
+        }
+    }
+
+    // 注册 Plugin 时创建日志记录，已存在时保留历史日志；
+    // 其余方法只查找已有的记录，避免为不存在的 Plugin 分配内存
+    pub fn add(&mut self, plugin: &str) {
+        self.plugins
+            .entry(plugin.to_string())
+            .or_insert_with(|| PluginLogs {
+                records: VecDeque::new(),
+                sender: broadcast::channel(LOG_CAPACITY).0,
+            });
+    }
+
+    pub fn push(&mut self, plugin: &str, record: LogRecord) {
+        let Some(logs) = self.plugins.get_mut(plugin) else {
+            return;
+        };
+        logs.records.push_back(record.clone());
+        if logs.records.len() > LOG_CAPACITY {
+            logs.records.pop_front();
+        }
+        // 没有跟随的连接时丢弃
+        let _ = logs.sender.send(record);
+    }
+
+    pub fn tail(&self, plugin: &str, limit: usize) -> Vec<LogRecord> {
+        match self.plugins.get(plugin) {
+            Some(logs) => {
+                let skip = logs.records.len().saturating_sub(limit);
+                logs.records.iter().skip(skip).cloned().collect()
+            }
+            None => Vec::new(),
+        }
+    }
+
+    pub fn subscribe(&self, plugin: &str) -> Option<Receiver<LogRecord>> {
+        self.plugins.get(plugin).map(|logs| logs.sender.subscribe())
+    }
+
+    // 删除 Plugin 时移除日志，跟随的连接随之结束
+    pub fn remove(&mut self, plugin: &str) {
+        self.plugins.remove(plugin);
+    }
+}
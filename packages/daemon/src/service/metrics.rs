@@ -0,0 +1,58 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use plugin::metrics::{Family, Histogram};
+
+// Daemon 代理到各 Plugin 的请求指标，本地与远程 Plugin 都会记录
+pub struct ProxyMetrics {
+    requests: BTreeMap<(String, u16), u64>,
+    durations: BTreeMap<String, Histogram>,
+}
+
+impl ProxyMetrics {
+    pub fn new() -> Self {
+        ProxyMetrics {
+            requests: BTreeMap::new(),
+            durations: BTreeMap::new(),
+        }
+    }
+
+    pub fn observe(&mut self, plugin: &str, status: u16, duration: Duration) {
+        *self
+            .requests
+            .entry((plugin.to_string(), status))
+            .or_default() += 1;
+        self.durations
+            .entry(plugin.to_string())
+            .or_default()
+            .observe(duration);
+    }
+
+    pub fn remove(&mut self, plugin: &str) {
+        self.requests.retain(|(name, _), _| name != plugin);
+        self.durations.remove(plugin);
+    }
+
+    pub fn encode(&self, out: &mut String) {
+        let mut family = Family::new(
+            out,
+            "plat_proxy_requests_total",
+            "counter",
+            "Daemon 代理到 Plugin 的请求数量，按状态码区分",
+        );
+        for ((plugin, status), count) in self.requests.iter() {
+            let status = status.to_string();
+            family.sample(&[("plugin", plugin), ("status", &status)], count);
+        }
+
+        let mut family = Family::new(
+            out,
+            "plat_proxy_request_duration_seconds",
+            "histogram",
+            "Daemon 代理请求返回响应首部的耗时",
+        );
+        for (plugin, histogram) in self.durations.iter() {
+            family.histogram(&[("plugin", plugin)], histogram);
+        }
+    }
+}
@@ -1,61 +1,154 @@
-use std::{collections::HashMap, fs, path::PathBuf, sync::Arc};
+use std::{
+    collections::HashMap,
+    fs,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
 
+use anyhow::Context;
 use axum::{
-    extract::State,
-    http::StatusCode,
-    routing::{get, post},
+    extract::{DefaultBodyLimit, State},
+    http::{HeaderValue, StatusCode},
+    routing::{any, get, post},
     Json, Router,
 };
 use handlers::{
-    connect_handler, delete_plugin_handler, install_plugin_handler, list_plugin_handler,
+    connect_handler, delete_plugin_handler, health_handler, install_plugin_handler,
+    list_plugin_handler, metrics_handler, plugin_errors_handler, plugin_logs_handler,
+    plugin_prefix, plugin_schedules_handler, proxy_handler, proxy_redirect_handler, ready_handler,
     regist_handler, sig_handler, Connection,
 };
-use plugin::{models::Plugin, Options, PluginServer};
+use plugin::{
+    models::{GuestError, LogRecord, Notification, Plugin, ScheduleRun},
+    LocalOptions, LocalPlugin,
+};
+use proxy::PluginProxy;
 use serde_json::{json, Value};
-use tokio::sync::{broadcast::Sender, Mutex};
+use tokio::sync::{
+    broadcast::{error::RecvError, Receiver, Sender},
+    Mutex,
+};
 use tower::ServiceBuilder;
 use tower_http::{
     cors::{AllowHeaders, AllowMethods, AllowOrigin},
     services::{ServeDir, ServeFile},
 };
-use typings::{VerifyRequest, VerifyResponse};
+use typings::{InsertRejected, VerifyRequest, VerifyResponse};
 
+use errors::ErrorLog;
+use events::EventLog;
+use health::HealthLog;
+use logs::LogHub;
+use metrics::ProxyMetrics;
+use schedules::ScheduleLog;
+
+mod errors;
+mod events;
 mod handlers;
+mod health;
+mod logs;
+mod metrics;
+mod proxy;
+mod schedules;
+mod trace;
 mod typings;
 
-use crate::daemon::{Daemon, SignBox};
+use crate::{
+    config::{DaemonConfig, LogLevel},
+    daemon::{Daemon, SignBox},
+    protocol::{
+        DaemonSnapshot, PluginAdded, PluginNotification, PluginRemoved, PluginStatus,
+        PluginStatusChanged, PluginUpdated, ServerMessage,
+    },
+    succession::SuccessionChain,
+    tls::DaemonTls,
+};
 
 pub struct DaemonServer {
     pub daemon: Daemon,
+    // 密钥轮换历史
+    pub successions: SuccessionChain,
     // 已连接的 Plugin
     pub plugins: Arc<Mutex<HashMap<String, Plugin>>>,
-    // 本地启动的 Plugin 服务
-    plugin_servers: Arc<Mutex<HashMap<String, PluginServer>>>,
+    // 运行在 Daemon 进程内的 Plugin
+    local_plugins: Arc<Mutex<HashMap<String, Arc<LocalPlugin>>>>,
     // Daemon 地址
     pub address: String,
     // Daemon 文件夹路径
     root_path: PathBuf,
+    // Daemon 配置
+    pub config: DaemonConfig,
+    // 启用 TLS 时的证书配置
+    pub tls: Option<DaemonTls>,
+    // 转发远程 Plugin 请求的客户端
+    proxy: PluginProxy,
     // 当前正活跃的用户连接
     connections: Mutex<Vec<Arc<Connection>>>,
+    // Plugin 变化事件，新连接与重连的连接从这里获取快照序号或补发事件
+    events: Mutex<EventLog>,
+    // Plugin 的 Guest 错误记录
+    errors: Mutex<ErrorLog>,
+    // Plugin 日志，远程 Plugin 的日志通过 /api/regist 连接发送
+    logs: Mutex<LogHub>,
+    // 代理到各 Plugin 的请求指标
+    proxy_metrics: Mutex<ProxyMetrics>,
+    // Plugin 最近一次健康探测的结果
+    health: Mutex<HealthLog>,
+    // Plugin 定时任务的执行状态
+    schedules: Mutex<ScheduleLog>,
+    // 本地 Plugin 是否已全部启动
+    ready: AtomicBool,
     terminate: Sender<()>,
 }
 
 impl DaemonServer {
-    pub async fn new(daemon: Daemon, root_path: PathBuf, port: u16) -> anyhow::Result<Arc<Self>> {
-        let assets_path = root_path.join("assets");
-        let tcp_listener = tokio::net::TcpListener::bind(format!("127.0.0.1:{}", port)).await?;
-        let address = format!("http://{}", tcp_listener.local_addr()?.to_string());
+    pub async fn new(
+        daemon: Daemon,
+        root_path: PathBuf,
+        config: DaemonConfig,
+    ) -> anyhow::Result<Arc<Self>> {
+        config.validate().context("daemon.toml 配置校验失败")?;
+
+        let assets_path = root_path.join(&config.paths.assets);
+        let successions = SuccessionChain::load(&root_path)?;
+        successions
+            .verify(&daemon.public_key)
+            .context("校验密钥轮换记录失败")?;
+
+        let tcp_listener = tokio::net::TcpListener::bind(config.socket_addr()?)
+            .await
+            .context("监听端口失败")?;
+        let tls = DaemonTls::load(&config, &root_path, &daemon).context("加载 TLS 配置失败")?;
+        let scheme = match tls.is_some() {
+            true => "https",
+            false => "http",
+        };
+        let address = format!("{}://{}", scheme, tcp_listener.local_addr()?);
 
         let (tx, _rx) = tokio::sync::broadcast::channel::<()>(4);
 
         let service = DaemonServer {
             daemon,
+            successions,
             plugins: Arc::new(Mutex::new(HashMap::new())),
-            plugin_servers: Arc::new(Mutex::new(HashMap::new())),
+            local_plugins: Arc::new(Mutex::new(HashMap::new())),
             address,
             root_path,
+            config,
+            proxy: PluginProxy::new()?,
+            tls,
             terminate: tx,
             connections: Mutex::new(Vec::new()),
+            events: Mutex::new(EventLog::new()),
+            errors: Mutex::new(ErrorLog::new()),
+            logs: Mutex::new(LogHub::new()),
+            proxy_metrics: Mutex::new(ProxyMetrics::new()),
+            health: Mutex::new(HealthLog::new()),
+            schedules: Mutex::new(ScheduleLog::new()),
+            ready: AtomicBool::new(false),
         };
         let service = Arc::new(service);
 
@@ -64,77 +157,418 @@ impl DaemonServer {
             async move {
                 let serve_dir = ServeDir::new(assets_path.clone())
                     .not_found_service(ServeFile::new(assets_path.join("index.html")));
+                let allow_origin = match service.config.cors.allow_origins.is_empty() {
+                    true => AllowOrigin::mirror_request(),
+                    false => AllowOrigin::list(
+                        service
+                            .config
+                            .cors
+                            .allow_origins
+                            .iter()
+                            .filter_map(|origin| HeaderValue::from_str(origin).ok()),
+                    ),
+                };
 
                 let app = Router::new()
                     .route("/api", get(root_handler))
+                    .route("/api/health", get(health_handler))
+                    .route("/api/ready", get(ready_handler))
                     .route("/api/regist", get(regist_handler))
                     .route("/api/sig", post(sig_handler))
                     .route("/api/verify", post(verify_handler))
                     .route("/api/connect", get(connect_handler))
+                    .route("/api/metrics", get(metrics_handler))
                     .route(
                         "/api/plugin",
                         get(list_plugin_handler)
                             .post(install_plugin_handler)
-                            .delete(delete_plugin_handler),
+                            .delete(delete_plugin_handler)
+                            .layer(DefaultBodyLimit::max(service.config.plugin.max_upload_size)),
                     )
+                    .route("/api/plugin/:name/errors", get(plugin_errors_handler))
+                    .route("/api/plugin/:name/logs", get(plugin_logs_handler))
+                    .route("/api/plugin/:name/schedules", get(plugin_schedules_handler))
+                    .route("/plugins/:name", any(proxy_redirect_handler))
+                    .route("/plugins/:name/", any(proxy_handler))
+                    .route("/plugins/:name/*path", any(proxy_handler))
                     .fallback_service(serve_dir)
                     .layer(
-                        ServiceBuilder::new().layer(
-                            tower_http::cors::CorsLayer::new()
-                                .allow_methods(AllowMethods::mirror_request())
-                                .allow_origin(AllowOrigin::mirror_request())
-                                .allow_credentials(true)
-                                .allow_headers(AllowHeaders::mirror_request()),
-                        ),
+                        ServiceBuilder::new()
+                            .layer(axum::middleware::from_fn(trace::trace_request))
+                            .layer(
+                                tower_http::cors::CorsLayer::new()
+                                    .allow_methods(AllowMethods::mirror_request())
+                                    .allow_origin(allow_origin)
+                                    .allow_credentials(true)
+                                    .allow_headers(AllowHeaders::mirror_request()),
+                            ),
                     )
                     .with_state(service.clone());
-                axum::serve(tcp_listener, app)
-                    .with_graceful_shutdown(async move {
-                        let _ = service.terminate.subscribe().recv().await;
-                    })
-                    .await
-                    .unwrap();
+                match service.tls.as_ref() {
+                    Some(tls) => {
+                        crate::tls::serve(
+                            tcp_listener,
+                            app,
+                            tls.server_config.clone(),
+                            service.terminate.subscribe(),
+                        )
+                        .await
+                    }
+                    None => axum::serve(tcp_listener, app)
+                        .with_graceful_shutdown(async move {
+                            let _ = service.terminate.subscribe().recv().await;
+                        })
+                        .await
+                        .unwrap(),
+                }
             }
         });
 
         service.start_local_plugin().await?;
+        service.ready.store(true, Ordering::Release);
+        health::spawn_probe(&service);
 
         Ok(service)
     }
 
-    pub async fn start_local_plugin(&self) -> anyhow::Result<()> {
-        let plugins_dir = self.root_path.join("plugins");
+    pub async fn start_local_plugin(self: &Arc<Self>) -> anyhow::Result<()> {
+        let plugins_dir = self.plugins_path();
         if !plugins_dir.exists() {
             return Ok(());
         }
 
         for entry in fs::read_dir(&plugins_dir)? {
             let entry = entry?;
-            let plugin_server = PluginServer::new(
-                entry.path().join("plugin.json"),
-                Options {
-                    port: 0,
-                    daemon_address: self.address.clone(),
-                    regist_address: None,
-                },
-            )
-            .await?;
-            self.plugin_servers
-                .lock()
+            let local_plugin = self.create_local_plugin(entry.path())?;
+            self.register_local_plugin(local_plugin).await?;
+        }
+
+        Ok(())
+    }
+
+    // 在 Daemon 进程内加载 Plugin，对外地址为 Daemon 代理的 /plugins/{name}
+    #[tracing::instrument(skip(self), fields(dir = %plugin_dir.display()), err)]
+    pub fn create_local_plugin(&self, plugin_dir: PathBuf) -> anyhow::Result<LocalPlugin> {
+        let plugin_config_path = plugin_dir.join("plugin.json");
+        let plugin: Plugin = serde_json::from_slice(
+            &fs::read(&plugin_config_path).context("读取 plugin.json 失败")?,
+        )
+        .context("序列化 plugin.json 失败")?;
+
+        LocalPlugin::new(
+            plugin_config_path,
+            LocalOptions {
+                daemon_address: self.address.clone(),
+                daemon_public_key: self.daemon.public_key.clone(),
+                address: format!("{}{}", self.address, plugin_prefix(&plugin.name)),
+                data_dir: self.plugin_data_path(&plugin.name),
+                https: self.tls.is_some(),
+                debug: self.config.log.enabled(LogLevel::Debug),
+                max_body_size: self.config.plugin.max_body_size,
+            },
+        )
+    }
+
+    // 将进程内的 Plugin 直接登记到 plugins，同名的本地 Plugin 会被停止并替换
+    #[tracing::instrument(skip_all, fields(plugin = %local_plugin.plugin().name), err)]
+    pub async fn register_local_plugin(
+        self: &Arc<Self>,
+        local_plugin: LocalPlugin,
+    ) -> anyhow::Result<()> {
+        let plugin = local_plugin.plugin().clone();
+        let replaced = self.local_plugins.lock().await.contains_key(&plugin.name);
+        // 先登记到 plugins，名称冲突与数量上限在同一次加锁中检查
+        if let Err(e) = self.insert_plugin(&plugin, replaced).await {
+            local_plugin.stop().await;
+            return Err(e.into());
+        }
+
+        // 转发 Guest 推送的消息与日志并保存错误与定时任务记录，PlatServer 释放后结束
+        tokio::task::spawn({
+            let server = Arc::downgrade(self);
+            let name = plugin.name.clone();
+            let mut notifications = local_plugin.subscribe_notifications();
+            let mut errors = local_plugin.subscribe_errors();
+            let mut logs = local_plugin.subscribe_logs();
+            let mut schedule_runs = local_plugin.subscribe_schedule_runs();
+            async move {
+                loop {
+                    tokio::select! {
+                        notification = notifications.recv() => {
+                            let notification = match notification {
+                                Ok(notification) => notification,
+                                Err(RecvError::Lagged(_)) => continue,
+                                Err(RecvError::Closed) => break,
+                            };
+                            match server.upgrade() {
+                                Some(server) => server.notify(&name, notification).await,
+                                None => break,
+                            }
+                        }
+                        error = errors.recv() => {
+                            let error = match error {
+                                Ok(error) => error,
+                                Err(RecvError::Lagged(_)) => continue,
+                                Err(RecvError::Closed) => break,
+                            };
+                            match server.upgrade() {
+                                Some(server) => server.record_error(&name, error).await,
+                                None => break,
+                            }
+                        }
+                        record = logs.recv() => {
+                            let record = match record {
+                                Ok(record) => record,
+                                Err(RecvError::Lagged(_)) => continue,
+                                Err(RecvError::Closed) => break,
+                            };
+                            match server.upgrade() {
+                                Some(server) => server.record_log(&name, record).await,
+                                None => break,
+                            }
+                        }
+                        run = schedule_runs.recv() => {
+                            let run = match run {
+                                Ok(run) => run,
+                                Err(RecvError::Lagged(_)) => continue,
+                                Err(RecvError::Closed) => break,
+                            };
+                            match server.upgrade() {
+                                Some(server) => server.record_schedule_run(&name, run).await,
+                                None => break,
+                            }
+                        }
+                    }
+                }
+            }
+        });
+
+        let previous = self
+            .local_plugins
+            .lock()
+            .await
+            .insert(plugin.name.clone(), Arc::new(local_plugin));
+        if let Some(previous) = previous {
+            previous.stop().await;
+        }
+        self.health.lock().await.remove(&plugin.name);
+        self.schedules.lock().await.interrupt(&plugin.name);
+        self.publish_plugin_status(&plugin.name, PluginStatus::Online)
+            .await;
+        tracing::info!(replaced, "本地 Plugin 已上线");
+
+        Ok(())
+    }
+
+    pub fn assets_path(&self) -> PathBuf {
+        self.root_path.join(&self.config.paths.assets)
+    }
+
+    pub fn plugins_path(&self) -> PathBuf {
+        self.root_path.join(&self.config.paths.plugins)
+    }
+
+    // 宿主为 Plugin 管理的数据目录，位于插件目录之外
+    pub fn plugin_data_path(&self, name: &str) -> PathBuf {
+        self.root_path
+            .join(&self.config.paths.data)
+            .join(urlencoding::encode(name).as_ref())
+    }
+
+    // 检查已注册的 Plugin 数量是否达到配置的上限，仅用于提前拒绝，最终以 insert_plugin 的检查为准
+    pub async fn check_plugin_limit(&self) -> anyhow::Result<()> {
+        if let Some(max_plugins) = self.config.plugin.max_plugins {
+            if self.plugins.lock().await.len() >= max_plugins {
+                return Err(InsertRejected::PluginLimit(max_plugins).into());
+            }
+        }
+
+        Ok(())
+    }
+
+    // 当前所有 Plugin 的快照
+    pub async fn snapshot(&self, seq: u64) -> DaemonSnapshot {
+        DaemonSnapshot {
+            seq,
+            public_key: self.daemon.public_key.clone(),
+            plugins: self.plugins.lock().await.values().cloned().collect(),
+            health: self.health.lock().await.list(),
+        }
+    }
+
+    // 登记 Plugin 并记录 plugin/added 或 plugin/updated 事件，二者在持有事件锁时完成，
+    // 新连接获取的快照与之后补发的事件才不会重复或遗漏；replace 为 false 且已存在同名 Plugin，
+    // 或新增 Plugin 时数量已达到 max_plugins 时拒绝登记，检查与登记在同一次加锁中完成
+    pub async fn insert_plugin(
+        &self,
+        plugin: &Plugin,
+        replace: bool,
+    ) -> Result<(), InsertRejected> {
+        let mut events = self.events.lock().await;
+        let replaced = {
+            let mut plugins = self.plugins.lock().await;
+            let exists = plugins.contains_key(&plugin.name);
+            if exists && !replace {
+                return Err(InsertRejected::DuplicateName(plugin.name.clone()));
+            }
+            if let Some(max_plugins) = self.config.plugin.max_plugins {
+                if !exists && plugins.len() >= max_plugins {
+                    return Err(InsertRejected::PluginLimit(max_plugins));
+                }
+            }
+            self.logs.lock().await.add(&plugin.name);
+            plugins.insert(plugin.name.clone(), plugin.clone());
+            exists
+        };
+        match replaced {
+            true => {
+                self.publish_locked(&mut events, |seq| {
+                    ServerMessage::PluginUpdated(PluginUpdated {
+                        seq,
+                        plugin: plugin.clone(),
+                    })
+                })
+                .await
+            }
+            false => {
+                self.publish_locked(&mut events, |seq| {
+                    ServerMessage::PluginAdded(PluginAdded {
+                        seq,
+                        plugin: plugin.clone(),
+                    })
+                })
                 .await
-                .insert(plugin_server.plugin().name.clone(), plugin_server);
+            }
         }
 
         Ok(())
     }
 
+    // 移除 Plugin 并记录 plugin/status 与 plugin/removed 事件，同样在持有事件锁时完成
+    pub async fn remove_plugin(&self, name: &str) -> Option<Plugin> {
+        let mut events = self.events.lock().await;
+        let plugin = self.plugins.lock().await.remove(name)?;
+        self.publish_locked(&mut events, |seq| {
+            ServerMessage::PluginStatus(PluginStatusChanged {
+                seq,
+                name: name.to_string(),
+                status: PluginStatus::Offline,
+            })
+        })
+        .await;
+        self.publish_locked(&mut events, |seq| {
+            ServerMessage::PluginRemoved(PluginRemoved {
+                seq,
+                name: name.to_string(),
+            })
+        })
+        .await;
+
+        Some(plugin)
+    }
+
+    // 记录 Plugin 变化事件并发送给所有用户连接，
+    // 协议版本 1 的连接除 plugin/status 外只能收到完整快照
+    pub async fn publish(&self, event: impl FnOnce(u64) -> ServerMessage) {
+        let mut events = self.events.lock().await;
+        self.publish_locked(&mut events, event).await;
+    }
+
+    async fn publish_locked(
+        &self,
+        events: &mut EventLog,
+        event: impl FnOnce(u64) -> ServerMessage,
+    ) {
+        let message = events.push(event);
+        let legacy = match &message {
+            ServerMessage::PluginStatus(_) => message.clone(),
+            _ => ServerMessage::Daemon(self.snapshot(events.seq()).await),
+        };
+
+        for connection in self.connections.lock().await.iter() {
+            let _ = match connection.version {
+                1 => connection.send(&legacy),
+                _ => connection.send(&message),
+            };
+        }
+    }
+
+    pub async fn publish_plugin_status(&self, name: &str, status: PluginStatus) {
+        self.publish(|seq| {
+            ServerMessage::PluginStatus(PluginStatusChanged {
+                seq,
+                name: name.to_string(),
+                status,
+            })
+        })
+        .await;
+    }
+
+    // 保存 Guest 处理请求失败的记录
+    pub async fn record_error(&self, plugin: &str, error: GuestError) {
+        // 错误已由 Plugin 在处理请求时输出，这里只记录保存
+        tracing::debug!(plugin, request_id = %error.request_id, "保存 Guest 错误记录");
+        self.errors.lock().await.push(plugin, error);
+    }
+
+    pub async fn record_schedule_run(&self, plugin: &str, run: ScheduleRun) {
+        self.schedules.lock().await.record(plugin, run);
+    }
+
+    pub async fn record_log(&self, plugin: &str, record: LogRecord) {
+        self.logs.lock().await.push(plugin, record);
+    }
+
+    // 返回 Plugin 最后的 limit 条日志，本地 Plugin 从日志文件读取；Plugin 不存在时返回 None
+    pub async fn tail_logs(&self, plugin: &str, limit: usize) -> Option<Vec<LogRecord>> {
+        if !self.plugins.lock().await.contains_key(plugin) {
+            return None;
+        }
+        let local_plugin = self.local_plugins.lock().await.get(plugin).cloned();
+        match local_plugin {
+            Some(local_plugin) => {
+                tokio::task::spawn_blocking(move || local_plugin.tail_logs(limit))
+                    .await
+                    .ok()
+            }
+            None => Some(self.logs.lock().await.tail(plugin, limit)),
+        }
+    }
+
+    // Plugin 不存在时返回 None
+    pub async fn subscribe_logs(&self, plugin: &str) -> Option<Receiver<LogRecord>> {
+        if !self.plugins.lock().await.contains_key(plugin) {
+            return None;
+        }
+        self.logs.lock().await.subscribe(plugin)
+    }
+
+    // 将 Plugin 推送的消息转发给订阅了该主题的用户连接
+    pub async fn notify(&self, plugin: &str, notification: Notification) {
+        let mut message = None;
+        for connection in self.connections.lock().await.iter() {
+            if !connection.is_subscribed(plugin, &notification.topic) {
+                continue;
+            }
+            let message = message.get_or_insert_with(|| {
+                ServerMessage::Notification(PluginNotification {
+                    plugin: plugin.to_string(),
+                    topic: notification.topic.clone(),
+                    payload: notification.payload.clone(),
+                })
+            });
+            let _ = connection.send(message);
+        }
+    }
+
     pub async fn stop(&self) -> anyhow::Result<()> {
+        tracing::info!("Daemon 正在停止");
         for connection in self.connections.lock().await.iter() {
             connection.stop().await;
         }
 
-        for plugin_server in self.plugin_servers.lock().await.values() {
-            plugin_server.stop().await;
+        for local_plugin in self.local_plugins.lock().await.values() {
+            local_plugin.stop().await;
         }
 
         self.terminate.send(())?;
@@ -150,6 +584,8 @@ impl DaemonServer {
 async fn root_handler(State(service): State<Arc<DaemonServer>>) -> (StatusCode, Json<Value>) {
     let out = json!({
         "public_key": &service.daemon.public_key,
+        "successions": &service.successions.records,
+        "tls": service.tls.as_ref().map(|tls| &tls.identity),
     });
     (StatusCode::OK, Json(out))
 }
@@ -171,3 +607,52 @@ async fn verify_handler(
         }),
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn insert_plugin_enforces_limit_under_concurrency() {
+        let daemon = Daemon::new_random().unwrap();
+        let root_path = env::temp_dir().join(format!("plat-service-{}", daemon.public_key));
+        fs::create_dir_all(&root_path).unwrap();
+        let mut config = DaemonConfig {
+            host: String::from("127.0.0.1"),
+            port: 0,
+            ..DaemonConfig::default()
+        };
+        config.plugin.max_plugins = Some(1);
+        let server = DaemonServer::new(daemon, root_path.clone(), config)
+            .await
+            .unwrap();
+
+        let tasks = (0..8).map(|index| {
+            let server = server.clone();
+            tokio::spawn(async move {
+                let plugin: Plugin = serde_json::from_value(json!({
+                    "name": format!("plugin-{}", index),
+                    "wasm_root": "",
+                    "assets_root": "",
+                    "storage_root": "",
+                    "entries": [],
+                    "address": null,
+                }))
+                .unwrap();
+                server.insert_plugin(&plugin, false).await
+            })
+        });
+        let results = futures::future::join_all(tasks).await;
+        let inserted = results
+            .into_iter()
+            .filter(|result| result.as_ref().unwrap().is_ok())
+            .count();
+
+        assert_eq!(inserted, 1);
+        assert_eq!(server.plugins.lock().await.len(), 1);
+        let _ = server.stop().await;
+        fs::remove_dir_all(root_path).unwrap();
+    }
+}
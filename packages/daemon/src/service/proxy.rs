@@ -0,0 +1,69 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use plugin::tls::{self, rustls::ClientConfig};
+use reqwest::{redirect::Policy, Client};
+
+// 向远程注册的 Plugin 服务转发请求时使用的客户端
+pub struct PluginProxy {
+    // 使用 WebPKI 根证书校验，用于未报告证书指纹的 Plugin
+    default: ProxyClient,
+    // 按 Plugin 注册时报告的证书指纹固定证书，支持自签名证书
+    pinned: Mutex<HashMap<String, ProxyClient>>,
+}
+
+#[derive(Clone)]
+pub struct ProxyClient {
+    tls: Arc<ClientConfig>,
+    client: Client,
+}
+
+impl ProxyClient {
+    fn new(fingerprint: Option<&str>) -> anyhow::Result<Self> {
+        let tls = tls::client_config(fingerprint)?;
+        let client = Client::builder()
+            .use_preconfigured_tls(tls.as_ref().clone())
+            .redirect(Policy::none())
+            .build()?;
+
+        Ok(ProxyClient { tls, client })
+    }
+
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    pub fn websocket_tls(&self) -> Arc<ClientConfig> {
+        self.tls.clone()
+    }
+}
+
+impl PluginProxy {
+    pub fn new() -> anyhow::Result<Self> {
+        Ok(PluginProxy {
+            default: ProxyClient::new(None)?,
+            pinned: Mutex::new(HashMap::new()),
+        })
+    }
+
+    // 按证书指纹取得客户端，同一指纹的 Plugin 共享连接池
+    pub fn client(&self, fingerprint: Option<&str>) -> anyhow::Result<ProxyClient> {
+        let Some(fingerprint) = fingerprint else {
+            return Ok(self.default.clone());
+        };
+        let mut pinned = self.pinned.lock().unwrap();
+        if let Some(client) = pinned.get(fingerprint) {
+            return Ok(client.clone());
+        }
+        let client = ProxyClient::new(Some(fingerprint))?;
+        pinned.insert(fingerprint.to_string(), client.clone());
+        Ok(client)
+    }
+
+    // Plugin 断开后不再需要固定其证书
+    pub fn forget(&self, fingerprint: &str) {
+        self.pinned.lock().unwrap().remove(fingerprint);
+    }
+}
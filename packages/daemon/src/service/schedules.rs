@@ -0,0 +1,62 @@
+use std::collections::HashMap;
+
+use plugin::models::{ScheduleRun, ScheduleRunStatus};
+
+// 定时任务的执行状态
+#[derive(Debug, Clone, Default)]
+pub struct ScheduleState {
+    // 正在执行时为开始时间，Unix 时间戳（毫秒）
+    pub running_since: Option<u64>,
+    // 最近一次已结束的执行
+    pub last_run: Option<ScheduleRun>,
+}
+
+// 各 Plugin 定时任务的执行状态，按 Plugin 与任务名称保存
+pub struct ScheduleLog {
+    plugins: HashMap<String, HashMap<String, ScheduleState>>,
+}
+
+impl ScheduleLog {
+    pub fn new() -> Self {
+        ScheduleLog {
+            plugins: HashMap::new(),
+        }
+    }
+
+    pub fn record(&mut self, plugin: &str, run: ScheduleRun) {
+        let state = self
+            .plugins
+            .entry(plugin.to_string())
+            .or_default()
+            .entry(run.schedule.clone())
+            .or_default();
+        match run.status {
+            ScheduleRunStatus::Running => state.running_since = Some(run.started_at),
+            _ => {
+                state.running_since = None;
+                state.last_run = Some(run);
+            }
+        }
+    }
+
+    pub fn get(&self, plugin: &str, schedule: &str) -> ScheduleState {
+        self.plugins
+            .get(plugin)
+            .and_then(|schedules| schedules.get(schedule))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    // Plugin 停止或断开时正在执行的任务已被终止，只保留最近一次已结束的执行
+    pub fn interrupt(&mut self, plugin: &str) {
+        if let Some(schedules) = self.plugins.get_mut(plugin) {
+            for state in schedules.values_mut() {
+                state.running_since = None;
+            }
+        }
+    }
+
+    pub fn remove(&mut self, plugin: &str) {
+        self.plugins.remove(plugin);
+    }
+}
@@ -0,0 +1,39 @@
+use std::time::Instant;
+
+use axum::{extract::Request, http::HeaderValue, middleware::Next, response::Response};
+use plugin::{request_id, REQUEST_ID_HEADER};
+use tracing::Instrument;
+
+// 为每个 HTTP 请求创建 span，沿用客户端提供的请求 ID 或生成新的 ID；
+// 请求 ID 写回请求首部，代理到 Plugin 时随其他首部一起转发
+pub async fn trace_request(mut req: Request, next: Next) -> Response {
+    let request_id = request_id(req.headers());
+    let header_value = HeaderValue::from_str(&request_id).ok();
+    if let Some(value) = header_value.clone() {
+        req.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    let span = tracing::info_span!(
+        "http",
+        request_id = %request_id,
+        method = %req.method(),
+        path = %req.uri().path(),
+    );
+
+    async move {
+        let start = Instant::now();
+        let mut res = next.run(req).await;
+        tracing::debug!(
+            status = res.status().as_u16(),
+            elapsed_ms = start.elapsed().as_millis() as u64,
+            "请求完成"
+        );
+        if let Some(value) = header_value {
+            if !res.headers().contains_key(REQUEST_ID_HEADER) {
+                res.headers_mut().insert(REQUEST_ID_HEADER, value);
+            }
+        }
+        res
+    }
+    .instrument(span)
+    .await
+}
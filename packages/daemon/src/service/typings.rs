@@ -1,6 +1,5 @@
 use axum::{http::StatusCode, response::IntoResponse};
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RegistPluginRequest {
@@ -24,13 +23,26 @@ pub struct VerifyResponse {
     pub success: bool,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ConnectionMessage {
-    #[serde(rename(serialize = "type", deserialize = "type"))]
-    pub ty: String,
-    pub payload: Value,
+// 登记 Plugin 失败的原因
+#[derive(Debug)]
+pub enum InsertRejected {
+    DuplicateName(String),
+    PluginLimit(usize),
 }
 
+impl std::fmt::Display for InsertRejected {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InsertRejected::DuplicateName(name) => write!(f, "已存在相同名称的 Plugin：{}", name),
+            InsertRejected::PluginLimit(max_plugins) => {
+                write!(f, "已注册的 Plugin 数量达到上限：{}", max_plugins)
+            }
+        }
+    }
+}
+
+impl std::error::Error for InsertRejected {}
+
 pub struct AppError(anyhow::Error);
 
 impl IntoResponse for AppError {
@@ -0,0 +1,273 @@
+use std::{
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Context};
+use serde::{Deserialize, Serialize};
+
+use crate::daemon::{Daemon, SignBox, SUCCESSION_DOMAIN};
+
+// 密钥轮换记录，由旧密钥签名，证明新公钥是旧公钥的合法继任者
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SuccessionRecord {
+    pub previous_public_key: String,
+    pub public_key: String,
+    pub created_at: u64,
+    pub signature: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SuccessionClaim<'a> {
+    previous_public_key: &'a str,
+    public_key: &'a str,
+    created_at: u64,
+}
+
+impl SuccessionRecord {
+    // 被签名的数据，格式与 SignBox::verify 的入参保持一致
+    // 签名的内容，签名时在前面加上 SUCCESSION_DOMAIN，与 /api/sig 签名的数据区分
+    pub fn data(&self) -> anyhow::Result<Vec<u8>> {
+        let claim = SuccessionClaim {
+            previous_public_key: &self.previous_public_key,
+            public_key: &self.public_key,
+            created_at: self.created_at,
+        };
+        Ok(serde_json::to_vec(&claim)?)
+    }
+
+    pub fn sign_box(&self) -> SignBox {
+        SignBox {
+            public_key: self.previous_public_key.clone(),
+            signature: self.signature.clone(),
+        }
+    }
+
+    pub fn verify(&self) -> anyhow::Result<()> {
+        self.sign_box()
+            .verify_in_domain(SUCCESSION_DOMAIN, &self.data()?)
+    }
+}
+
+// 保存在 Daemon 文件夹中的密钥轮换历史
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SuccessionChain {
+    pub records: Vec<SuccessionRecord>,
+}
+
+impl SuccessionChain {
+    pub fn path(root_path: &Path) -> PathBuf {
+        root_path.join("successions.json")
+    }
+
+    pub fn load(root_path: &Path) -> anyhow::Result<Self> {
+        let path = Self::path(root_path);
+        if !path.exists() {
+            return Ok(SuccessionChain::default());
+        }
+
+        serde_json::from_slice(&fs::read(path).context("读取 successions.json 失败")?)
+            .context("反序列化 successions.json 失败")
+    }
+
+    pub fn save(&self, root_path: &Path) -> anyhow::Result<()> {
+        let path = Self::path(root_path);
+        let temp_path = write_temp(&path, serde_json::to_string(self)?.as_bytes())
+            .context("写入 successions.json 失败")?;
+        fs::rename(temp_path, path).context("写入 successions.json 失败")
+    }
+
+    // 校验每条记录的签名，以及记录之间的首尾相接关系
+    pub fn verify(&self, current_public_key: &str) -> anyhow::Result<()> {
+        let mut expected_public_key: Option<&str> = None;
+        for record in self.records.iter() {
+            if let Some(expected) = expected_public_key {
+                if record.previous_public_key != expected {
+                    return Err(anyhow!("轮换记录不连续，期望的上一任公钥为：{}", expected));
+                }
+            }
+            record.verify()?;
+            expected_public_key.replace(&record.public_key);
+        }
+
+        match expected_public_key {
+            Some(expected) if expected != current_public_key => {
+                Err(anyhow!("轮换记录的最终公钥与当前 Daemon 公钥不一致"))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Daemon {
+    // 生成新的密钥对，并使用当前密钥为其签发轮换记录
+    pub fn rotate(&self) -> anyhow::Result<(Daemon, SuccessionRecord)> {
+        let successor = Daemon::new_random()?;
+        let mut record = SuccessionRecord {
+            previous_public_key: self.public_key.clone(),
+            public_key: successor.public_key.clone(),
+            created_at: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+            signature: String::new(),
+        };
+        record.signature = self
+            .sign_in_domain(SUCCESSION_DOMAIN, &record.data()?)?
+            .signature;
+
+        Ok((successor, record))
+    }
+
+    // 在 Daemon 文件夹中完成轮换：追加轮换记录并覆盖 daemon.json
+    pub fn rotate_file(daemon_path: &Path) -> anyhow::Result<(Daemon, SuccessionRecord)> {
+        let root_path = daemon_path.parent().unwrap();
+        let daemon: Daemon =
+            serde_json::from_slice(&fs::read(daemon_path).context("读取 daemon.json 失败")?)
+                .context("反序列化 daemon.json 失败")?;
+
+        let mut chain = SuccessionChain::load(root_path)?;
+        chain.verify(&daemon.public_key)?;
+
+        let (successor, record) = daemon.rotate()?;
+        chain.records.push(record.clone());
+
+        // 两个文件都先写入临时文件，写入失败时原有文件保持不变
+        let chain_path = SuccessionChain::path(root_path);
+        let chain_temp = write_temp(&chain_path, serde_json::to_string(&chain)?.as_bytes())
+            .context("写入 successions.json 失败")?;
+        let daemon_temp =
+            match write_temp(daemon_path, serde_json::to_string(&successor)?.as_bytes()) {
+                Ok(temp_path) => temp_path,
+                Err(e) => {
+                    let _ = fs::remove_file(&chain_temp);
+                    return Err(e).context("写入 daemon.json 失败");
+                }
+            };
+
+        // 最后替换 daemon.json，替换失败时恢复原有的轮换记录，保证两者始终一致
+        fs::rename(&chain_temp, &chain_path).context("写入 successions.json 失败")?;
+        if let Err(e) = fs::rename(&daemon_temp, daemon_path) {
+            let _ = fs::remove_file(&daemon_temp);
+            chain.records.pop();
+            chain
+                .save(root_path)
+                .context("恢复 successions.json 失败")?;
+            return Err(e).context("写入 daemon.json 失败");
+        }
+
+        Ok((successor, record))
+    }
+}
+
+// 写入同一文件夹下的临时文件并落盘，之后通过 rename 原子地替换目标文件
+fn write_temp(path: &Path, contents: &[u8]) -> std::io::Result<PathBuf> {
+    let temp_path = path.with_extension("json.tmp");
+    let mut file = File::create(&temp_path)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    Ok(temp_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use base64::prelude::*;
+
+    use super::*;
+
+    // 连续轮换两次，返回最终的 Daemon 与完整的轮换记录
+    fn rotate_twice() -> (Daemon, SuccessionChain) {
+        let first = Daemon::new_random().unwrap();
+        let (second, first_record) = first.rotate().unwrap();
+        let (third, second_record) = second.rotate().unwrap();
+
+        let chain = SuccessionChain {
+            records: vec![first_record, second_record],
+        };
+        (third, chain)
+    }
+
+    #[test]
+    fn verify_accepts_valid_chain() {
+        let (daemon, chain) = rotate_twice();
+
+        chain.verify(&daemon.public_key).unwrap();
+        SuccessionChain::default()
+            .verify(&daemon.public_key)
+            .unwrap();
+    }
+
+    #[test]
+    fn verify_rejects_wrong_current_key() {
+        let (_, chain) = rotate_twice();
+        let other = Daemon::new_random().unwrap();
+
+        assert!(chain.verify(&other.public_key).is_err());
+    }
+
+    #[test]
+    fn verify_rejects_broken_link() {
+        let (daemon, mut chain) = rotate_twice();
+        chain.records.remove(0);
+        let (_, unrelated) = Daemon::new_random().unwrap().rotate().unwrap();
+        chain.records.insert(0, unrelated);
+
+        assert!(chain.verify(&daemon.public_key).is_err());
+    }
+
+    #[test]
+    fn verify_rejects_tampered_record() {
+        let (daemon, mut chain) = rotate_twice();
+        chain.records[1].created_at += 1;
+
+        assert!(chain.verify(&daemon.public_key).is_err());
+    }
+
+    #[test]
+    fn verify_rejects_record_signed_without_domain() {
+        let daemon = Daemon::new_random().unwrap();
+        let attacker = Daemon::new_random().unwrap();
+        let mut record = SuccessionRecord {
+            previous_public_key: daemon.public_key.clone(),
+            public_key: attacker.public_key.clone(),
+            created_at: 1,
+            signature: String::new(),
+        };
+        // 与 /api/sig 相同，直接对轮换内容签名
+        record.signature = daemon
+            .sign(BASE64_URL_SAFE.encode(record.data().unwrap()))
+            .unwrap()
+            .signature;
+        let chain = SuccessionChain {
+            records: vec![record],
+        };
+
+        assert!(chain.verify(&attacker.public_key).is_err());
+    }
+
+    #[test]
+    fn rotate_file_appends_record_and_replaces_identity() {
+        let root_path = env::temp_dir().join(format!(
+            "plat-succession-{}",
+            Daemon::new_random().unwrap().public_key
+        ));
+        fs::create_dir_all(&root_path).unwrap();
+        let daemon_path = root_path.join("daemon.json");
+        let daemon = Daemon::new_random().unwrap();
+        fs::write(&daemon_path, serde_json::to_string(&daemon).unwrap()).unwrap();
+
+        let (first, _) = Daemon::rotate_file(&daemon_path).unwrap();
+        let (second, _) = Daemon::rotate_file(&daemon_path).unwrap();
+
+        let stored: Daemon = serde_json::from_slice(&fs::read(&daemon_path).unwrap()).unwrap();
+        let chain = SuccessionChain::load(&root_path).unwrap();
+        assert_eq!(stored.public_key, second.public_key);
+        assert_eq!(chain.records.len(), 2);
+        assert_eq!(chain.records[0].previous_public_key, daemon.public_key);
+        assert_eq!(chain.records[1].previous_public_key, first.public_key);
+        chain.verify(&second.public_key).unwrap();
+
+        fs::remove_dir_all(root_path).unwrap();
+    }
+}
@@ -0,0 +1,244 @@
+use std::{fs, path::Path, sync::Arc};
+
+use anyhow::{anyhow, Context};
+use axum::Router;
+use base64::prelude::*;
+use hyper_util::{
+    rt::{TokioExecutor, TokioIo},
+    server::conn::auto,
+    service::TowerToHyperService,
+};
+use plugin::tls::{self, rustls::ServerConfig, FingerprintVerifier, ServerTls};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::{net::TcpListener, sync::broadcast::Receiver};
+use tokio_rustls::TlsAcceptor;
+
+use crate::{
+    config::DaemonConfig,
+    daemon::{Daemon, SignBox, TLS_FINGERPRINT_DOMAIN},
+    succession::SuccessionChain,
+};
+
+// 通过 /api 公开的 TLS 身份，signature 为 Daemon 私钥对 TLS_FINGERPRINT_DOMAIN 与证书指纹的签名
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TlsIdentity {
+    pub fingerprint: String,
+    pub signature: String,
+}
+
+impl TlsIdentity {
+    pub fn verify(&self, public_key: &str) -> anyhow::Result<()> {
+        SignBox {
+            public_key: public_key.to_string(),
+            signature: self.signature.clone(),
+        }
+        .verify_in_domain(
+            TLS_FINGERPRINT_DOMAIN,
+            &BASE64_URL_SAFE.decode(&self.fingerprint)?,
+        )
+    }
+}
+
+pub struct DaemonTls {
+    pub server_config: Arc<ServerConfig>,
+    pub identity: TlsIdentity,
+}
+
+impl DaemonTls {
+    pub fn load(
+        config: &DaemonConfig,
+        root_path: &Path,
+        daemon: &Daemon,
+    ) -> anyhow::Result<Option<Self>> {
+        let (cert_path, key_path) = match (&config.tls.cert, &config.tls.key) {
+            (Some(cert), Some(key)) => (root_path.join(cert), root_path.join(key)),
+            _ if config.tls.self_signed => {
+                let tls_dir = root_path.join("tls");
+                let cert_path = tls_dir.join("cert.pem");
+                let key_path = tls_dir.join("key.pem");
+                if !cert_path.exists() || !key_path.exists() {
+                    generate_self_signed(config, &cert_path, &key_path)?;
+                }
+                (cert_path, key_path)
+            }
+            _ => return Ok(None),
+        };
+
+        let server_tls = ServerTls::load(&cert_path, &key_path)?;
+        let signature = daemon
+            .sign_in_domain(
+                TLS_FINGERPRINT_DOMAIN,
+                &BASE64_URL_SAFE.decode(&server_tls.fingerprint)?,
+            )?
+            .signature;
+
+        Ok(Some(DaemonTls {
+            server_config: server_tls.config,
+            identity: TlsIdentity {
+                fingerprint: server_tls.fingerprint,
+                signature,
+            },
+        }))
+    }
+}
+
+fn generate_self_signed(
+    config: &DaemonConfig,
+    cert_path: &Path,
+    key_path: &Path,
+) -> anyhow::Result<()> {
+    let mut subject_alt_names = vec![String::from("localhost"), String::from("127.0.0.1")];
+    if !subject_alt_names.contains(&config.host) {
+        subject_alt_names.push(config.host.clone());
+    }
+    subject_alt_names.extend(config.tls.subject_alt_names.iter().cloned());
+
+    let key_pair = rcgen::KeyPair::generate().context("生成 TLS 私钥失败")?;
+    let cert = rcgen::CertificateParams::new(subject_alt_names)
+        .context("构建自签名证书参数失败")?
+        .self_signed(&key_pair)
+        .context("生成自签名证书失败")?;
+
+    fs::create_dir_all(cert_path.parent().unwrap())?;
+    fs::write(cert_path, cert.pem()).context("写入自签名证书失败")?;
+    tls::write_private(key_path, key_pair.serialize_pem().as_bytes())
+        .context("写入 TLS 私钥失败")?;
+
+    Ok(())
+}
+
+pub(crate) async fn serve(
+    tcp_listener: TcpListener,
+    app: Router,
+    server_config: Arc<ServerConfig>,
+    mut terminate: Receiver<()>,
+) {
+    let tls_acceptor = TlsAcceptor::from(server_config);
+    loop {
+        let (stream, _addr) = tokio::select! {
+            accepted = tcp_listener.accept() => match accepted {
+                Ok(value) => value,
+                Err(_) => continue,
+            },
+            _ = terminate.recv() => break,
+        };
+
+        let tls_acceptor = tls_acceptor.clone();
+        let app = app.clone();
+        tokio::spawn(async move {
+            let stream = match tls_acceptor.accept(stream).await {
+                Ok(stream) => stream,
+                Err(_) => return,
+            };
+            let _ = auto::Builder::new(TokioExecutor::new())
+                .serve_connection_with_upgrades(TokioIo::new(stream), TowerToHyperService::new(app))
+                .await;
+        });
+    }
+}
+
+// 远程 Daemon 的身份信息，tls_fingerprint 仅在使用 HTTPS 时存在
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RemoteIdentity {
+    pub public_key: String,
+    pub tls_fingerprint: Option<String>,
+}
+
+// 访问远程 Daemon 的 /api，并确认其出示的证书指纹经过 Daemon 身份签名；
+// 提供 pinned_public_key 时要求远程 Daemon 使用该公钥，或出示由其开始的轮换记录
+pub async fn fetch_remote_identity(
+    address: &str,
+    pinned_public_key: Option<&str>,
+) -> anyhow::Result<RemoteIdentity> {
+    let verifier = Arc::new(FingerprintVerifier::new(None));
+    let client_config = tls::rustls::ClientConfig::builder_with_provider(tls::provider())
+        .with_safe_default_protocol_versions()?
+        .dangerous()
+        .with_custom_certificate_verifier(verifier.clone())
+        .with_no_client_auth();
+    let client = reqwest::Client::builder()
+        .use_preconfigured_tls(client_config)
+        .build()?;
+
+    let api_address = url::Url::parse(address)?.join("api")?;
+    let https = api_address.scheme() == "https";
+    let body: Value = client
+        .get(api_address)
+        .send()
+        .await
+        .context("访问远程 Daemon 失败")?
+        .json()
+        .await
+        .context("解析远程 Daemon 信息失败")?;
+
+    let public_key = body["public_key"]
+        .as_str()
+        .ok_or(anyhow!("远程 Daemon 未返回公钥"))?
+        .to_string();
+    if let Some(pinned) = pinned_public_key.filter(|pinned| *pinned != public_key) {
+        let chain = SuccessionChain {
+            records: serde_json::from_value(body["successions"].clone()).unwrap_or_default(),
+        };
+        chain
+            .verify(&public_key)
+            .context("远程 Daemon 的轮换记录校验失败")?;
+        if !chain
+            .records
+            .iter()
+            .any(|record| record.previous_public_key == pinned)
+        {
+            return Err(anyhow!("远程 Daemon 的公钥与记录的公钥不一致"));
+        }
+    }
+    if !https {
+        return Ok(RemoteIdentity {
+            public_key,
+            tls_fingerprint: None,
+        });
+    }
+
+    let identity: TlsIdentity =
+        serde_json::from_value(body["tls"].clone()).context("远程 Daemon 未返回 TLS 身份")?;
+    if verifier.presented().as_ref() != Some(&identity.fingerprint) {
+        return Err(anyhow!("远程 Daemon 出示的证书与其声明的指纹不一致"));
+    }
+    identity
+        .verify(&public_key)
+        .context("远程 Daemon 的证书指纹签名校验失败")?;
+
+    Ok(RemoteIdentity {
+        public_key,
+        tls_fingerprint: Some(identity.fingerprint),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identity_requires_domain_signature() {
+        let daemon = Daemon::new_random().unwrap();
+        let fingerprint = BASE64_URL_SAFE.encode([7u8; 32]);
+        let signature = daemon
+            .sign_in_domain(
+                TLS_FINGERPRINT_DOMAIN,
+                &BASE64_URL_SAFE.decode(&fingerprint).unwrap(),
+            )
+            .unwrap()
+            .signature;
+        let identity = TlsIdentity {
+            fingerprint: fingerprint.clone(),
+            signature,
+        };
+        identity.verify(&daemon.public_key).unwrap();
+
+        // 与 /api/sig 相同，直接对指纹签名
+        let forged = TlsIdentity {
+            signature: daemon.sign(fingerprint.clone()).unwrap().signature,
+            fingerprint,
+        };
+        assert!(forged.verify(&daemon.public_key).is_err());
+    }
+}
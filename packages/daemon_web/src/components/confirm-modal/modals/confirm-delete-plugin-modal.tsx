@@ -11,6 +11,7 @@ import { ConfirmModalVariant } from "../typings";
 import { useMemo } from "react";
 import { useRecoilValue } from "recoil";
 import { connectionState } from "../../connection-provider/context";
+import { ClientMessage } from "../../../protocol/ClientMessage";
 
 export default function ConfirmDeletePluginModal() {
   const connection = useRecoilValue(connectionState);
@@ -23,15 +24,14 @@ export default function ConfirmDeletePluginModal() {
   }, [open, state]);
 
   const handleResult = (allow: boolean) => {
-    connection.ws?.send(
-      JSON.stringify({
-        type: "confirm/delete-plugin",
-        payload: {
-          name: plugin?.name,
-          allow,
-        },
-      })
-    );
+    const message: ClientMessage = {
+      type: "confirm/delete-plugin",
+      payload: {
+        name: plugin?.name ?? "",
+        allow,
+      },
+    };
+    connection.ws?.send(JSON.stringify(message));
     closeModal();
   };
 
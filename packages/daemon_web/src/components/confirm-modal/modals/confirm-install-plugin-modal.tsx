@@ -11,6 +11,7 @@ import { ConfirmModalVariant } from "../typings";
 import { useMemo } from "react";
 import { useRecoilValue } from "recoil";
 import { connectionState } from "../../connection-provider/context";
+import { ClientMessage } from "../../../protocol/ClientMessage";
 
 export default function ConfirmInstallPluginModal() {
   const connection = useRecoilValue(connectionState);
@@ -29,15 +30,14 @@ export default function ConfirmInstallPluginModal() {
   }, [state, open]);
 
   const handleResult = (allow: boolean) => {
-    connection.ws?.send(
-      JSON.stringify({
-        type: "confirm/install-plugin",
-        payload: {
-          name,
-          allow,
-        },
-      })
-    );
+    const message: ClientMessage = {
+      type: "confirm/install-plugin",
+      payload: {
+        name: name ?? "",
+        allow,
+      },
+    };
+    connection.ws?.send(JSON.stringify(message));
     closeModal();
   };
 
@@ -2,18 +2,15 @@ import { Button } from "@nextui-org/react";
 
 interface Props {
   reason: string;
+  onReconnect: () => void;
 }
 
-export default function ConnectionClose({ reason }: Props) {
+export default function ConnectionClose({ reason, onReconnect }: Props) {
   return (
     <div className="h-full flex flex-col items-center justify-center gap-3">
       <p className="text-red-600">连接已断开</p>
       <p className="text-sm">原因：{reason}</p>
-      <Button
-        color="primary"
-        className="mt-3"
-        onClick={() => location.reload()}
-      >
+      <Button color="primary" className="mt-3" onClick={onReconnect}>
         重新连接
       </Button>
     </div>
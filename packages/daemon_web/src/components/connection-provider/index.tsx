@@ -1,15 +1,32 @@
 import { PropsWithChildren, useEffect, useState } from "react";
 import ConnectionPending from "./pending";
-import { ConnectionStatus, IDaemon } from "./typings";
+import { ConnectionStatus, IDaemon, IPlugin } from "./typings";
 import ConnectionClose from "./close";
 import { useSetRecoilState } from "recoil";
 import { connectionState } from "./context";
 import useConfirmModal from "../confirm-modal/hooks/use-confirm-modal";
 import useOrigin from "../../hooks/use-origin";
+import { ServerMessage } from "../../protocol/ServerMessage";
+import { ClientMessage } from "../../protocol/ClientMessage";
+import { Resume } from "../../protocol/Resume";
+
+// 客户端支持的 /api/connect 协议版本
+const PROTOCOL_VERSIONS = [1, 2];
+
+// 上次收到的事件位置，重连时请求 Daemon 只补发之后的事件
+let resume: Resume | undefined;
+
+function updatePlugins(
+  daemon: IDaemon | undefined,
+  update: (plugins: IPlugin[]) => IPlugin[],
+): IDaemon | undefined {
+  return daemon && { ...daemon, plugins: update(daemon.plugins) };
+}
 
 export default function ConnectionProvider({ children }: PropsWithChildren) {
   const [status, setStatus] = useState(ConnectionStatus.Pending);
   const [closeReason, setCloseReason] = useState("");
+  const [attempt, setAttempt] = useState(0);
   const { confirmInstallPlugin, confirmDeletePlugin } = useConfirmModal();
 
   const setConnection = useSetRecoilState(connectionState);
@@ -24,23 +41,64 @@ export default function ConnectionProvider({ children }: PropsWithChildren) {
       : connectionOrigin;
     const ws = new WebSocket(`${origin}/api/connect`);
 
+    const send = (message: ClientMessage) => {
+      ws.send(JSON.stringify(message));
+    };
+
     const handleMessage = async (message: string) => {
-      const data: {
-        type: string;
-        // eslint-disable-next-line @typescript-eslint/no-explicit-any
-        payload: any;
-      } = JSON.parse(message);
+      const data: ServerMessage = JSON.parse(message);
       console.info("receive message", data);
       switch (data.type) {
-        case "ok":
+        case "hello":
+          send({
+            type: "hello",
+            payload: { versions: PROTOCOL_VERSIONS, resume },
+          });
+          break;
+        case "ready":
+          if (resume?.epoch !== data.payload.epoch) {
+            resume = { epoch: data.payload.epoch, seq: 0 };
+          }
           setStatus(ConnectionStatus.Open);
           break;
         case "daemon":
+          if (resume) resume.seq = data.payload.seq;
           setConnection((prev) => ({
             ...prev,
             daemon: data.payload as IDaemon,
           }));
           break;
+        case "plugin/added":
+        case "plugin/updated": {
+          const plugin = data.payload.plugin as IPlugin;
+          if (resume) resume.seq = data.payload.seq;
+          setConnection((prev) => ({
+            ...prev,
+            daemon: updatePlugins(prev.daemon, (plugins) => [
+              ...plugins.filter((item) => item.name !== plugin.name),
+              plugin,
+            ]),
+          }));
+          break;
+        }
+        case "plugin/removed": {
+          const name = data.payload.name;
+          if (resume) resume.seq = data.payload.seq;
+          setConnection((prev) => ({
+            ...prev,
+            daemon: updatePlugins(prev.daemon, (plugins) =>
+              plugins.filter((item) => item.name !== name),
+            ),
+          }));
+          break;
+        }
+        case "plugin/status":
+          if (resume) resume.seq = data.payload.seq;
+          break;
+        case "error":
+          console.error("protocol error", data.payload);
+          setCloseReason(data.payload.message);
+          break;
         case "confirm/install-plugin":
           confirmInstallPlugin(data.payload.name, data.payload.plugin);
           break;
@@ -73,6 +131,7 @@ export default function ConnectionProvider({ children }: PropsWithChildren) {
       setConnection((prev) => ({ ...prev, ws: undefined }));
     };
   }, [
+    attempt,
     connectionOrigin,
     confirmInstallPlugin,
     confirmDeletePlugin,
@@ -84,7 +143,12 @@ export default function ConnectionProvider({ children }: PropsWithChildren) {
   }
 
   if (status === ConnectionStatus.Close) {
-    return <ConnectionClose reason={closeReason} />;
+    return (
+      <ConnectionClose
+        reason={closeReason}
+        onReconnect={() => setAttempt((value) => value + 1)}
+      />
+    );
   }
 
   return <>{children}</>;
@@ -15,7 +15,7 @@ export interface IPlugin {
   assets_root: string;
   storage_root: string;
   entries: IPluginEntry[];
-  address?: string;
+  address?: string | null;
 }
 
 export interface IPluginEntry {
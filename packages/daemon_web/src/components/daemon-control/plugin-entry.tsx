@@ -1,5 +1,6 @@
 import { Button, Link } from "@nextui-org/react";
 import { IPlugin, IPluginEntry } from "../connection-provider/typings";
+import usePluginOrigin from "../../hooks/use-plugin-origin";
 
 interface Props {
   plugin: IPlugin;
@@ -8,9 +9,11 @@ interface Props {
 }
 
 export default function PluginEntry({ plugin, entry, onClose }: Props) {
+  const pluginOrigin = usePluginOrigin(plugin.name);
+
   return (
     <Button
-      startContent={<img src={plugin.address + entry.icon} />}
+      startContent={<img src={pluginOrigin + entry.icon} />}
       as={Link}
       href={`/plugin/${encodeURIComponent(plugin.name)}/${encodeURIComponent(
         entry.label
@@ -0,0 +1,11 @@
+import { useMemo } from "react";
+import useOrigin from "./use-origin";
+
+export default function usePluginOrigin(pluginName?: string): string {
+  const origin = useOrigin();
+
+  return useMemo(() => {
+    const daemonOrigin = origin.endsWith("/") ? origin.slice(0, -1) : origin;
+    return `${daemonOrigin}/plugins/${encodeURIComponent(pluginName ?? "")}`;
+  }, [origin, pluginName]);
+}
@@ -0,0 +1,9 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DeletePluginResponse } from "./DeletePluginResponse";
+import type { Hello } from "./Hello";
+import type { InstallPluginResponse } from "./InstallPluginResponse";
+import type { RpcCancel } from "./RpcCancel";
+import type { RpcRequest } from "./RpcRequest";
+import type { Subscription } from "./Subscription";
+
+export type ClientMessage = { "type": "hello", "payload": Hello } | { "type": "confirm/install-plugin", "payload": InstallPluginResponse } | { "type": "confirm/delete-plugin", "payload": DeletePluginResponse } | { "type": "request", "payload": RpcRequest } | { "type": "cancel", "payload": RpcCancel } | { "type": "subscribe", "payload": Subscription } | { "type": "unsubscribe", "payload": Subscription };
@@ -0,0 +1,5 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { LogLevel } from "./LogLevel";
+import type { LogSource } from "./LogSource";
+
+export type LogRecord = { time: number, level: LogLevel, source: LogSource, message: string, fields: { [key in string]?: string }, };
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { PluginEntry } from "./PluginEntry";
+import type { RequestLimits } from "./RequestLimits";
+import type { Schedule } from "./Schedule";
+import type { StaticFiles } from "./StaticFiles";
+
+export type Plugin = { name: string, wasm_root: string, assets_root: string, storage_root: string, entries: Array<PluginEntry>, address: string | null, tls_fingerprint?: string, static_files?: StaticFiles, request_limits?: RequestLimits, schedules?: Array<Schedule>, sql_max_size?: number, };
@@ -0,0 +1,5 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Plugin } from "./Plugin";
+import type { PluginHealth } from "./PluginHealth";
+
+export type PluginList = { plugins: Array<Plugin>, health: Array<PluginHealth>, };
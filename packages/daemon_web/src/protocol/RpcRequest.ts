@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DeletePluginParams } from "./DeletePluginParams";
+import type { InstallPluginParams } from "./InstallPluginParams";
+import type { QueryLogsParams } from "./QueryLogsParams";
+
+export type RpcRequest = { id: string, } & ({ "method": "plugin/list" } | { "method": "plugin/install", "params": InstallPluginParams } | { "method": "plugin/delete", "params": DeletePluginParams } | { "method": "logs/query", "params": QueryLogsParams });
@@ -0,0 +1,5 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { RpcError } from "./RpcError";
+import type { RpcResult } from "./RpcResult";
+
+export type RpcResponse = { id: string, } & ({ "result": RpcResult } | { "error": RpcError });
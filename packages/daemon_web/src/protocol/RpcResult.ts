@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { PluginDeleted } from "./PluginDeleted";
+import type { PluginInstalled } from "./PluginInstalled";
+import type { PluginList } from "./PluginList";
+import type { PluginLogs } from "./PluginLogs";
+
+export type RpcResult = { "method": "plugin/list", "data": PluginList } | { "method": "plugin/install", "data": PluginInstalled } | { "method": "plugin/delete", "data": PluginDeleted } | { "method": "logs/query", "data": PluginLogs };
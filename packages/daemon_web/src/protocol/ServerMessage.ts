@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DaemonSnapshot } from "./DaemonSnapshot";
+import type { DeletePluginRequest } from "./DeletePluginRequest";
+import type { Hello } from "./Hello";
+import type { InstallPluginRequest } from "./InstallPluginRequest";
+import type { PluginAdded } from "./PluginAdded";
+import type { PluginNotification } from "./PluginNotification";
+import type { PluginRemoved } from "./PluginRemoved";
+import type { PluginStatusChanged } from "./PluginStatusChanged";
+import type { PluginUpdated } from "./PluginUpdated";
+import type { ProtocolError } from "./ProtocolError";
+import type { Ready } from "./Ready";
+import type { RpcResponse } from "./RpcResponse";
+
+export type ServerMessage = { "type": "hello", "payload": Hello } | { "type": "ready", "payload": Ready } | { "type": "daemon", "payload": DaemonSnapshot } | { "type": "plugin/added", "payload": PluginAdded } | { "type": "plugin/removed", "payload": PluginRemoved } | { "type": "plugin/updated", "payload": PluginUpdated } | { "type": "plugin/status", "payload": PluginStatusChanged } | { "type": "confirm/install-plugin", "payload": InstallPluginRequest } | { "type": "confirm/delete-plugin", "payload": DeletePluginRequest } | { "type": "response", "payload": RpcResponse } | { "type": "notification", "payload": PluginNotification } | { "type": "error", "payload": ProtocolError };
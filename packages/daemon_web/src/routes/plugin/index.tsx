@@ -3,10 +3,12 @@ import { useParams } from "react-router-dom";
 import { useRecoilValue } from "recoil";
 import { connectionState } from "../../components/connection-provider/context";
 import WujieReact from "wujie-react";
+import usePluginOrigin from "../../hooks/use-plugin-origin";
 
 export default function PluginPage() {
   const connection = useRecoilValue(connectionState);
   const { pluginName, entryLabel } = useParams();
+  const pluginOrigin = usePluginOrigin(pluginName);
 
   const address = useMemo(() => {
     const plugin = connection.daemon?.plugins.find(
@@ -14,8 +16,8 @@ export default function PluginPage() {
     );
     const entry = plugin?.entries.find((item) => item.label === entryLabel);
 
-    return `${plugin?.address}${entry?.href}`;
-  }, [connection.daemon?.plugins, entryLabel, pluginName]);
+    return `${pluginOrigin}${entry?.href}`;
+  }, [connection.daemon?.plugins, entryLabel, pluginName, pluginOrigin]);
 
   return (
     <WujieReact
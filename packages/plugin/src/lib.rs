@@ -1,5 +1,9 @@
+pub mod metrics;
 pub mod models;
 mod plat_bindings;
+pub mod regist;
 mod server;
+pub mod tls;
 
-pub use server::{Options, PluginServer};
+pub use server::wasi::{request_id, REQUEST_ID_HEADER};
+pub use server::{LocalOptions, LocalPlugin, Options, PluginServer, ServerLimits};
@@ -0,0 +1,284 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+// Prometheus 文本格式的 Content-Type
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+// 延迟直方图的桶上限，单位为秒
+const BUCKETS: [f64; 12] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0,
+];
+// 每个 Plugin 最多记录的路由数量，超出后归入 other，避免路径过多导致指标膨胀
+const MAX_ROUTES: usize = 64;
+
+#[derive(Debug, Clone, Default)]
+pub struct Histogram {
+    // 每个桶内（不累计）的观测次数，最后一个为超出所有桶上限的次数
+    counts: [u64; BUCKETS.len() + 1],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    pub fn observe(&mut self, duration: Duration) {
+        let value = duration.as_secs_f64();
+        let index = BUCKETS
+            .iter()
+            .position(|bound| value <= *bound)
+            .unwrap_or(BUCKETS.len());
+        self.counts[index] += 1;
+        self.sum += value;
+        self.count += 1;
+    }
+}
+
+// 一个指标族，先写入 HELP 与 TYPE，之后写入的样本都属于该指标
+pub struct Family<'a> {
+    out: &'a mut String,
+    name: &'static str,
+}
+
+impl<'a> Family<'a> {
+    pub fn new(out: &'a mut String, name: &'static str, kind: &str, help: &str) -> Self {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} {}", name, kind);
+        Family { out, name }
+    }
+
+    pub fn sample(&mut self, labels: &[(&str, &str)], value: impl Display) {
+        write_sample(self.out, self.name, "", labels, None, value);
+    }
+
+    pub fn histogram(&mut self, labels: &[(&str, &str)], histogram: &Histogram) {
+        let mut cumulative = 0;
+        for (index, bound) in BUCKETS.iter().enumerate() {
+            cumulative += histogram.counts[index];
+            let bound = bound.to_string();
+            write_sample(
+                self.out,
+                self.name,
+                "_bucket",
+                labels,
+                Some(&bound),
+                cumulative,
+            );
+        }
+        write_sample(
+            self.out,
+            self.name,
+            "_bucket",
+            labels,
+            Some("+Inf"),
+            histogram.count,
+        );
+        write_sample(self.out, self.name, "_sum", labels, None, histogram.sum);
+        write_sample(self.out, self.name, "_count", labels, None, histogram.count);
+    }
+}
+
+fn write_sample(
+    out: &mut String,
+    name: &str,
+    suffix: &str,
+    labels: &[(&str, &str)],
+    le: Option<&str>,
+    value: impl Display,
+) {
+    let mut pairs: Vec<String> = labels
+        .iter()
+        .map(|(key, value)| format!("{}=\"{}\"", key, escape(value)))
+        .collect();
+    if let Some(le) = le {
+        pairs.push(format!("le=\"{}\"", le));
+    }
+    match pairs.is_empty() {
+        true => {
+            let _ = writeln!(out, "{}{} {}", name, suffix, value);
+        }
+        false => {
+            let _ = writeln!(out, "{}{}{{{}}} {}", name, suffix, pairs.join(","), value);
+        }
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct RequestKey {
+    route: String,
+    method: String,
+    status: u16,
+}
+
+// Plugin 处理请求的指标，由宿主在 Plugin 服务或 Daemon 进程内记录
+#[derive(Default)]
+pub struct PluginMetrics {
+    requests: Mutex<BTreeMap<RequestKey, u64>>,
+    durations: Mutex<BTreeMap<String, Histogram>>,
+    instantiations: Mutex<Histogram>,
+    traps: AtomicU64,
+    errors: AtomicU64,
+}
+
+// 某一时刻的 PluginMetrics，附带正在处理的请求数
+#[derive(Debug, Clone)]
+pub struct MetricsSnapshot {
+    requests: BTreeMap<RequestKey, u64>,
+    durations: BTreeMap<String, Histogram>,
+    instantiations: Histogram,
+    traps: u64,
+    errors: u64,
+    in_flight: usize,
+}
+
+impl PluginMetrics {
+    pub fn observe_request(&self, route: &str, method: &str, status: u16, duration: Duration) {
+        let mut durations = lock(&self.durations);
+        let route = match durations.contains_key(route) || durations.len() < MAX_ROUTES {
+            true => route,
+            false => "other",
+        };
+        durations
+            .entry(route.to_string())
+            .or_default()
+            .observe(duration);
+        *lock(&self.requests)
+            .entry(RequestKey {
+                route: route.to_string(),
+                method: method_label(method).to_string(),
+                status,
+            })
+            .or_default() += 1;
+    }
+
+    pub fn observe_instantiation(&self, duration: Duration) {
+        lock(&self.instantiations).observe(duration);
+    }
+
+    // Guest 处理请求失败，trap 与其他错误分别计数
+    pub fn record_error(&self, trap: bool) {
+        match trap {
+            true => self.traps.fetch_add(1, Ordering::Relaxed),
+            false => self.errors.fetch_add(1, Ordering::Relaxed),
+        };
+    }
+
+    pub fn snapshot(&self, in_flight: usize) -> MetricsSnapshot {
+        MetricsSnapshot {
+            requests: lock(&self.requests).clone(),
+            durations: lock(&self.durations).clone(),
+            instantiations: lock(&self.instantiations).clone(),
+            traps: self.traps.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+            in_flight,
+        }
+    }
+}
+
+// 写入多个 Plugin 的指标，同名指标的样本写在一起
+pub fn encode_plugins<S: AsRef<str>>(out: &mut String, plugins: &[(S, MetricsSnapshot)]) {
+    let mut family = Family::new(
+        out,
+        "plat_plugin_requests_total",
+        "counter",
+        "Plugin 处理的请求数量，按路由的第一段路径、方法与状态码区分",
+    );
+    for (plugin, snapshot) in plugins {
+        let plugin = plugin.as_ref();
+        for (key, count) in snapshot.requests.iter() {
+            let status = key.status.to_string();
+            family.sample(
+                &[
+                    ("plugin", plugin),
+                    ("route", &key.route),
+                    ("method", &key.method),
+                    ("status", &status),
+                ],
+                count,
+            );
+        }
+    }
+
+    let mut family = Family::new(
+        out,
+        "plat_plugin_request_duration_seconds",
+        "histogram",
+        "Plugin 返回响应首部的耗时",
+    );
+    for (plugin, snapshot) in plugins {
+        let plugin = plugin.as_ref();
+        for (route, histogram) in snapshot.durations.iter() {
+            family.histogram(&[("plugin", plugin), ("route", route)], histogram);
+        }
+    }
+
+    let mut family = Family::new(
+        out,
+        "plat_plugin_requests_in_flight",
+        "gauge",
+        "正在由 Guest 处理的请求数量，包括未关闭的 WebSocket 连接",
+    );
+    for (plugin, snapshot) in plugins {
+        let plugin = plugin.as_ref();
+        family.sample(&[("plugin", plugin)], snapshot.in_flight);
+    }
+
+    let mut family = Family::new(
+        out,
+        "plat_plugin_guest_errors_total",
+        "counter",
+        "Guest 处理请求失败的次数，kind 为 trap 或 error",
+    );
+    for (plugin, snapshot) in plugins {
+        let plugin = plugin.as_ref();
+        family.sample(&[("plugin", plugin), ("kind", "trap")], snapshot.traps);
+        family.sample(&[("plugin", plugin), ("kind", "error")], snapshot.errors);
+    }
+
+    let mut family = Family::new(
+        out,
+        "plat_plugin_instantiation_duration_seconds",
+        "histogram",
+        "实例化 Guest 组件的耗时",
+    );
+    for (plugin, snapshot) in plugins {
+        let plugin = plugin.as_ref();
+        family.histogram(&[("plugin", plugin)], &snapshot.instantiations);
+    }
+}
+
+// 标准请求方法原样作为方法标签，其余归入 OTHER，避免任意方法名导致指标膨胀
+fn method_label(method: &str) -> &str {
+    match method {
+        "GET" | "HEAD" | "POST" | "PUT" | "DELETE" | "CONNECT" | "OPTIONS" | "TRACE" | "PATCH" => {
+            method
+        }
+        _ => "OTHER",
+    }
+}
+
+// 请求路径的第一段，作为路由标签
+pub fn route_label(path: &str) -> &str {
+    if path.is_empty() {
+        return "/";
+    }
+    let rest = path.trim_start_matches('/');
+    match rest.find('/') {
+        Some(index) => &path[..path.len() - rest.len() + index],
+        None => path,
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    match mutex.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
@@ -1,6 +1,10 @@
+use std::collections::BTreeMap;
+
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use ts_rs::TS;
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema, TS)]
 pub struct Plugin {
     pub name: String,
     pub wasm_root: String,
@@ -8,12 +12,190 @@ pub struct Plugin {
     pub storage_root: String,
     pub entries: Vec<PluginEntry>,
     pub address: Option<String>,
+    // 远程 Plugin 服务启用 TLS 时其证书的指纹，Daemon 代理请求时固定该证书
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub tls_fingerprint: Option<String>,
+    // 由宿主直接提供的静态文件，不经过 Guest
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub static_files: Option<StaticFiles>,
+    // Guest 处理请求的超时与并发限制，未设置时使用默认值
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub request_limits: Option<RequestLimits>,
+    // 定时任务，到期时宿主调用 Guest 导出的 scheduler.on-tick
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub schedules: Option<Vec<Schedule>>,
+    // 由宿主管理的 SQL 数据库的大小上限，单位为字节，未设置时为 1 GiB；
+    // 只限制数据库，不包括 storage_root 中的文件
+    #[serde(
+        default,
+        alias = "storage_quota",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[ts(optional, type = "number")]
+    pub sql_max_size: Option<u64>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+impl Plugin {
+    pub fn sql_max_size(&self) -> u64 {
+        self.sql_max_size.unwrap_or(1024 * 1024 * 1024)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema, TS)]
 pub struct PluginEntry {
     pub label: String,
     pub icon: String,
     pub href: String,
     pub target: String,
 }
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema, TS)]
+pub struct StaticFiles {
+    // 请求路径前缀，匹配的 GET/HEAD 请求从 assets_root 中读取文件，例如 "/static"
+    pub prefix: String,
+    // 文件不存在时返回的页面（相对于 assets_root），仅用于浏览器页面请求，供单页应用使用；
+    // 未设置或请求不接受 HTML 时交给 Guest 处理
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub fallback: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, JsonSchema, TS)]
+pub struct RequestLimits {
+    // Guest 返回响应的超时时间，单位为秒，超时返回 504
+    #[serde(default = "RequestLimits::default_timeout")]
+    #[ts(type = "number")]
+    pub timeout: u64,
+    // 同时处理的最大请求数，超出时返回 503
+    #[serde(default = "RequestLimits::default_max_in_flight")]
+    pub max_in_flight: usize,
+}
+
+impl RequestLimits {
+    fn default_timeout() -> u64 {
+        60
+    }
+
+    fn default_max_in_flight() -> usize {
+        128
+    }
+}
+
+impl Default for RequestLimits {
+    fn default() -> Self {
+        RequestLimits {
+            timeout: Self::default_timeout(),
+            max_in_flight: Self::default_max_in_flight(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema, TS)]
+pub struct Schedule {
+    // 任务名称，作为 on-tick 的参数，在同一 Plugin 内唯一
+    pub name: String,
+    // cron 表达式（分 时 日 月 周，按本地时间），也可以使用 @hourly、@daily 等简写；
+    // 与 interval 必须且只能设置一个
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub cron: Option<String>,
+    // 执行间隔，单位为秒，从 Plugin 启动时开始计算
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional, type = "number")]
+    pub interval: Option<u64>,
+    // 单次执行的超时时间，单位为秒，超时后终止该次执行
+    #[serde(default = "Schedule::default_timeout")]
+    #[ts(type = "number")]
+    pub timeout: u64,
+}
+
+impl Schedule {
+    fn default_timeout() -> u64 {
+        300
+    }
+}
+
+// Plugin 通过 notify.send 发出的推送消息
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Notification {
+    pub topic: String,
+    pub payload: String,
+}
+
+// Guest 处理请求失败的记录，本地 Plugin 直接交给 Daemon，远程 Plugin 通过 /api/regist 连接发送
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GuestError {
+    pub request_id: String,
+    // 发生错误的时间，Unix 时间戳（毫秒）
+    pub time: u64,
+    pub method: String,
+    pub path: String,
+    pub message: String,
+    // Guest trap 时的 wasm 调用栈
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backtrace: Option<String>,
+}
+
+// 定时任务的一次执行，开始时以 running 状态发送一次，结束后再发送最终状态；
+// 本地 Plugin 直接交给 Daemon，远程 Plugin 通过 /api/regist 连接发送
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScheduleRun {
+    pub schedule: String,
+    // 开始执行的时间，Unix 时间戳（毫秒）
+    pub started_at: u64,
+    // 执行耗时，单位为毫秒，执行中时为空
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration: Option<u64>,
+    pub status: ScheduleRunStatus,
+    // 失败或超时的原因
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ScheduleRunStatus {
+    Running,
+    Succeeded,
+    // on-tick 返回错误或 Guest trap
+    Failed,
+    TimedOut,
+}
+
+// Plugin 日志中的一条记录，按行以 JSON 保存在宿主为 Plugin 管理的数据目录的 logs/plugin.log 中
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema, TS)]
+pub struct LogRecord {
+    // 记录时间，Unix 时间戳（毫秒）
+    #[ts(type = "number")]
+    pub time: u64,
+    pub level: LogLevel,
+    pub source: LogSource,
+    pub message: String,
+    // Guest 通过 log 接口附加的键值对
+    #[serde(default)]
+    pub fields: BTreeMap<String, String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, JsonSchema, TS)]
+#[serde(rename_all = "kebab-case")]
+pub enum LogLevel {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, JsonSchema, TS)]
+#[serde(rename_all = "kebab-case")]
+pub enum LogSource {
+    // Guest 的标准输出与标准错误
+    Stdout,
+    Stderr,
+    // Guest 通过 log 接口写入
+    Guest,
+}
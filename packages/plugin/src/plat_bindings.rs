@@ -1,12 +1,23 @@
+use std::collections::BTreeMap;
 use std::fs;
+use std::sync::Arc;
+use std::time::Duration;
 
 use channel::ChannelHandler;
 use lock::LockHandler;
+use tokio::sync::broadcast::Sender;
 use wasmtime::component::ResourceTable;
 use wasmtime_wasi::{async_trait, DirPerms, FilePerms, WasiCtx, WasiCtxBuilder, WasiView};
 use wasmtime_wasi_http::{WasiHttpCtx, WasiHttpView};
 
+use crate::models::{LogLevel, LogSource, Notification};
+use crate::server::kv::KvStore;
+use crate::server::logs::{LogOutput, PluginLog};
+use crate::server::sql::SqlSession;
 use crate::server::wasi::PlatServer;
+use crate::server::websocket::WebSockets;
+use tokio_tungstenite::tungstenite;
+use websocket::WebsocketHandler;
 
 wasmtime::component::bindgen!({
   path: "wit",
@@ -38,6 +49,11 @@ pub struct Component {
     wasi: WasiCtx,
     http: WasiHttpCtx,
     table: ResourceTable,
+    notifications: Sender<Notification>,
+    log: Arc<PluginLog>,
+    kv: Arc<KvStore>,
+    sql: SqlSession,
+    pub websockets: WebSockets,
 }
 
 impl Component {
@@ -59,10 +75,17 @@ impl Component {
         Component {
             table: ResourceTable::new(),
             wasi: WasiCtxBuilder::new()
-                .inherit_stdio()
+                .stdout(LogOutput {
+                    log: plat_server.log.clone(),
+                    source: LogSource::Stdout,
+                })
+                .stderr(LogOutput {
+                    log: plat_server.log.clone(),
+                    source: LogSource::Stderr,
+                })
                 .envs(&[
-                    ("daemon_address", &plat_server.daemon_address),
-                    ("daemon_public_key", &plat_server.daemon_public_key),
+                    ("daemon_address", plat_server.daemon_address.clone()),
+                    ("daemon_public_key", plat_server.daemon_public_key()),
                 ])
                 .preopened_dir(storage_path, "/storage", DirPerms::all(), FilePerms::all())
                 .unwrap()
@@ -70,6 +93,11 @@ impl Component {
                 .unwrap()
                 .build(),
             http: WasiHttpCtx::new(),
+            notifications: plat_server.notifications(),
+            log: plat_server.log.clone(),
+            kv: plat_server.kv.clone(),
+            sql: SqlSession::new(plat_server.sql.clone()),
+            websockets: WebSockets::default(),
         }
     }
 }
@@ -78,57 +106,328 @@ impl Component {
 impl lock::Host for Component {
     async fn create_lock_handler(
         &mut self,
-        name: wasmtime::component::__internal::String,
+        _name: wasmtime::component::__internal::String,
     ) -> wasmtime::Result<LockHandler> {
         todo!()
     }
 
-    async fn drop_lock_handler(&mut self, handler: LockHandler) -> wasmtime::Result<()> {
+    async fn drop_lock_handler(&mut self, _handler: LockHandler) -> wasmtime::Result<()> {
         todo!()
     }
 
-    async fn lock(&mut self, handler: LockHandler) -> wasmtime::Result<()> {
+    async fn lock(&mut self, _handler: LockHandler) -> wasmtime::Result<()> {
         todo!()
     }
 
-    async fn unlock(&mut self, handler: LockHandler) -> wasmtime::Result<()> {
+    async fn unlock(&mut self, _handler: LockHandler) -> wasmtime::Result<()> {
         todo!()
     }
 }
 
 #[async_trait]
 impl channel::Host for Component {
-    async fn create_channel_handler(&mut self, name: String) -> wasmtime::Result<ChannelHandler> {
+    async fn create_channel_handler(&mut self, _name: String) -> wasmtime::Result<ChannelHandler> {
         todo!()
     }
 
-    async fn drop_channel_handler(&mut self, handler: ChannelHandler) -> wasmtime::Result<()> {
+    async fn drop_channel_handler(&mut self, _handler: ChannelHandler) -> wasmtime::Result<()> {
         todo!()
     }
 
-    async fn send(&mut self, handler: ChannelHandler, message: String) -> wasmtime::Result<()> {
+    async fn send(&mut self, _handler: ChannelHandler, _message: String) -> wasmtime::Result<()> {
         todo!()
     }
 
-    async fn recv(&mut self, handler: ChannelHandler) -> wasmtime::Result<()> {
+    async fn recv(&mut self, _handler: ChannelHandler) -> wasmtime::Result<()> {
         todo!()
     }
 }
 
 #[async_trait]
 impl task::Host for Component {
-    async fn spawn(&mut self, payload: String) -> wasmtime::Result<()> {
+    async fn spawn(&mut self, _payload: String) -> wasmtime::Result<()> {
         todo!()
     }
 }
 
+#[async_trait]
+impl log::Host for Component {
+    async fn log(
+        &mut self,
+        level: log::Level,
+        message: String,
+        fields: Vec<(String, String)>,
+    ) -> wasmtime::Result<()> {
+        let level = match level {
+            log::Level::Trace => LogLevel::Trace,
+            log::Level::Debug => LogLevel::Debug,
+            log::Level::Info => LogLevel::Info,
+            log::Level::Warn => LogLevel::Warn,
+            log::Level::Error => LogLevel::Error,
+        };
+        self.log.write(
+            level,
+            LogSource::Guest,
+            message,
+            fields.into_iter().collect::<BTreeMap<_, _>>(),
+        );
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl notify::Host for Component {
+    async fn send(&mut self, topic: String, payload: String) -> wasmtime::Result<()> {
+        // 没有订阅者时丢弃消息
+        let _ = self.notifications.send(Notification { topic, payload });
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl websocket::Host for Component {
+    async fn accept(
+        &mut self,
+        protocol: Option<String>,
+    ) -> wasmtime::Result<Result<WebsocketHandler, String>> {
+        Ok(self
+            .websockets
+            .accept(protocol)
+            .map(|id| WebsocketHandler { id })
+            .map_err(|e| e.to_string()))
+    }
+
+    async fn send(
+        &mut self,
+        handler: WebsocketHandler,
+        message: websocket::Message,
+    ) -> wasmtime::Result<Result<(), String>> {
+        let message = match message {
+            websocket::Message::Text(text) => tungstenite::Message::Text(text),
+            websocket::Message::Binary(data) => tungstenite::Message::Binary(data),
+        };
+        Ok(self
+            .websockets
+            .send(handler.id, message)
+            .map_err(|e| e.to_string()))
+    }
+
+    async fn recv(
+        &mut self,
+        handler: WebsocketHandler,
+    ) -> wasmtime::Result<Option<websocket::Message>> {
+        Ok(self
+            .websockets
+            .recv(handler.id)
+            .await?
+            .and_then(guest_message))
+    }
+
+    async fn recv_timeout(
+        &mut self,
+        handler: WebsocketHandler,
+        timeout_ms: u32,
+    ) -> wasmtime::Result<websocket::RecvResult> {
+        let timeout = Duration::from_millis(timeout_ms as u64);
+        let result = match tokio::time::timeout(timeout, self.websockets.recv(handler.id)).await {
+            Err(_) => websocket::RecvResult::Timeout,
+            Ok(message) => match message?.and_then(guest_message) {
+                Some(message) => websocket::RecvResult::Message(message),
+                None => websocket::RecvResult::Closed,
+            },
+        };
+        Ok(result)
+    }
+
+    async fn close(
+        &mut self,
+        handler: WebsocketHandler,
+        code: u16,
+        reason: String,
+    ) -> wasmtime::Result<()> {
+        self.websockets.close(handler.id, code, reason);
+        Ok(())
+    }
+}
+
+// 只有文本与二进制消息会转发给 Guest
+fn guest_message(message: tungstenite::Message) -> Option<websocket::Message> {
+    match message {
+        tungstenite::Message::Text(text) => Some(websocket::Message::Text(text)),
+        tungstenite::Message::Binary(data) => Some(websocket::Message::Binary(data)),
+        _ => None,
+    }
+}
+
+// 键值存储的读写会访问文件，放到阻塞线程中执行；错误交给 Guest 处理
+async fn with_kv<T, F>(kv: &Arc<KvStore>, f: F) -> wasmtime::Result<Result<T, String>>
+where
+    T: Send + 'static,
+    F: FnOnce(&KvStore) -> anyhow::Result<T> + Send + 'static,
+{
+    let kv = kv.clone();
+    let result = tokio::task::spawn_blocking(move || f(&kv)).await?;
+    Ok(result.map_err(|e| format!("{:#}", e)))
+}
+
+#[async_trait]
+impl kv::Host for Component {
+    async fn get(&mut self, key: String) -> wasmtime::Result<Result<Option<Vec<u8>>, String>> {
+        with_kv(&self.kv, move |kv| kv.get(&key)).await
+    }
+
+    async fn set(&mut self, key: String, value: Vec<u8>) -> wasmtime::Result<Result<(), String>> {
+        with_kv(&self.kv, move |kv| {
+            kv.transaction(&[], vec![(key, Some(value))]).map(|_| ())
+        })
+        .await
+    }
+
+    async fn delete(&mut self, key: String) -> wasmtime::Result<Result<(), String>> {
+        with_kv(&self.kv, move |kv| {
+            kv.transaction(&[], vec![(key, None)]).map(|_| ())
+        })
+        .await
+    }
+
+    async fn list_prefix(
+        &mut self,
+        prefix: String,
+        limit: u32,
+    ) -> wasmtime::Result<Result<Vec<kv::Entry>, String>> {
+        with_kv(&self.kv, move |kv| {
+            Ok(kv
+                .list_prefix(&prefix, limit as usize)?
+                .into_iter()
+                .map(|(key, value)| kv::Entry { key, value })
+                .collect())
+        })
+        .await
+    }
+
+    async fn compare_and_swap(
+        &mut self,
+        key: String,
+        expected: Option<Vec<u8>>,
+        value: Option<Vec<u8>>,
+    ) -> wasmtime::Result<Result<bool, String>> {
+        with_kv(&self.kv, move |kv| {
+            kv.transaction(&[(key.clone(), expected)], vec![(key, value)])
+        })
+        .await
+    }
+
+    async fn transaction(
+        &mut self,
+        conditions: Vec<kv::Condition>,
+        writes: Vec<kv::Write>,
+    ) -> wasmtime::Result<Result<bool, String>> {
+        with_kv(&self.kv, move |kv| {
+            let conditions: Vec<_> = conditions
+                .into_iter()
+                .map(|condition| (condition.key, condition.value))
+                .collect();
+            let writes = writes
+                .into_iter()
+                .map(|write| (write.key, write.value))
+                .collect();
+            kv.transaction(&conditions, writes)
+        })
+        .await
+    }
+}
+
+impl From<sql::Value> for rusqlite::types::Value {
+    fn from(value: sql::Value) -> Self {
+        match value {
+            sql::Value::Null => rusqlite::types::Value::Null,
+            sql::Value::Integer(value) => rusqlite::types::Value::Integer(value),
+            sql::Value::Real(value) => rusqlite::types::Value::Real(value),
+            sql::Value::Text(value) => rusqlite::types::Value::Text(value),
+            sql::Value::Blob(value) => rusqlite::types::Value::Blob(value),
+        }
+    }
+}
+
+impl From<rusqlite::types::Value> for sql::Value {
+    fn from(value: rusqlite::types::Value) -> Self {
+        match value {
+            rusqlite::types::Value::Null => sql::Value::Null,
+            rusqlite::types::Value::Integer(value) => sql::Value::Integer(value),
+            rusqlite::types::Value::Real(value) => sql::Value::Real(value),
+            rusqlite::types::Value::Text(value) => sql::Value::Text(value),
+            rusqlite::types::Value::Blob(value) => sql::Value::Blob(value),
+        }
+    }
+}
+
+// SQL 的错误交给 Guest 处理
+fn sql_result<T>(result: anyhow::Result<T>) -> wasmtime::Result<Result<T, String>> {
+    Ok(result.map_err(|e| format!("{:#}", e)))
+}
+
+#[async_trait]
+impl sql::Host for Component {
+    async fn prepare(
+        &mut self,
+        sql: String,
+    ) -> wasmtime::Result<Result<sql::StatementHandler, String>> {
+        let result = self.sql.prepare(sql).await;
+        sql_result(result.map(|id| sql::StatementHandler { id }))
+    }
+
+    async fn execute(
+        &mut self,
+        handler: sql::StatementHandler,
+        params: Vec<sql::Value>,
+    ) -> wasmtime::Result<Result<u64, String>> {
+        let params = params.into_iter().map(Into::into).collect();
+        sql_result(self.sql.execute(handler.id, params).await)
+    }
+
+    async fn query(
+        &mut self,
+        handler: sql::StatementHandler,
+        params: Vec<sql::Value>,
+    ) -> wasmtime::Result<Result<sql::QueryResult, String>> {
+        let params = params.into_iter().map(Into::into).collect();
+        let result = self.sql.query(handler.id, params).await;
+        sql_result(result.map(|result| {
+            sql::QueryResult {
+                columns: result.columns,
+                rows: result
+                    .rows
+                    .into_iter()
+                    .map(|row| row.into_iter().map(Into::into).collect())
+                    .collect(),
+            }
+        }))
+    }
+
+    async fn finalize(&mut self, handler: sql::StatementHandler) -> wasmtime::Result<()> {
+        self.sql.finalize(handler.id).await
+    }
+
+    async fn begin(&mut self) -> wasmtime::Result<Result<(), String>> {
+        sql_result(self.sql.begin().await)
+    }
+
+    async fn commit(&mut self) -> wasmtime::Result<Result<(), String>> {
+        sql_result(self.sql.commit().await)
+    }
+
+    async fn rollback(&mut self) -> wasmtime::Result<Result<(), String>> {
+        sql_result(self.sql.rollback().await)
+    }
+}
+
 #[async_trait]
 impl plat::Host for Component {
-    async fn sig(&mut self, source: Vec<u8>) -> wasmtime::Result<Vec<u8>> {
+    async fn sig(&mut self, _source: Vec<u8>) -> wasmtime::Result<Vec<u8>> {
         todo!()
     }
 
-    async fn verify(&mut self, source: Vec<u8>, sig: Vec<u8>) -> wasmtime::Result<bool> {
+    async fn verify(&mut self, _source: Vec<u8>, _sig: Vec<u8>) -> wasmtime::Result<bool> {
         todo!()
     }
 }
@@ -0,0 +1,168 @@
+use std::{fs, path::Path};
+
+use anyhow::Context;
+use base64::prelude::*;
+use ed25519_dalek::{Signer, SigningKey};
+use rand::rngs::OsRng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::models::{GuestError, LogRecord, Notification, Plugin, ScheduleRun};
+
+// 签名内容的前缀，避免注册签名被用于其他场景
+const CHALLENGE_DOMAIN: &[u8] = b"plat-regist\0";
+
+// Daemon 通过 /api/regist 发送给 Plugin 的消息
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum RegistServerMessage {
+    // 连接建立后发送，Plugin 需要使用 nonce 证明自己已获授权
+    Challenge {
+        daemon_public_key: String,
+        nonce: String,
+        // Daemon 发送 Ping 的间隔（秒），Plugin 据此判断连接是否已断开
+        #[serde(default)]
+        ping_interval: Option<u64>,
+    },
+    Accepted,
+    Rejected {
+        reason: RegistRejectReason,
+        message: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum RegistRejectReason {
+    InvalidMessage,
+    Unauthorized,
+    DuplicateName,
+    PluginLimit,
+}
+
+impl RegistRejectReason {
+    // 令牌失效或消息格式错误时重试没有意义，重名与数量上限可能在 Daemon 清理旧连接后恢复
+    pub fn is_permanent(&self) -> bool {
+        matches!(
+            self,
+            RegistRejectReason::InvalidMessage | RegistRejectReason::Unauthorized
+        )
+    }
+}
+
+// Daemon 拒绝注册时返回的错误
+#[derive(Debug, Clone)]
+pub struct RegistRejected {
+    pub reason: RegistRejectReason,
+    pub message: String,
+}
+
+impl std::fmt::Display for RegistRejected {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Daemon 拒绝注册 Plugin（{:?}）：{}",
+            self.reason, self.message
+        )
+    }
+}
+
+impl std::error::Error for RegistRejected {}
+
+// Plugin 收到 Challenge 后发送的注册请求
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RegistRequest {
+    pub plugin: Plugin,
+    pub credential: Option<RegistCredential>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum RegistCredential {
+    // Daemon 配置中的注册令牌
+    Token {
+        token: String,
+    },
+    // 使用 Daemon 配置中已授权的密钥对 Challenge 的签名
+    Signature {
+        public_key: String,
+        signature: String,
+    },
+}
+
+// 注册完成后 Plugin 通过 /api/regist 发送给 Daemon 的消息
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum RegistPluginMessage {
+    Notify(Notification),
+    GuestError(GuestError),
+    Log(LogRecord),
+    ScheduleRun(ScheduleRun),
+}
+
+// Plugin 注册密钥，格式与 daemon.json 相同
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RegistKey {
+    pub public_key: String,
+    pub private_key: String,
+}
+
+impl RegistKey {
+    pub fn generate() -> Self {
+        let signing_key = SigningKey::generate(&mut OsRng);
+
+        RegistKey {
+            public_key: BASE64_URL_SAFE.encode(signing_key.verifying_key().as_bytes()),
+            private_key: BASE64_URL_SAFE.encode(signing_key.as_bytes()),
+        }
+    }
+
+    pub fn from_file(path: &Path) -> anyhow::Result<Self> {
+        let content =
+            fs::read(path).with_context(|| format!("读取注册密钥失败：{}", path.display()))?;
+        serde_json::from_slice(&content).context("解析注册密钥失败")
+    }
+
+    // 签名同时覆盖 Daemon 公钥与 Plugin 配置，签名无法被转交给其他 Daemon 或用于注册其他配置
+    pub fn credential(
+        &self,
+        nonce: &str,
+        daemon_public_key: &str,
+        plugin: &Plugin,
+    ) -> anyhow::Result<RegistCredential> {
+        let signing_key = SigningKey::from_bytes(
+            BASE64_URL_SAFE
+                .decode(&self.private_key)?
+                .as_slice()
+                .try_into()
+                .context("注册密钥格式错误")?,
+        );
+        let data = BASE64_URL_SAFE.decode(challenge_data(nonce, daemon_public_key, plugin)?)?;
+
+        Ok(RegistCredential::Signature {
+            public_key: self.public_key.clone(),
+            signature: BASE64_URL_SAFE.encode(signing_key.sign(&data).to_bytes()),
+        })
+    }
+}
+
+// 需要签名的内容：前缀、Daemon 公钥、Plugin 配置 JSON 的 SHA-256 摘要与 nonce 依次拼接后的
+// Base64 URL 编码；前三段长度固定，nonce 放在最后，拼接结果没有歧义
+pub fn challenge_data(
+    nonce: &str,
+    daemon_public_key: &str,
+    plugin: &Plugin,
+) -> anyhow::Result<String> {
+    let daemon_public_key = BASE64_URL_SAFE
+        .decode(daemon_public_key)
+        .ok()
+        .filter(|key| key.len() == 32)
+        .context("Daemon 公钥格式错误")?;
+
+    let mut data = CHALLENGE_DOMAIN.to_vec();
+    data.extend(daemon_public_key);
+    data.extend(Sha256::digest(serde_json::to_vec(plugin)?));
+    data.extend(BASE64_URL_SAFE.decode(nonce).context("nonce 格式错误")?);
+
+    Ok(BASE64_URL_SAFE.encode(data))
+}
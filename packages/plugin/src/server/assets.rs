@@ -0,0 +1,164 @@
+use base64::prelude::*;
+use http_body_util::{BodyExt, Empty};
+use hyper::header::{self, HeaderMap, HeaderValue};
+use hyper::{Method, Request, Response, StatusCode, Uri};
+use sha2::{Digest, Sha256};
+use tower::ServiceExt;
+use tower_http::services::{ServeDir, ServeFile};
+use wasmtime::Result;
+use wasmtime_wasi_http::bindings::http::types::ErrorCode;
+use wasmtime_wasi_http::body::HyperOutgoingBody;
+
+use crate::models::StaticFiles;
+use crate::server::wasi::{PlatServer, SyncBody};
+
+// 从 assets_root 中提供 static_files 配置的静态文件，返回 None 时请求交给 Guest 处理
+// 只借用请求的方法、地址与首部，以便请求体不满足 Sync 时返回的 Future 仍满足 Send
+pub async fn serve(
+    plat_server: &PlatServer,
+    method: &Method,
+    uri: &Uri,
+    headers: &HeaderMap,
+) -> Result<Option<Response<HyperOutgoingBody>>> {
+    let Some(config) = plat_server.plugin_config.static_files.as_ref() else {
+        return Ok(None);
+    };
+    if method != Method::GET && method != Method::HEAD {
+        return Ok(None);
+    }
+    let Some(path) = strip_prefix(config, uri.path()) else {
+        return Ok(None);
+    };
+
+    let assets_path = plat_server
+        .plugin_config_directory
+        .join(&plat_server.plugin_config.assets_root);
+    let serve_dir = ServeDir::new(&assets_path)
+        .precompressed_gzip()
+        .precompressed_br();
+    let mut res = serve_dir
+        .oneshot(file_request(method, uri, headers, path)?)
+        .await?;
+
+    if res.status() == StatusCode::NOT_FOUND {
+        match config.fallback.as_ref() {
+            Some(fallback) if accepts_html(headers) => {
+                let serve_file = ServeFile::new(assets_path.join(fallback))
+                    .precompressed_gzip()
+                    .precompressed_br();
+                res = serve_file
+                    .oneshot(file_request(method, uri, headers, "/")?)
+                    .await?;
+                if res.status() == StatusCode::NOT_FOUND {
+                    return Ok(None);
+                }
+            }
+            _ => return Ok(None),
+        }
+    }
+
+    let (mut parts, body) = res.into_parts();
+    // 目录重定向使用相对地址，避免丢失前缀以及 Daemon 代理的路径
+    if let Some(location) = parts.headers.get(header::LOCATION) {
+        let location = location.to_str().unwrap_or_default();
+        let relative = match location.trim_end_matches('/').rsplit_once('/') {
+            Some((_, name)) => format!("{}/", name),
+            None => "./".to_string(),
+        };
+        parts.headers.insert(header::LOCATION, relative.parse()?);
+    }
+    if parts.status == StatusCode::OK {
+        parts
+            .headers
+            .insert(header::VARY, HeaderValue::from_static("accept-encoding"));
+        if let Some(etag) = etag(&parts.headers) {
+            if matches_etag(headers, &etag) {
+                let mut res = Response::new(empty());
+                *res.status_mut() = StatusCode::NOT_MODIFIED;
+                for name in [header::LAST_MODIFIED, header::VARY] {
+                    if let Some(value) = parts.headers.get(&name) {
+                        res.headers_mut().insert(name, value.clone());
+                    }
+                }
+                res.headers_mut().insert(header::ETAG, etag.parse()?);
+                return Ok(Some(res));
+            }
+            parts.headers.insert(header::ETAG, etag.parse()?);
+        }
+    }
+
+    let body = SyncBody(std::sync::Mutex::new(body))
+        .map_err(|e| ErrorCode::InternalError(Some(e.to_string())))
+        .boxed();
+    Ok(Some(Response::from_parts(parts, body)))
+}
+
+// 返回去掉前缀后的路径，前缀只按完整的路径段匹配
+fn strip_prefix<'a>(config: &StaticFiles, path: &'a str) -> Option<&'a str> {
+    let prefix = config.prefix.trim_end_matches('/');
+    let rest = path.strip_prefix(prefix)?;
+    match rest {
+        "" => Some("/"),
+        rest if rest.starts_with('/') => Some(rest),
+        _ => None,
+    }
+}
+
+// 构造交给 ServeDir 的请求，保留查询参数与条件请求、Range、Accept-Encoding 等首部
+fn file_request(
+    method: &Method,
+    uri: &Uri,
+    headers: &HeaderMap,
+    path: &str,
+) -> Result<Request<Empty<hyper::body::Bytes>>> {
+    let uri = match uri.query() {
+        Some(query) => format!("{}?{}", path, query),
+        None => path.to_string(),
+    };
+    let mut file_req = Request::builder()
+        .method(method)
+        .uri(uri.parse::<Uri>()?)
+        .body(Empty::new())?;
+    *file_req.headers_mut() = headers.clone();
+    Ok(file_req)
+}
+
+fn accepts_html(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .any(|value| value.contains("text/html"))
+}
+
+// 根据修改时间、长度与编码生成弱 ETag，同一文件的不同压缩版本使用不同的 ETag
+fn etag(headers: &HeaderMap) -> Option<String> {
+    let last_modified = headers.get(header::LAST_MODIFIED)?;
+    let content_length = headers.get(header::CONTENT_LENGTH)?;
+    let mut hasher = Sha256::new();
+    hasher.update(last_modified.as_bytes());
+    hasher.update(content_length.as_bytes());
+    if let Some(encoding) = headers.get(header::CONTENT_ENCODING) {
+        hasher.update(encoding.as_bytes());
+    }
+    Some(format!(
+        "W/\"{}\"",
+        BASE64_URL_SAFE_NO_PAD.encode(&hasher.finalize()[..12])
+    ))
+}
+
+fn matches_etag(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|value| value.trim())
+        .any(|value| {
+            value == "*" || value.trim_start_matches("W/") == etag.trim_start_matches("W/")
+        })
+}
+
+fn empty() -> HyperOutgoingBody {
+    Empty::new().map_err(|never| match never {}).boxed()
+}
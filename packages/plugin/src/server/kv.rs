@@ -0,0 +1,525 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock, Weak};
+
+use anyhow::{anyhow, bail, Context};
+use sha2::{Digest, Sha256};
+
+// 保存在宿主为 Plugin 管理的数据目录下的数据文件名
+const FILE_NAME: &str = "plat.kv";
+// 与数据文件放在一起的锁文件，重写数据文件时不会被替换
+const LOCK_FILE_NAME: &str = "plat.kv.lock";
+// 键与值的大小上限
+const MAX_KEY_SIZE: usize = 1024;
+const MAX_VALUE_SIZE: usize = 4 * 1024 * 1024;
+// 单个事务最多包含的条件与写入数量
+const MAX_TRANSACTION_SIZE: usize = 1024;
+// 数据文件超过该大小且超过有效数据的两倍时重写，去掉已被覆盖或删除的记录
+const COMPACT_MIN_SIZE: u64 = 1024 * 1024;
+// 重写数据文件时每条记录的大致大小
+const COMPACT_RECORD_SIZE: usize = 1024 * 1024;
+
+// 事务中的写入，值为 None 表示删除
+pub(crate) type KvWrite = (String, Option<Vec<u8>>);
+// 事务的前置条件，值为 None 表示键不存在
+pub(crate) type KvCondition = (String, Option<Vec<u8>>);
+
+// 进程内已打开的键值存储，替换本地 Plugin 时新旧 PlatServer 使用同一个实例
+static STORES: OnceLock<Mutex<HashMap<PathBuf, Weak<KvStore>>>> = OnceLock::new();
+
+// Plugin 的键值存储，同一 Plugin 的所有 Store 共享一个实例；
+// 数据保存在内存中，每次写入以一条带校验的记录追加到数据文件，重启时按顺序重放
+pub struct KvStore {
+    path: PathBuf,
+    // 首次访问时加载数据文件
+    inner: Mutex<Option<KvInner>>,
+}
+
+struct KvInner {
+    entries: BTreeMap<String, Vec<u8>>,
+    file: File,
+    // 数据文件的大小与其中有效数据的大小
+    file_size: u64,
+    live_size: u64,
+    // 持有锁文件的排他锁，避免多个进程同时写入同一个数据文件；释放文件时解锁
+    _lock: fd_lock::RwLock<File>,
+}
+
+impl KvStore {
+    pub fn shared(data_path: PathBuf) -> Arc<Self> {
+        let path = data_path.join(FILE_NAME);
+        let mut stores = match STORES.get_or_init(Default::default).lock() {
+            Ok(stores) => stores,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        stores.retain(|_, store| store.strong_count() > 0);
+        if let Some(store) = stores.get(&path).and_then(Weak::upgrade) {
+            return store;
+        }
+        let store = Arc::new(KvStore {
+            path: path.clone(),
+            inner: Mutex::new(None),
+        });
+        stores.insert(path, Arc::downgrade(&store));
+        store
+    }
+
+    pub fn get(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        let mut inner = self.lock();
+        Ok(self.open(&mut inner)?.entries.get(key).cloned())
+    }
+
+    // 按键排序返回以 prefix 开头的条目，最多 limit 条
+    pub fn list_prefix(
+        &self,
+        prefix: &str,
+        limit: usize,
+    ) -> anyhow::Result<Vec<(String, Vec<u8>)>> {
+        let mut inner = self.lock();
+        Ok(self
+            .open(&mut inner)?
+            .entries
+            .range(prefix.to_string()..)
+            .take_while(|(key, _)| key.starts_with(prefix))
+            .take(limit)
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect())
+    }
+
+    // 所有条件满足时原子地执行全部写入并返回 true，否则不写入任何内容并返回 false
+    pub fn transaction(
+        &self,
+        conditions: &[KvCondition],
+        writes: Vec<KvWrite>,
+    ) -> anyhow::Result<bool> {
+        if conditions.len() > MAX_TRANSACTION_SIZE || writes.len() > MAX_TRANSACTION_SIZE {
+            bail!("事务中的条件或写入超过 {} 个", MAX_TRANSACTION_SIZE);
+        }
+        for (key, value) in writes.iter() {
+            validate(key, value.as_deref())?;
+        }
+
+        let mut inner = self.lock();
+        let store = self.open(&mut inner)?;
+        let satisfied = conditions
+            .iter()
+            .all(|(key, expected)| store.entries.get(key) == expected.as_ref());
+        if !satisfied {
+            return Ok(false);
+        }
+        if writes.is_empty() {
+            return Ok(true);
+        }
+
+        // 先写入文件再修改内存中的数据；写入失败时截断不完整的记录，数据不变
+        let record = encode_record(&writes);
+        let result = store
+            .file
+            .write_all(&record)
+            .and_then(|_| store.file.sync_data());
+        if let Err(e) = result {
+            let _ = store.file.set_len(store.file_size);
+            return Err(e).context("写入键值存储失败");
+        }
+        store.file_size += record.len() as u64;
+        for (key, value) in writes {
+            let previous = match value {
+                Some(value) => {
+                    store.live_size += entry_size(&key, &value);
+                    store.entries.insert(key.clone(), value)
+                }
+                None => store.entries.remove(&key),
+            };
+            if let Some(previous) = previous {
+                store.live_size -= entry_size(&key, &previous);
+            }
+        }
+
+        if store.file_size > COMPACT_MIN_SIZE && store.file_size > store.live_size * 2 {
+            if let Err(e) = self.compact(store) {
+                tracing::warn!("重写键值存储失败：{:#}", e);
+            }
+        }
+        Ok(true)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Option<KvInner>> {
+        match self.inner.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    fn open<'a>(&self, inner: &'a mut Option<KvInner>) -> anyhow::Result<&'a mut KvInner> {
+        if let Some(inner) = inner {
+            return Ok(inner);
+        }
+
+        let lock = lock_file(&self.path.with_file_name(LOCK_FILE_NAME))?;
+        let data = match fs::read(&self.path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e).context("读取键值存储失败"),
+        };
+        let mut entries = BTreeMap::new();
+        let mut offset = 0;
+        while offset < data.len() {
+            match decode_record(&data, offset) {
+                Decoded::Record(writes, next) => {
+                    for (key, value) in writes {
+                        match value {
+                            Some(value) => entries.insert(key, value),
+                            None => entries.remove(&key),
+                        };
+                    }
+                    offset = next;
+                }
+                Decoded::Incomplete => break,
+                // 完整但无法解析的记录不是中断的写入造成的，保留文件等待人工处理
+                Decoded::Corrupt => bail!(
+                    "键值存储 {} 在第 {} 字节处的记录已损坏",
+                    self.path.display(),
+                    offset
+                ),
+            }
+        }
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .context("打开键值存储失败")?;
+        // 写入过程中进程退出时最后一条记录超出文件末尾，丢弃该事务
+        if offset < data.len() {
+            tracing::warn!(
+                path = %self.path.display(),
+                "键值存储末尾的记录不完整，已丢弃 {} 字节",
+                data.len() - offset
+            );
+            file.set_len(offset as u64).context("截断键值存储失败")?;
+        }
+        let live_size = entries
+            .iter()
+            .map(|(key, value)| entry_size(key, value))
+            .sum();
+        Ok(inner.insert(KvInner {
+            entries,
+            file,
+            file_size: offset as u64,
+            live_size,
+            _lock: lock,
+        }))
+    }
+
+    // 将当前数据写入临时文件后替换数据文件
+    fn compact(&self, store: &mut KvInner) -> anyhow::Result<()> {
+        let temp_path = self.path.with_extension("kv.tmp");
+        let mut temp = File::create(&temp_path)?;
+        let mut file_size = 0;
+        let mut batch = Vec::new();
+        let mut batch_size = 0;
+        for (key, value) in store.entries.iter() {
+            batch.push((key.clone(), Some(value.clone())));
+            batch_size += entry_size(key, value) as usize;
+            if batch_size >= COMPACT_RECORD_SIZE {
+                let record = encode_record(&batch);
+                temp.write_all(&record)?;
+                file_size += record.len() as u64;
+                batch.clear();
+                batch_size = 0;
+            }
+        }
+        if !batch.is_empty() {
+            let record = encode_record(&batch);
+            temp.write_all(&record)?;
+            file_size += record.len() as u64;
+        }
+        temp.sync_all()?;
+        drop(temp);
+        // 在替换之前打开，替换后文件句柄仍指向新的数据文件
+        let file = OpenOptions::new().append(true).open(&temp_path)?;
+        fs::rename(&temp_path, &self.path)?;
+        // 同步目录，确保替换在断电后仍然有效
+        #[cfg(unix)]
+        if let Some(parent) = self.path.parent() {
+            File::open(parent)?.sync_all()?;
+        }
+
+        store.file = file;
+        store.file_size = file_size;
+        Ok(())
+    }
+}
+
+// 以非阻塞方式获取锁文件的排他锁，锁由打开的文件持有，文件关闭时释放
+fn lock_file(path: &Path) -> anyhow::Result<fd_lock::RwLock<File>> {
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path)
+        .context("打开键值存储的锁文件失败")?;
+    let mut lock = fd_lock::RwLock::new(file);
+    match lock.try_write() {
+        // 守卫只借用 lock，不在释放时解锁，锁随文件一同释放
+        Ok(guard) => std::mem::forget(guard),
+        Err(e) if e.kind() == ErrorKind::WouldBlock => {
+            return Err(anyhow!("键值存储正被其他进程使用：{}", path.display()));
+        }
+        Err(e) => return Err(e).context("锁定键值存储失败"),
+    }
+    Ok(lock)
+}
+
+fn validate(key: &str, value: Option<&[u8]>) -> anyhow::Result<()> {
+    if key.is_empty() || key.len() > MAX_KEY_SIZE {
+        bail!("键的长度必须在 1 到 {} 字节之间", MAX_KEY_SIZE);
+    }
+    if value.is_some_and(|value| value.len() > MAX_VALUE_SIZE) {
+        bail!("值超过大小上限：{} 字节", MAX_VALUE_SIZE);
+    }
+    Ok(())
+}
+
+fn entry_size(key: &str, value: &[u8]) -> u64 {
+    (key.len() + value.len() + 9) as u64
+}
+
+// 记录格式：内容长度（u32）、内容 SHA-256 的前 4 字节、内容；
+// 内容为依次排列的写入：键长度（u32）、键、是否有值（u8）、值长度（u32）、值
+fn encode_record(writes: &[KvWrite]) -> Vec<u8> {
+    let mut payload = Vec::new();
+    for (key, value) in writes {
+        payload.extend((key.len() as u32).to_le_bytes());
+        payload.extend(key.as_bytes());
+        match value {
+            Some(value) => {
+                payload.push(1);
+                payload.extend((value.len() as u32).to_le_bytes());
+                payload.extend(value);
+            }
+            None => payload.push(0),
+        }
+    }
+
+    let mut record = Vec::with_capacity(payload.len() + 8);
+    record.extend((payload.len() as u32).to_le_bytes());
+    record.extend(&Sha256::digest(&payload)[..4]);
+    record.extend(payload);
+    record
+}
+
+enum Decoded {
+    // 记录中的写入与下一条记录的位置
+    Record(Vec<KvWrite>, usize),
+    // 记录超出文件末尾
+    Incomplete,
+    // 记录完整但校验失败或内容无法解析
+    Corrupt,
+}
+
+// 解析 offset 处的记录
+fn decode_record(data: &[u8], offset: usize) -> Decoded {
+    let mut reader = Reader { data, offset };
+    let (Some(length), Some(checksum)) = (reader.u32(), reader.bytes(4)) else {
+        return Decoded::Incomplete;
+    };
+    let Some(payload) = reader.bytes(length as usize) else {
+        return Decoded::Incomplete;
+    };
+    if Sha256::digest(payload)[..4] != *checksum {
+        return Decoded::Corrupt;
+    }
+    match decode_payload(payload) {
+        Some(writes) => Decoded::Record(writes, reader.offset),
+        None => Decoded::Corrupt,
+    }
+}
+
+fn decode_payload(payload: &[u8]) -> Option<Vec<KvWrite>> {
+    let mut reader = Reader {
+        data: payload,
+        offset: 0,
+    };
+    let mut writes = Vec::new();
+    while reader.offset < payload.len() {
+        let key_length = reader.u32()? as usize;
+        let key = String::from_utf8(reader.bytes(key_length)?.to_vec()).ok()?;
+        let value = match reader.bytes(1)?[0] {
+            0 => None,
+            _ => {
+                let value_length = reader.u32()? as usize;
+                Some(reader.bytes(value_length)?.to_vec())
+            }
+        };
+        writes.push((key, value));
+    }
+    Some(writes)
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, length: usize) -> Option<&'a [u8]> {
+        let bytes = self
+            .data
+            .get(self.offset..self.offset.checked_add(length)?)?;
+        self.offset += length;
+        Some(bytes)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.bytes(4)?.try_into().ok()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    fn temp_store(name: &str) -> KvStore {
+        let dir = env::temp_dir().join(format!("plat-kv-{}-{}", name, rand::random::<u64>()));
+        fs::create_dir_all(&dir).unwrap();
+        KvStore {
+            path: dir.join(FILE_NAME),
+            inner: Mutex::new(None),
+        }
+    }
+
+    fn reopen(store: KvStore) -> KvStore {
+        let path = store.path.clone();
+        drop(store);
+        KvStore {
+            path,
+            inner: Mutex::new(None),
+        }
+    }
+
+    fn put(store: &KvStore, key: &str, value: &[u8]) {
+        let writes = vec![(key.to_string(), Some(value.to_vec()))];
+        assert!(store.transaction(&[], writes).unwrap());
+    }
+
+    #[test]
+    fn record_round_trips() {
+        let writes = vec![
+            ("a".to_string(), Some(b"1".to_vec())),
+            ("键".to_string(), Some(Vec::new())),
+            ("b".to_string(), None),
+        ];
+        let record = encode_record(&writes);
+        match decode_record(&record, 0) {
+            Decoded::Record(decoded, next) => {
+                assert_eq!(decoded, writes);
+                assert_eq!(next, record.len());
+            }
+            _ => panic!("记录解析失败"),
+        }
+    }
+
+    #[test]
+    fn decode_distinguishes_torn_and_corrupt_records() {
+        let record = encode_record(&[("a".to_string(), Some(b"value".to_vec()))]);
+        for length in 0..record.len() {
+            assert!(matches!(
+                decode_record(&record[..length], 0),
+                Decoded::Incomplete
+            ));
+        }
+
+        let mut corrupt = record.clone();
+        *corrupt.last_mut().unwrap() ^= 1;
+        assert!(matches!(decode_record(&corrupt, 0), Decoded::Corrupt));
+    }
+
+    #[test]
+    fn open_discards_torn_tail() {
+        let store = temp_store("torn");
+        put(&store, "a", b"1");
+        put(&store, "b", b"2");
+        let size = fs::metadata(&store.path).unwrap().len();
+
+        let record = encode_record(&[("c".to_string(), Some(b"3".to_vec()))]);
+        let mut file = OpenOptions::new().append(true).open(&store.path).unwrap();
+        file.write_all(&record[..record.len() - 1]).unwrap();
+        drop(file);
+
+        let store = reopen(store);
+        assert_eq!(store.get("a").unwrap(), Some(b"1".to_vec()));
+        assert_eq!(store.get("b").unwrap(), Some(b"2".to_vec()));
+        assert_eq!(store.get("c").unwrap(), None);
+        assert_eq!(fs::metadata(&store.path).unwrap().len(), size);
+
+        put(&store, "c", b"3");
+        let store = reopen(store);
+        assert_eq!(store.get("c").unwrap(), Some(b"3".to_vec()));
+    }
+
+    #[test]
+    fn open_fails_on_corrupt_record_and_keeps_file() {
+        let store = temp_store("corrupt");
+        put(&store, "a", b"1");
+        put(&store, "b", b"2");
+
+        let mut data = fs::read(&store.path).unwrap();
+        let last = data.len() - 1;
+        data[last] ^= 1;
+        fs::write(&store.path, &data).unwrap();
+
+        let store = reopen(store);
+        assert!(store.get("a").is_err());
+        assert_eq!(fs::read(&store.path).unwrap(), data);
+    }
+
+    #[test]
+    fn compact_keeps_live_entries() {
+        let store = temp_store("compact");
+        for index in 0..100 {
+            put(&store, "counter", index.to_string().as_bytes());
+            put(&store, &format!("key-{}", index % 10), &[index as u8; 64]);
+        }
+        assert!(store
+            .transaction(&[], vec![("key-0".to_string(), None)])
+            .unwrap());
+        let size = fs::metadata(&store.path).unwrap().len();
+
+        {
+            let mut inner = store.lock();
+            let inner = store.open(&mut inner).unwrap();
+            store.compact(inner).unwrap();
+        }
+        assert!(fs::metadata(&store.path).unwrap().len() < size);
+        assert!(!store.path.with_extension("kv.tmp").exists());
+
+        // 重写后的文件继续追加写入
+        put(&store, "after", b"compact");
+        let store = reopen(store);
+        assert_eq!(store.get("counter").unwrap(), Some(b"99".to_vec()));
+        assert_eq!(store.get("key-0").unwrap(), None);
+        assert_eq!(store.get("key-9").unwrap(), Some(vec![99; 64]));
+        assert_eq!(store.get("after").unwrap(), Some(b"compact".to_vec()));
+        assert_eq!(store.list_prefix("key-", 100).unwrap().len(), 9);
+    }
+
+    #[test]
+    fn open_locks_store() {
+        let store = temp_store("lock");
+        put(&store, "a", b"1");
+
+        let other = KvStore {
+            path: store.path.clone(),
+            inner: Mutex::new(None),
+        };
+        assert!(other.get("a").is_err());
+
+        drop(store);
+        assert_eq!(other.get("a").unwrap(), Some(b"1".to_vec()));
+    }
+}
@@ -0,0 +1,127 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context;
+use http_body_util::Limited;
+use hyper::body::{Body, Bytes};
+use hyper::{Request, Response};
+use tokio::sync::broadcast::{Receiver, Sender};
+use wasmtime::Result;
+use wasmtime_wasi_http::body::HyperOutgoingBody;
+
+use crate::metrics::MetricsSnapshot;
+use crate::models::{GuestError, LogRecord, Notification, Plugin, ScheduleRun};
+use crate::server::wasi::PlatServer;
+
+use super::plugin_server::{exceeds_body_limit, payload_too_large, route_request, spawn_on_start};
+use super::scheduler::spawn_schedules;
+
+pub struct LocalOptions {
+    pub daemon_address: String,
+    pub daemon_public_key: String,
+    // 对外公开的 Plugin 地址，由 Daemon 代理
+    pub address: String,
+    // 宿主为 Plugin 管理的数据（日志等）所在目录，位于插件目录之外，重新安装时保留
+    pub data_dir: PathBuf,
+    // Daemon 是否通过 HTTPS 提供服务
+    pub https: bool,
+    // 错误响应中包含错误详情与 wasm 调用栈
+    pub debug: bool,
+    // 请求体的最大字节数，超出时返回 413
+    pub max_body_size: u64,
+}
+
+// 运行在 Daemon 进程内的 Plugin，不监听端口，也不通过 /api/regist 注册，
+// 由 Daemon 直接将请求交给 PlatServer 处理
+pub struct LocalPlugin {
+    terminate: Sender<()>,
+    plat_server: Arc<PlatServer>,
+    max_body_size: u64,
+    pub path: PathBuf,
+}
+
+impl LocalPlugin {
+    pub fn new(plugin_path: PathBuf, options: LocalOptions) -> anyhow::Result<Self> {
+        let plugin_dir = plugin_path.parent().unwrap().to_path_buf();
+
+        let mut plat_server = PlatServer::new(
+            plugin_dir.join("plugin.json"),
+            options.daemon_address,
+            options.data_dir,
+        )
+        .context("创建 PlatServer 失败")?;
+        plat_server.https = options.https;
+        plat_server.debug = options.debug;
+        plat_server.set_daemon_public_key(options.daemon_public_key);
+        plat_server.plugin_config.address.replace(options.address);
+
+        let plat_server = Arc::new(plat_server);
+        let (terminate, _rx) = tokio::sync::broadcast::channel::<()>(4);
+        spawn_on_start(plat_server.clone(), terminate.clone());
+        spawn_schedules(plat_server.clone(), terminate.clone());
+
+        Ok(LocalPlugin {
+            terminate,
+            plat_server,
+            max_body_size: options.max_body_size,
+            path: plugin_dir,
+        })
+    }
+
+    pub fn plugin(&self) -> &Plugin {
+        &self.plat_server.plugin_config
+    }
+
+    // Guest 发出的推送消息，由 Daemon 转发给用户连接
+    pub fn subscribe_notifications(&self) -> Receiver<Notification> {
+        self.plat_server.subscribe_notifications()
+    }
+
+    // Guest 处理请求失败的记录，由 Daemon 保存
+    pub fn subscribe_errors(&self) -> Receiver<GuestError> {
+        self.plat_server.subscribe_errors()
+    }
+
+    // Guest 的输出与日志记录，由 Daemon 转发给跟随日志的连接
+    pub fn subscribe_logs(&self) -> Receiver<LogRecord> {
+        self.plat_server.log.subscribe()
+    }
+
+    // 定时任务的执行记录，由 Daemon 保存
+    pub fn subscribe_schedule_runs(&self) -> Receiver<ScheduleRun> {
+        self.plat_server.subscribe_schedule_runs()
+    }
+
+    // 调用 Guest 导出的健康检查，返回不健康的原因；超过 timeout 未返回时视为不健康
+    pub async fn check_health(&self, timeout: Duration) -> std::result::Result<(), String> {
+        self.plat_server.check_health(timeout).await
+    }
+
+    // 处理请求的指标，由 Daemon 的 /api/metrics 输出
+    pub fn metrics(&self) -> MetricsSnapshot {
+        self.plat_server.metrics_snapshot()
+    }
+
+    // 从日志文件中读取最后的 limit 条记录
+    pub fn tail_logs(&self, limit: usize) -> Vec<LogRecord> {
+        self.plat_server.log.tail(limit)
+    }
+
+    pub async fn handle_request<B>(&self, req: Request<B>) -> Result<Response<HyperOutgoingBody>>
+    where
+        B: Body<Data = Bytes> + Send + Unpin + 'static,
+        B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        if exceeds_body_limit(&req, self.max_body_size) {
+            return payload_too_large();
+        }
+        let req = req.map(|body| Limited::new(body, self.max_body_size as usize));
+        route_request(&self.plat_server, req).await
+    }
+
+    pub async fn stop(&self) {
+        tracing::info!(plugin = %self.plugin().name, "本地 Plugin 正在停止");
+        let _ = self.terminate.send(());
+    }
+}
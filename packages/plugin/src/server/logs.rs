@@ -0,0 +1,277 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use hyper::body::Bytes;
+use tokio::sync::broadcast::{self, Receiver, Sender};
+use wasmtime_wasi::{async_trait, HostOutputStream, StdoutStream, StreamResult, Subscribe};
+
+use crate::models::{LogLevel, LogRecord, LogSource};
+
+// 单个日志文件的大小上限，超出后轮转
+const MAX_FILE_SIZE: u64 = 4 * 1024 * 1024;
+// 轮转后保留的旧日志文件数量，依次为 plugin.log.1、plugin.log.2
+const MAX_ROTATED_FILES: usize = 2;
+// 转发给 Daemon 或跟随日志的连接之前最多缓存的记录数量
+const LOG_CAPACITY: usize = 1024;
+// 等待写入文件的最大记录数量，写入跟不上时丢弃新的记录
+const WRITE_QUEUE_CAPACITY: usize = 4096;
+// Guest 输出的单行最大长度，超出时直接写入
+const MAX_LINE_LENGTH: usize = 16 * 1024;
+
+// Plugin 的日志文件，保存在宿主为 Plugin 管理的数据目录的 logs 文件夹中；
+// 文件由独立的线程写入，Guest 输出日志时不会阻塞在文件读写上
+pub struct PluginLog {
+    dir: PathBuf,
+    writer: SyncSender<LogRecord>,
+    // 写入队列已满时丢弃的记录数量，由写入线程报告
+    dropped: Arc<AtomicU64>,
+    // 是否同时输出到宿主进程的标准输出
+    echo: AtomicBool,
+    records: Sender<LogRecord>,
+}
+
+impl PluginLog {
+    pub fn new(data_dir: PathBuf) -> Self {
+        let dir = data_dir.join("logs");
+        let (writer, queue) = mpsc::sync_channel(WRITE_QUEUE_CAPACITY);
+        let dropped = Arc::new(AtomicU64::new(0));
+        let mut log_writer = LogWriter {
+            dir: dir.clone(),
+            file: None,
+        };
+        let writer_dropped = dropped.clone();
+        // PluginLog 释放后队列关闭，线程随之结束
+        std::thread::spawn(move || {
+            for record in queue {
+                if let Err(e) = log_writer.append(&record) {
+                    tracing::warn!("写入 Plugin 日志失败：{:?}", e);
+                }
+                let dropped = writer_dropped.swap(0, Ordering::Relaxed);
+                if dropped > 0 {
+                    tracing::warn!(dropped, "Plugin 日志写入过慢，已丢弃部分日志");
+                }
+            }
+        });
+
+        PluginLog {
+            dir,
+            writer,
+            dropped,
+            echo: AtomicBool::new(false),
+            records: broadcast::channel(LOG_CAPACITY).0,
+        }
+    }
+
+    pub fn set_echo(&self, echo: bool) {
+        self.echo.store(echo, Ordering::Relaxed);
+    }
+
+    pub fn subscribe(&self) -> Receiver<LogRecord> {
+        self.records.subscribe()
+    }
+
+    pub fn write(
+        &self,
+        level: LogLevel,
+        source: LogSource,
+        message: String,
+        fields: BTreeMap<String, String>,
+    ) {
+        let record = LogRecord {
+            time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|time| time.as_millis() as u64)
+                .unwrap_or_default(),
+            level,
+            source,
+            message,
+            fields,
+        };
+        if self.echo.load(Ordering::Relaxed) {
+            match source {
+                LogSource::Stdout => println!("{}", record.message),
+                LogSource::Stderr => eprintln!("{}", record.message),
+                LogSource::Guest => {
+                    let mut line = format!("[{:?}] {}", record.level, record.message);
+                    for (key, value) in record.fields.iter() {
+                        line.push_str(&format!(" {}={}", key, value));
+                    }
+                    println!("{}", line);
+                }
+            }
+        }
+        match self.writer.try_send(record.clone()) {
+            Ok(()) => (),
+            Err(TrySendError::Full(_)) => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(TrySendError::Disconnected(_)) => tracing::warn!("Plugin 日志写入线程已退出"),
+        }
+        // 没有订阅者时丢弃记录
+        let _ = self.records.send(record);
+    }
+
+    // 按时间顺序返回最后的 limit 条记录，当前文件不足时继续读取轮转后的文件；
+    // 尚在写入队列中的记录不会包含在内
+    pub fn tail(&self, limit: usize) -> Vec<LogRecord> {
+        let mut records = VecDeque::new();
+        for index in 0..=MAX_ROTATED_FILES {
+            if records.len() >= limit {
+                break;
+            }
+            let Ok(content) = fs::read_to_string(log_path(&self.dir, index)) else {
+                break;
+            };
+            for line in content.lines().rev() {
+                if records.len() >= limit {
+                    break;
+                }
+                if let Ok(record) = serde_json::from_str(line) {
+                    records.push_front(record);
+                }
+            }
+        }
+        records.into()
+    }
+}
+
+// 写入线程持有的日志文件
+struct LogWriter {
+    dir: PathBuf,
+    // 当前日志文件与已写入的大小，首次写入时打开
+    file: Option<(File, u64)>,
+}
+
+impl LogWriter {
+    fn append(&mut self, record: &LogRecord) -> anyhow::Result<()> {
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+
+        if let Some((_, size)) = self.file.as_ref() {
+            if size + line.len() as u64 > MAX_FILE_SIZE {
+                self.file.take();
+                self.rotate()?;
+            }
+        }
+        if self.file.is_none() {
+            fs::create_dir_all(&self.dir)?;
+            let current = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(log_path(&self.dir, 0))?;
+            let size = current.metadata()?.len();
+            self.file.replace((current, size));
+        }
+
+        let (current, size) = self.file.as_mut().unwrap();
+        current.write_all(&line)?;
+        *size += line.len() as u64;
+        Ok(())
+    }
+
+    fn rotate(&self) -> anyhow::Result<()> {
+        for index in (0..MAX_ROTATED_FILES).rev() {
+            let from = log_path(&self.dir, index);
+            if from.exists() {
+                fs::rename(from, log_path(&self.dir, index + 1))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn log_path(dir: &Path, index: usize) -> PathBuf {
+    match index {
+        0 => dir.join("plugin.log"),
+        index => dir.join(format!("plugin.log.{}", index)),
+    }
+}
+
+// 将 Guest 的标准输出或标准错误按行写入日志
+pub struct LogOutput {
+    pub log: Arc<PluginLog>,
+    pub source: LogSource,
+}
+
+impl StdoutStream for LogOutput {
+    fn stream(&self) -> Box<dyn HostOutputStream> {
+        Box::new(LogOutputStream {
+            log: self.log.clone(),
+            source: self.source,
+            buffer: Vec::new(),
+        })
+    }
+
+    fn isatty(&self) -> bool {
+        false
+    }
+}
+
+struct LogOutputStream {
+    log: Arc<PluginLog>,
+    source: LogSource,
+    // 尚未遇到换行的输出
+    buffer: Vec<u8>,
+}
+
+impl LogOutputStream {
+    fn write_line(&self, line: &[u8]) {
+        let level = match self.source {
+            LogSource::Stderr => LogLevel::Error,
+            _ => LogLevel::Info,
+        };
+        let message = String::from_utf8_lossy(line)
+            .trim_end_matches('\r')
+            .to_string();
+        self.log.write(level, self.source, message, BTreeMap::new());
+    }
+
+    fn write_buffer(&mut self) {
+        if !self.buffer.is_empty() {
+            let buffer = std::mem::take(&mut self.buffer);
+            self.write_line(&buffer);
+        }
+    }
+}
+
+#[async_trait]
+impl Subscribe for LogOutputStream {
+    async fn ready(&mut self) {}
+}
+
+impl HostOutputStream for LogOutputStream {
+    fn write(&mut self, bytes: Bytes) -> StreamResult<()> {
+        let mut rest = bytes.as_ref();
+        while let Some(position) = rest.iter().position(|byte| *byte == b'\n') {
+            self.buffer.extend_from_slice(&rest[..position]);
+            let line = std::mem::take(&mut self.buffer);
+            self.write_line(&line);
+            rest = &rest[position + 1..];
+        }
+        self.buffer.extend_from_slice(rest);
+        if self.buffer.len() > MAX_LINE_LENGTH {
+            self.write_buffer();
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> StreamResult<()> {
+        Ok(())
+    }
+
+    fn check_write(&mut self) -> StreamResult<usize> {
+        Ok(usize::MAX)
+    }
+}
+
+impl Drop for LogOutputStream {
+    fn drop(&mut self) {
+        self.write_buffer();
+    }
+}
@@ -1,6 +1,16 @@
+mod assets;
+mod idle;
+pub(crate) mod kv;
+mod local_plugin;
+pub(crate) mod logs;
 mod plugin_server;
+mod regist_client;
+mod scheduler;
+pub(crate) mod sql;
 mod typings;
 pub mod wasi;
+pub(crate) mod websocket;
 
+pub use local_plugin::{LocalOptions, LocalPlugin};
 pub use plugin_server::PluginServer;
-pub use typings::Options;
+pub use typings::{Options, ServerLimits};
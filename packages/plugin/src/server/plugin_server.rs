@@ -1,21 +1,34 @@
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Instant;
 
+use crate::metrics;
 use crate::models::Plugin;
-use crate::server::wasi::PlatServer;
+use crate::server::wasi::{request_id, PlatServer, HEALTH_TIMEOUT, REQUEST_ID_HEADER};
 use anyhow::Context;
-use http_body_util::{BodyExt, Full};
-use hyper::body::Incoming;
-use hyper::server::conn::http1;
-use hyper::{Method, Request, Response};
+use http_body_util::{BodyExt, Full, Limited};
+use hyper::body::{Body, Bytes, Incoming};
+use hyper::header::{self, HeaderValue};
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::{TokioExecutor, TokioTimer};
+use hyper_util::server::conn::auto;
+use serde_json::json;
+use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::net::TcpListener;
 use tokio::sync::broadcast::Sender;
+use tokio::sync::Semaphore;
+use tokio_rustls::TlsAcceptor;
 use tower_http::cors::{AllowHeaders, AllowMethods, AllowOrigin};
-use wasmtime::{Result, Store};
+use tracing::Instrument;
+use wasmtime::Result;
 use wasmtime_wasi_http::body::HyperOutgoingBody;
 use wasmtime_wasi_http::io::TokioIo;
 
-use super::Options;
+use super::assets;
+use super::idle::{Activity, IdleIo};
+use super::regist_client::{self, RegistAuth};
+use super::scheduler::spawn_schedules;
+use super::{Options, ServerLimits};
 
 pub struct PluginServer {
     terminate: Sender<()>,
@@ -29,15 +42,36 @@ impl PluginServer {
     pub async fn new(plugin_path: PathBuf, options: Options) -> anyhow::Result<Self> {
         let plugin_dir = plugin_path.parent().unwrap().to_path_buf();
         let daemon_address = options.daemon_address;
+        options.limits.validate()?;
 
         let tcp_listener = TcpListener::bind(format!("127.0.0.1:{}", options.port))
             .await
             .context("监听端口失败")?;
 
         let mut plat_server =
-            PlatServer::new(plugin_dir.join("plugin.json"), daemon_address.clone())
+            // 独立运行时数据保存在插件目录中
+            PlatServer::new(
+                plugin_dir.join("plugin.json"),
+                daemon_address.clone(),
+                plugin_dir.clone(),
+            )
                 .context("创建 PlatServer 失败")?;
-        let server_address = format!("http://{}", tcp_listener.local_addr()?);
+        plat_server.https = options.tls.is_some();
+        plat_server.daemon_fingerprint = options.daemon_fingerprint.clone();
+        plat_server.debug = options.debug;
+        // 独立运行时 Guest 的输出同时显示在终端中
+        plat_server.log.set_echo(true);
+        let scheme = match plat_server.https {
+            true => "https",
+            false => "http",
+        };
+        let server_address = format!("{}://{}", scheme, tcp_listener.local_addr()?);
+        // 通过 ALPN 协商 HTTP/2，未启用 TLS 时客户端可直接使用 h2c
+        let tls_acceptor = options
+            .tls
+            .as_ref()
+            .map(|tls| TlsAcceptor::from(tls.config.clone()));
+        let limits = options.limits;
         let regist_address = match options.regist_address.as_ref() {
             Some(address) => address.clone(),
             None => server_address.clone(),
@@ -46,44 +80,43 @@ impl PluginServer {
             .plugin_config
             .address
             .replace(regist_address.clone());
+        // Daemon 代理请求时只信任该指纹的证书，因此可以使用自签名证书
+        plat_server.plugin_config.tls_fingerprint =
+            options.tls.as_ref().map(|tls| tls.fingerprint.clone());
 
-        let terminate = plat_server
-            .create_regist_plugin_handle()
+        // 首次注册失败时直接返回错误，之后的断线由 keepalive 负责重连
+        let regist_auth = RegistAuth {
+            token: options.regist_token,
+            key: options.regist_key,
+        };
+        let registration = regist_client::connect(&plat_server, &regist_auth)
             .await
             .context("注册 Plugin 失败")?;
 
         let plat_server = Arc::new(plat_server);
+        let (terminate, _rx) = tokio::sync::broadcast::channel::<()>(4);
+        regist_client::spawn_keepalive(
+            plat_server.clone(),
+            regist_auth,
+            registration,
+            terminate.clone(),
+        );
 
-        // plugin init
-        let init_handler = tokio::task::spawn({
-            let plat_server = plat_server.clone();
-            async move {
-                let mut store = Store::new(
-                    plat_server.pre.engine(),
-                    crate::plat_bindings::Component::new(&plat_server),
-                );
-                let world = plat_server.pre.instantiate_async(&mut store).await.unwrap();
-                world
-                    .lifecycle()
-                    .call_on_start(&mut store)
-                    .await
-                    .expect("调用 onStart 生命周期失败");
-            }
-        });
-        tokio::task::spawn({
-            let terminate = terminate.clone();
-            async move {
-                let _ = terminate.subscribe().recv().await;
-                init_handler.abort();
-            }
-        });
+        spawn_on_start(plat_server.clone(), terminate.clone());
+        spawn_schedules(plat_server.clone(), terminate.clone());
 
         tokio::task::spawn({
             let terminate = terminate.clone();
             let plat_server = plat_server.clone();
             async move {
                 let mut sub = terminate.subscribe();
+                let connections = Arc::new(Semaphore::new(limits.max_connections));
                 loop {
+                    // 达到连接上限时暂停 accept，等待已有连接关闭
+                    let permit = tokio::select! {
+                        permit = connections.clone().acquire_owned() => permit.expect("semaphore closed"),
+                        _ = sub.recv() => break,
+                    };
                     let (client, _addr) = tokio::select! {
                         val = tcp_listener.accept() => val,
                         _ = sub.recv() => break,
@@ -91,44 +124,27 @@ impl PluginServer {
                     .expect("plugin server accept failed");
 
                     let plat_server = plat_server.clone();
+                    let tls_acceptor = tls_acceptor.clone();
                     tokio::task::spawn(async move {
-                        let svc = tower::service_fn(move |req: Request<Incoming>| {
-                            let plat_server = plat_server.clone();
-                            async move {
-                                let method = req.method();
-                                let uri = req.uri();
-                                let path = uri.path();
-
-                                match (method, path) {
-                                    (&Method::GET, "/plugin.json") => {
-                                        send_plugin_json(req, &plat_server.plugin_config)
-                                    }
-                                    (_method, _uri) => plat_server.handle_request(req).await,
-                                }
-                            }
-                        });
-                        let svc = tower::ServiceBuilder::new()
-                            .layer(
-                                tower_http::cors::CorsLayer::new()
-                                    .allow_methods(AllowMethods::mirror_request())
-                                    .allow_origin(AllowOrigin::mirror_request())
-                                    .allow_credentials(true)
-                                    .allow_headers(AllowHeaders::mirror_request()),
-                            )
-                            .service(svc);
-                        let svc = hyper_util::service::TowerToHyperService::new(svc);
-
-                        if let Err(e) = http1::Builder::new()
-                            .serve_connection(TokioIo::new(client), svc)
-                            .await
-                        {
-                            println!("handle request error: {:?}", e);
+                        match tls_acceptor {
+                            None => serve_connection(client, plat_server, limits).await,
+                            Some(tls_acceptor) => match tls_acceptor.accept(client).await {
+                                Ok(stream) => serve_connection(stream, plat_server, limits).await,
+                                Err(e) => tracing::debug!("TLS 握手失败：{:?}", e),
+                            },
                         }
+                        drop(permit);
                     });
                 }
             }
         });
 
+        tracing::info!(
+            plugin = %plat_server.plugin_config.name,
+            address = %server_address,
+            "Plugin 服务已启动"
+        );
+
         Ok(PluginServer {
             path: plugin_dir,
             plat_server,
@@ -143,6 +159,7 @@ impl PluginServer {
     }
 
     pub async fn stop(&self) {
+        tracing::info!(plugin = %self.plugin().name, "Plugin 服务正在停止");
         let _ = self.terminate.send(());
     }
 
@@ -151,10 +168,190 @@ impl PluginServer {
     }
 }
 
-fn send_plugin_json(
-    _req: Request<Incoming>,
-    plugin_config: &crate::models::Plugin,
-) -> Result<hyper::Response<HyperOutgoingBody>> {
+// 调用 Plugin 的 onStart 生命周期，Plugin 停止时一并终止
+pub(super) fn spawn_on_start(plat_server: Arc<PlatServer>, terminate: Sender<()>) {
+    let span = tracing::info_span!("on_start", plugin = %plat_server.plugin_config.name);
+    let init_handler = tokio::task::spawn(
+        async move {
+            let mut store = plat_server.new_store();
+            let result = async {
+                let start = Instant::now();
+                let world = plat_server.pre.instantiate_async(&mut store).await?;
+                plat_server.metrics.observe_instantiation(start.elapsed());
+                world.lifecycle().call_on_start(&mut store).await
+            };
+            match result.await {
+                Ok(()) => tracing::debug!("onStart 生命周期已完成"),
+                Err(e) => tracing::error!("调用 onStart 生命周期失败：{:?}", e),
+            }
+        }
+        .instrument(span),
+    );
+    tokio::task::spawn(async move {
+        let _ = terminate.subscribe().recv().await;
+        init_handler.abort();
+    });
+}
+
+// 请求 ID 在这里确定，span 与之后交给 Guest 的请求使用同一个 ID
+pub(super) async fn route_request<B>(
+    plat_server: &PlatServer,
+    mut req: Request<B>,
+) -> Result<Response<HyperOutgoingBody>>
+where
+    B: Body<Data = Bytes> + Send + Unpin + 'static,
+    B::Error: std::fmt::Display,
+{
+    let request_id = request_id(req.headers());
+    req.headers_mut()
+        .insert(REQUEST_ID_HEADER, HeaderValue::from_str(&request_id)?);
+    let span = tracing::info_span!(
+        "request",
+        plugin = %plat_server.plugin_config.name,
+        request_id = %request_id,
+        method = %req.method(),
+        path = %req.uri().path(),
+    );
+
+    let start = Instant::now();
+    let method = req.method().to_string();
+    let route = metrics::route_label(req.uri().path()).to_string();
+    let result = async move {
+        match (req.method(), req.uri().path()) {
+            (&Method::GET, "/plugin.json") => send_plugin_json(&plat_server.plugin_config),
+            (&Method::GET, "/.plat/metrics") => send_metrics(plat_server),
+            (&Method::GET, "/.plat/health") => send_health(plat_server).await,
+            (_method, _uri) => {
+                match assets::serve(plat_server, req.method(), req.uri(), req.headers()).await? {
+                    Some(res) => Ok(res),
+                    None => plat_server.handle_request(req).await,
+                }
+            }
+        }
+    }
+    .instrument(span)
+    .await;
+
+    let status = match result.as_ref() {
+        Ok(res) => res.status(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    plat_server
+        .metrics
+        .observe_request(&route, &method, status.as_u16(), start.elapsed());
+    result
+}
+
+async fn serve_connection<I>(io: I, plat_server: Arc<PlatServer>, limits: ServerLimits)
+where
+    I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let svc = tower::service_fn(move |req: Request<Incoming>| {
+        let plat_server = plat_server.clone();
+        async move {
+            if exceeds_body_limit(&req, limits.max_body_size) {
+                return payload_too_large();
+            }
+            let req = req.map(|body| Limited::new(body, limits.max_body_size as usize));
+            route_request(&plat_server, req).await
+        }
+    });
+    let svc = tower::ServiceBuilder::new()
+        .layer(
+            tower_http::cors::CorsLayer::new()
+                .allow_methods(AllowMethods::mirror_request())
+                .allow_origin(AllowOrigin::mirror_request())
+                .allow_credentials(true)
+                .allow_headers(AllowHeaders::mirror_request()),
+        )
+        .service(svc);
+    let svc = hyper_util::service::TowerToHyperService::new(svc);
+
+    // 根据连接前言自动选择 HTTP/1.1 或 HTTP/2
+    let mut builder = auto::Builder::new(TokioExecutor::new());
+    builder
+        .http1()
+        .timer(TokioTimer::new())
+        .header_read_timeout(limits.idle_timeout)
+        .max_buf_size(limits.max_header_size.max(8192))
+        .http2()
+        .timer(TokioTimer::new())
+        .max_header_list_size(limits.max_header_size as u32);
+
+    let activity = Activity::new();
+    // 启用连接升级，供 Guest 接受 WebSocket 请求
+    let connection = builder
+        .serve_connection_with_upgrades(TokioIo::new(IdleIo::new(io, activity.clone())), svc);
+    tokio::pin!(connection);
+
+    // 空闲超时后等待连接优雅关闭，此时的错误不再输出
+    tokio::select! {
+        result = connection.as_mut() => {
+            if let Err(e) = result {
+                tracing::debug!("处理连接失败：{:?}", e);
+            }
+        }
+        _ = activity.idle(limits.idle_timeout) => {
+            connection.as_mut().graceful_shutdown();
+            let _ = connection.await;
+        }
+    }
+}
+
+// Content-Length 已声明超过上限的请求直接拒绝，未声明长度的请求在读取时限制
+pub(super) fn exceeds_body_limit<B>(req: &Request<B>, max_body_size: u64) -> bool {
+    req.headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok())
+        .is_some_and(|length| length > max_body_size)
+}
+
+pub(super) fn payload_too_large() -> Result<Response<HyperOutgoingBody>> {
+    let body = Full::new(Bytes::from_static("请求体超过大小上限".as_bytes()))
+        .map_err(|never| match never {})
+        .boxed();
+    Ok(Response::builder()
+        .status(StatusCode::PAYLOAD_TOO_LARGE)
+        .body(body)?)
+}
+
+// 健康时返回 200，Guest 健康检查失败时返回 503 与原因
+async fn send_health(plat_server: &PlatServer) -> Result<Response<HyperOutgoingBody>> {
+    let (status, body) = match plat_server.check_health(HEALTH_TIMEOUT).await {
+        Ok(()) => (StatusCode::OK, json!({ "status": "ok" })),
+        Err(message) => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            json!({ "status": "unhealthy", "message": message }),
+        ),
+    };
+    let body = Full::new(Bytes::from(body.to_string()))
+        .map_err(|never| match never {})
+        .boxed();
+    Ok(Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(body)?)
+}
+
+fn send_metrics(plat_server: &PlatServer) -> Result<Response<HyperOutgoingBody>> {
+    let mut out = String::new();
+    metrics::encode_plugins(
+        &mut out,
+        &[(
+            &plat_server.plugin_config.name,
+            plat_server.metrics_snapshot(),
+        )],
+    );
+    let body = Full::new(Bytes::from(out))
+        .map_err(|never| match never {})
+        .boxed();
+    Ok(Response::builder()
+        .header(header::CONTENT_TYPE, metrics::CONTENT_TYPE)
+        .body(body)?)
+}
+
+fn send_plugin_json(plugin_config: &crate::models::Plugin) -> Result<Response<HyperOutgoingBody>> {
     let plugin_json = serde_json::to_string(&plugin_config)?.as_bytes().to_vec();
 
     let body = Full::new(plugin_json.into())
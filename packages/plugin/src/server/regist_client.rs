@@ -0,0 +1,286 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context};
+use futures_util::{SinkExt, StreamExt};
+use tokio::net::TcpStream;
+use tokio::sync::broadcast::{error::RecvError, Receiver, Sender};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{Connector, MaybeTlsStream, WebSocketStream};
+use tracing::Instrument;
+use url::Url;
+
+use crate::models::{GuestError, LogRecord, Notification, ScheduleRun};
+use crate::regist::{
+    RegistCredential, RegistKey, RegistPluginMessage, RegistRejected, RegistRequest,
+    RegistServerMessage,
+};
+use crate::server::wasi::PlatServer;
+use crate::tls;
+
+pub type RegistConnection = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+// 超过 Ping 间隔的 2.5 倍未收到 Daemon 的消息（包括 Ping）即认为连接已断开；
+// Daemon 未告知 Ping 间隔时使用默认的超时时间
+const REGIST_READ_TIMEOUT: Duration = Duration::from_secs(10);
+// 从建立连接到收到注册结果的超时时间，避免 Daemon 无响应时一直等待
+const REGIST_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(15);
+// 重连间隔从 1 秒开始翻倍，最长 60 秒
+const RECONNECT_INITIAL_DELAY: Duration = Duration::from_secs(1);
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(60);
+
+// 注册所需的凭证，重连时需要再次使用
+#[derive(Clone, Default)]
+pub struct RegistAuth {
+    pub token: Option<String>,
+    pub key: Option<RegistKey>,
+}
+
+// 注册成功的连接，以及根据 Daemon 心跳间隔得出的读取超时
+pub struct Registration {
+    connection: RegistConnection,
+    read_timeout: Duration,
+}
+
+// 连接 Daemon 的 /api/regist 并完成注册握手
+pub async fn connect(plat_server: &PlatServer, auth: &RegistAuth) -> anyhow::Result<Registration> {
+    tokio::time::timeout(REGIST_HANDSHAKE_TIMEOUT, handshake(plat_server, auth))
+        .await
+        .map_err(|_| anyhow!("注册 Plugin 超时，Daemon 未在规定时间内完成握手"))?
+}
+
+async fn handshake(plat_server: &PlatServer, auth: &RegistAuth) -> anyhow::Result<Registration> {
+    let mut regist_plugin_address = Url::parse(&plat_server.daemon_address)?.join("api/regist")?;
+    match regist_plugin_address.scheme() {
+        "http" => {
+            regist_plugin_address.set_scheme("ws").unwrap();
+        }
+        "https" => {
+            regist_plugin_address.set_scheme("wss").unwrap();
+        }
+        _ => {}
+    };
+
+    let connector = match regist_plugin_address.scheme() {
+        "wss" => Some(Connector::Rustls(tls::client_config(
+            plat_server.daemon_fingerprint.as_deref(),
+        )?)),
+        _ => None,
+    };
+    let (mut connection, _response) = tokio_tungstenite::connect_async_tls_with_config(
+        regist_plugin_address.to_string(),
+        None,
+        false,
+        connector,
+    )
+    .await
+    .context("连接 Daemon 失败")?;
+
+    let (nonce, daemon_public_key, read_timeout) = match read_message(&mut connection).await? {
+        RegistServerMessage::Challenge {
+            daemon_public_key,
+            nonce,
+            ping_interval,
+        } => {
+            plat_server.set_daemon_public_key(daemon_public_key.clone());
+            let read_timeout = match ping_interval {
+                Some(interval) => Duration::from_millis(interval.saturating_mul(2500)),
+                None => REGIST_READ_TIMEOUT,
+            };
+            (nonce, daemon_public_key, read_timeout)
+        }
+        message => return Err(anyhow!("注册 Plugin 失败，未收到 Challenge：{:?}", message)),
+    };
+
+    // 优先使用注册密钥签名，其次使用注册令牌
+    let credential = match (auth.key.as_ref(), auth.token.as_ref()) {
+        (Some(key), _) => {
+            Some(key.credential(&nonce, &daemon_public_key, &plat_server.plugin_config)?)
+        }
+        (None, Some(token)) => Some(RegistCredential::Token {
+            token: token.clone(),
+        }),
+        (None, None) => None,
+    };
+    let request = RegistRequest {
+        plugin: plat_server.plugin_config.clone(),
+        credential,
+    };
+    connection
+        .send(Message::text(serde_json::to_string(&request)?))
+        .await?;
+
+    match read_message(&mut connection).await? {
+        RegistServerMessage::Accepted => Ok(Registration {
+            connection,
+            read_timeout,
+        }),
+        RegistServerMessage::Rejected { reason, message } => {
+            Err(RegistRejected { reason, message }.into())
+        }
+        message => Err(anyhow!("注册 Plugin 失败，收到意外的消息：{:?}", message)),
+    }
+}
+
+// 维持注册连接，连接断开时按指数退避重新注册；
+// Daemon 永久拒绝注册或 Plugin 停止时退出
+pub fn spawn_keepalive(
+    plat_server: Arc<PlatServer>,
+    auth: RegistAuth,
+    registration: Registration,
+    terminate: Sender<()>,
+) {
+    let mut terminate_sub = terminate.subscribe();
+    // 断线期间 Guest 发出的推送消息、错误记录、日志与定时任务记录会被缓存，重连后继续转发
+    let mut notifications = plat_server.subscribe_notifications();
+    let mut errors = plat_server.subscribe_errors();
+    let mut logs = plat_server.log.subscribe();
+    let mut schedule_runs = plat_server.subscribe_schedule_runs();
+    let span = tracing::info_span!(
+        "regist",
+        plugin = %plat_server.plugin_config.name,
+        daemon = %plat_server.daemon_address,
+    );
+    tokio::task::spawn(
+        async move {
+            let mut registration = Some(registration);
+            let mut delay = RECONNECT_INITIAL_DELAY;
+
+            loop {
+                if let Some(mut current) = registration.take() {
+                    tracing::info!("已注册到 Daemon");
+                    delay = RECONNECT_INITIAL_DELAY;
+                    tokio::select! {
+                        _ = keep(
+                            &mut current.connection,
+                            current.read_timeout,
+                            &mut notifications,
+                            &mut errors,
+                            &mut logs,
+                            &mut schedule_runs,
+                        ) => (),
+                        _ = terminate_sub.recv() => return,
+                    }
+                    tracing::warn!("注册连接已断开，{}s 后重新注册", delay.as_secs());
+                }
+
+                tokio::select! {
+                    _ = tokio::time::sleep(delay) => (),
+                    _ = terminate_sub.recv() => return,
+                }
+
+                let result = tokio::select! {
+                    result = connect(&plat_server, &auth) => result,
+                    _ = terminate_sub.recv() => return,
+                };
+                match result {
+                    Ok(value) => registration = Some(value),
+                    Err(e) => {
+                        if let Some(rejected) = e.downcast_ref::<RegistRejected>() {
+                            if rejected.reason.is_permanent() {
+                                tracing::error!("Daemon 拒绝注册：{}，停止 Plugin", rejected);
+                                let _ = terminate.send(());
+                                return;
+                            }
+                        }
+                        delay = (delay * 2).min(RECONNECT_MAX_DELAY);
+                        tracing::warn!("注册失败：{:#}，{}s 后重试", e, delay.as_secs());
+                    }
+                }
+            }
+        }
+        .instrument(span),
+    );
+}
+
+// 响应 Daemon 的心跳并转发推送消息、错误记录、日志与定时任务记录，直到连接断开或超时
+async fn keep(
+    connection: &mut RegistConnection,
+    read_timeout: Duration,
+    notifications: &mut Receiver<Notification>,
+    errors: &mut Receiver<GuestError>,
+    logs: &mut Receiver<LogRecord>,
+    schedule_runs: &mut Receiver<ScheduleRun>,
+) {
+    loop {
+        let message = tokio::select! {
+            message = tokio::time::timeout(read_timeout, connection.next()) => match message {
+                Ok(Some(Ok(message))) => message,
+                _ => break,
+            },
+            notification = notifications.recv() => {
+                let message = notification.map(RegistPluginMessage::Notify);
+                match forward(connection, message, "notify").await {
+                    true => continue,
+                    false => break,
+                }
+            }
+            error = errors.recv() => {
+                let message = error.map(RegistPluginMessage::GuestError);
+                match forward(connection, message, "guest error").await {
+                    true => continue,
+                    false => break,
+                }
+            }
+            record = logs.recv() => {
+                let message = record.map(RegistPluginMessage::Log);
+                match forward(connection, message, "log").await {
+                    true => continue,
+                    false => break,
+                }
+            }
+            run = schedule_runs.recv() => {
+                let message = run.map(RegistPluginMessage::ScheduleRun);
+                match forward(connection, message, "schedule run").await {
+                    true => continue,
+                    false => break,
+                }
+            }
+        };
+        match message {
+            Message::Ping(inner) => {
+                if connection.send(Message::Pong(inner)).await.is_err() {
+                    break;
+                }
+            }
+            Message::Close(_) => break,
+            _ => (),
+        }
+    }
+}
+
+// 将 Guest 产生的消息发送给 Daemon，返回 false 表示连接或 PlatServer 已关闭
+async fn forward(
+    connection: &mut RegistConnection,
+    message: Result<RegistPluginMessage, RecvError>,
+    kind: &str,
+) -> bool {
+    let message = match message {
+        Ok(message) => message,
+        Err(RecvError::Lagged(count)) => {
+            tracing::warn!(kind, count, "转发队列已满，丢弃较早的消息");
+            return true;
+        }
+        Err(RecvError::Closed) => return false,
+    };
+    match serde_json::to_string(&message) {
+        Ok(message) => connection.send(Message::text(message)).await.is_ok(),
+        Err(_) => true,
+    }
+}
+
+async fn read_message(connection: &mut RegistConnection) -> anyhow::Result<RegistServerMessage> {
+    loop {
+        match connection.next().await {
+            Some(Ok(Message::Text(text))) => {
+                return serde_json::from_str(&text).context("解析 Daemon 注册消息失败")
+            }
+            Some(Ok(Message::Close(frame))) => {
+                return Err(anyhow!("Daemon 关闭了注册连接：{:?}", frame))
+            }
+            Some(Ok(_)) => continue,
+            Some(Err(e)) => return Err(e.into()),
+            None => return Err(anyhow!("Daemon 关闭了注册连接")),
+        }
+    }
+}
@@ -0,0 +1,470 @@
+use std::collections::HashSet;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, bail, Context};
+use chrono::{
+    DateTime, Datelike, Local, LocalResult, NaiveDate, NaiveDateTime, TimeDelta, TimeZone, Timelike,
+};
+use tokio::sync::broadcast::Sender;
+use tokio::task::JoinHandle;
+use tracing::Instrument;
+
+use crate::models::{Schedule, ScheduleRun, ScheduleRunStatus};
+use crate::server::wasi::{DeadlineExceeded, PlatServer};
+
+// 查找下一次 cron 触发时间的范围，超出时认为表达式不会再触发（例如 2 月 30 日）
+const CRON_SEARCH_DAYS: i64 = 366 * 5;
+
+// 定时任务的触发方式
+pub(crate) enum Trigger {
+    Cron(Cron),
+    Interval(Duration),
+}
+
+impl Trigger {
+    // 校验 plugin.json 中的 schedules 并解析每个任务的触发方式
+    pub(crate) fn parse_all(schedules: &[Schedule]) -> anyhow::Result<Vec<(Schedule, Trigger)>> {
+        let mut names = HashSet::new();
+        schedules
+            .iter()
+            .map(|schedule| {
+                if schedule.name.is_empty() {
+                    bail!("定时任务名称不能为空");
+                }
+                if !names.insert(schedule.name.as_str()) {
+                    bail!("定时任务名称重复：{}", schedule.name);
+                }
+                if schedule.timeout == 0 {
+                    bail!("定时任务 {} 的 timeout 必须大于 0", schedule.name);
+                }
+                let trigger = Trigger::parse(schedule)
+                    .with_context(|| format!("定时任务 {} 配置无效", schedule.name))?;
+                Ok((schedule.clone(), trigger))
+            })
+            .collect()
+    }
+
+    fn parse(schedule: &Schedule) -> anyhow::Result<Self> {
+        match (schedule.cron.as_ref(), schedule.interval) {
+            (Some(cron), None) => Ok(Trigger::Cron(cron.parse()?)),
+            (None, Some(0)) => bail!("interval 必须大于 0"),
+            (None, Some(interval)) => Ok(Trigger::Interval(Duration::from_secs(interval))),
+            _ => bail!("cron 与 interval 必须且只能设置一个"),
+        }
+    }
+
+    // 等待到下一次触发，cron 表达式不会再触发时返回 false
+    async fn wait(&self) -> bool {
+        match self {
+            Trigger::Interval(interval) => {
+                tokio::time::sleep(*interval).await;
+                true
+            }
+            Trigger::Cron(cron) => match cron.next_in(&Local::now()) {
+                Some(target) => {
+                    sleep_until(target).await;
+                    true
+                }
+                None => false,
+            },
+        }
+    }
+}
+
+// 系统时间与计时器使用的单调时钟可能有偏差，醒来后确认已经到达目标时间，避免同一分钟触发两次
+async fn sleep_until(target: DateTime<Local>) {
+    loop {
+        match (target - Local::now()).to_std() {
+            Ok(delay) if !delay.is_zero() => tokio::time::sleep(delay).await,
+            _ => break,
+        }
+    }
+}
+
+// 五个字段的 cron 表达式，每个字段的可选值以位集合保存
+pub(crate) struct Cron {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    // 日与周都有限制时满足其中一个即可，与 crontab 相同
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+impl FromStr for Cron {
+    type Err = anyhow::Error;
+
+    fn from_str(expression: &str) -> anyhow::Result<Self> {
+        let expression = match expression.trim() {
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            "@monthly" => "0 0 1 * *",
+            "@weekly" => "0 0 * * 0",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@hourly" => "0 * * * *",
+            expression => expression,
+        };
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            bail!("cron 表达式需要 5 个字段（分 时 日 月 周）：{}", expression);
+        };
+
+        // 0 与 7 都表示周日
+        let mut weekday_bits = parse_field(weekdays, 0, 7).context("周字段无效")?;
+        if weekday_bits & (1 << 7) != 0 {
+            weekday_bits = (weekday_bits | 1) & !(1 << 7);
+        }
+        Ok(Cron {
+            minutes: parse_field(minutes, 0, 59).context("分钟字段无效")?,
+            hours: parse_field(hours, 0, 23).context("小时字段无效")?,
+            days: parse_field(days, 1, 31).context("日字段无效")?,
+            months: parse_field(months, 1, 12).context("月字段无效")?,
+            weekdays: weekday_bits,
+            days_restricted: !days.starts_with('*'),
+            weekdays_restricted: !weekdays.starts_with('*'),
+        })
+    }
+}
+
+impl Cron {
+    // 在 now 所在时区中晚于 now 的下一次触发时间；夏令时开始时跳过的本地时间不存在，
+    // 继续查找之后的时间，夏令时结束时重复的本地时间只在第一次出现时触发
+    fn next_in<Tz: TimeZone>(&self, now: &DateTime<Tz>) -> Option<DateTime<Tz>> {
+        let mut after = now.naive_local();
+        loop {
+            let next = self.next_after(after)?;
+            // LocalResult::Ambiguous 中两个时间的顺序不固定，取较早的一个
+            match now.timezone().from_local_datetime(&next) {
+                LocalResult::Single(next) => return Some(next),
+                LocalResult::Ambiguous(a, b) => return Some(a.min(b)),
+                LocalResult::None => after = next,
+            }
+        }
+    }
+
+    fn next_after(&self, after: NaiveDateTime) -> Option<NaiveDateTime> {
+        let mut time = after.with_second(0)?.with_nanosecond(0)? + TimeDelta::minutes(1);
+        let limit = time + TimeDelta::days(CRON_SEARCH_DAYS);
+        while time < limit {
+            if !contains(self.months, time.month()) {
+                let (year, month) = match time.month() {
+                    12 => (time.year() + 1, 1),
+                    month => (time.year(), month + 1),
+                };
+                time = NaiveDate::from_ymd_opt(year, month, 1)?.and_hms_opt(0, 0, 0)?;
+                continue;
+            }
+            if !self.matches_day(time.date()) {
+                time = time.date().succ_opt()?.and_hms_opt(0, 0, 0)?;
+                continue;
+            }
+            if !contains(self.hours, time.hour()) {
+                time = time.with_minute(0)? + TimeDelta::hours(1);
+                continue;
+            }
+            if !contains(self.minutes, time.minute()) {
+                time += TimeDelta::minutes(1);
+                continue;
+            }
+            return Some(time);
+        }
+        None
+    }
+
+    fn matches_day(&self, date: NaiveDate) -> bool {
+        let day = contains(self.days, date.day());
+        let weekday = contains(self.weekdays, date.weekday().num_days_from_sunday());
+        match self.days_restricted && self.weekdays_restricted {
+            true => day || weekday,
+            false => day && weekday,
+        }
+    }
+}
+
+fn contains(bits: u64, value: u32) -> bool {
+    bits & (1 << value) != 0
+}
+
+// 解析 cron 的一个字段，支持 *、单个值、a-b 范围、逗号分隔的列表以及 /n 步长
+fn parse_field(field: &str, min: u32, max: u32) -> anyhow::Result<u64> {
+    let mut bits = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step = step
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|step| *step > 0)
+                    .ok_or_else(|| anyhow!("步长无效：{}", part))?;
+                (range, Some(step))
+            }
+            None => (part, None),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            range => match range.split_once('-') {
+                Some((start, end)) => (parse_value(start)?, parse_value(end)?),
+                // 带步长的单个值表示从该值到最大值，例如 5/15
+                None => {
+                    let value = parse_value(range)?;
+                    match step {
+                        Some(_) => (value, max),
+                        None => (value, value),
+                    }
+                }
+            },
+        };
+        if start < min || end > max || start > end {
+            bail!("取值超出范围 {}-{}：{}", min, max, part);
+        }
+        for value in (start..=end).step_by(step.unwrap_or(1) as usize) {
+            bits |= 1 << value;
+        }
+    }
+    Ok(bits)
+}
+
+fn parse_value(value: &str) -> anyhow::Result<u32> {
+    value.parse().map_err(|_| anyhow!("无效的值：{}", value))
+}
+
+// 按 schedules 定时调用 Guest 导出的 scheduler.on-tick，上一次执行尚未结束时跳过本次；
+// Plugin 停止时终止正在执行的任务
+pub(super) fn spawn_schedules(plat_server: Arc<PlatServer>, terminate: Sender<()>) {
+    for index in 0..plat_server.schedules.len() {
+        let plat_server = plat_server.clone();
+        let mut terminate = terminate.subscribe();
+        let span = tracing::info_span!(
+            "schedule",
+            plugin = %plat_server.plugin_config.name,
+            schedule = %plat_server.schedules[index].0.name,
+        );
+        tokio::task::spawn(
+            async move {
+                let (_, trigger) = &plat_server.schedules[index];
+                let mut running: Option<JoinHandle<()>> = None;
+                loop {
+                    tokio::select! {
+                        more = trigger.wait() => if !more {
+                            tracing::warn!("cron 表达式不会再触发，定时任务结束");
+                            break;
+                        },
+                        _ = terminate.recv() => break,
+                    }
+                    if running.as_ref().is_some_and(|task| !task.is_finished()) {
+                        tracing::warn!("上一次执行尚未结束，跳过本次执行");
+                        continue;
+                    }
+                    running = Some(tokio::task::spawn(
+                        run(plat_server.clone(), index).in_current_span(),
+                    ));
+                }
+                if let Some(task) = running {
+                    task.abort();
+                }
+            }
+            .instrument(span),
+        );
+    }
+}
+
+// 执行一次定时任务，开始与结束时分别记录执行状态
+async fn run(plat_server: Arc<PlatServer>, index: usize) {
+    let (schedule, _) = &plat_server.schedules[index];
+    let started_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_millis() as u64)
+        .unwrap_or_default();
+    let mut record = ScheduleRun {
+        schedule: schedule.name.clone(),
+        started_at,
+        duration: None,
+        status: ScheduleRunStatus::Running,
+        message: None,
+    };
+    plat_server.record_schedule_run(record.clone());
+
+    let start = Instant::now();
+    let timeout = Duration::from_secs(schedule.timeout);
+    let timed_out = (
+        ScheduleRunStatus::TimedOut,
+        Some(format!("执行超过 {}s，已终止", timeout.as_secs())),
+    );
+    // Guest 自身的计算由 epoch 截止时间中止，外层的超时用于结束等待中的宿主调用，
+    // 两者都保证本次执行在 timeout 内结束，下一次触发不会与其重叠
+    let call = plat_server.call_on_tick(&schedule.name, timeout);
+    let (status, message) = match tokio::time::timeout(timeout, call).await {
+        Ok(Ok(Ok(()))) => (ScheduleRunStatus::Succeeded, None),
+        Ok(Ok(Err(message))) => (ScheduleRunStatus::Failed, Some(message)),
+        Ok(Err(e)) if e.downcast_ref::<DeadlineExceeded>().is_some() => timed_out,
+        Ok(Err(e)) => (ScheduleRunStatus::Failed, Some(format!("{:#}", e))),
+        Err(_) => timed_out,
+    };
+    let duration = start.elapsed();
+    match message.as_ref() {
+        None => tracing::debug!(elapsed_ms = duration.as_millis() as u64, "定时任务已完成"),
+        Some(message) => tracing::warn!(
+            elapsed_ms = duration.as_millis() as u64,
+            "定时任务执行失败：{}",
+            message
+        ),
+    }
+
+    record.duration = Some(duration.as_millis() as u64);
+    record.status = status;
+    record.message = message;
+    plat_server.record_schedule_run(record);
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, Offset};
+    use chrono_tz::Europe::Berlin;
+
+    use super::*;
+
+    fn values(bits: u64) -> Vec<u32> {
+        (0..64).filter(|value| contains(bits, *value)).collect()
+    }
+
+    fn at(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(year, month, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn parse_field_supports_ranges_steps_and_lists() {
+        assert_eq!(
+            values(parse_field("*", 1, 12).unwrap()),
+            (1..=12).collect::<Vec<_>>()
+        );
+        assert_eq!(values(parse_field("7", 0, 59).unwrap()), [7]);
+        assert_eq!(values(parse_field("1-5", 0, 6).unwrap()), [1, 2, 3, 4, 5]);
+        assert_eq!(values(parse_field("*/15", 0, 59).unwrap()), [0, 15, 30, 45]);
+        assert_eq!(values(parse_field("5/20", 0, 59).unwrap()), [5, 25, 45]);
+        assert_eq!(values(parse_field("10-20/5", 0, 59).unwrap()), [10, 15, 20]);
+        assert_eq!(
+            values(parse_field("1,3,5-7,30-59/15", 0, 59).unwrap()),
+            [1, 3, 5, 6, 7, 30, 45]
+        );
+    }
+
+    #[test]
+    fn parse_field_rejects_invalid_values() {
+        for field in ["60", "0-60", "5-3", "*/0", "a", "1-", "", "1,,2", "-1"] {
+            assert!(parse_field(field, 0, 59).is_err(), "{}", field);
+        }
+        assert!(parse_field("0", 1, 31).is_err());
+        assert!(parse_field("32", 1, 31).is_err());
+        assert!(parse_field("13", 1, 12).is_err());
+    }
+
+    #[test]
+    fn cron_parses_expressions() {
+        assert!("* * * *".parse::<Cron>().is_err());
+        assert!("* * * * * *".parse::<Cron>().is_err());
+        assert!("0 24 * * *".parse::<Cron>().is_err());
+        assert!("0 0 * * 8".parse::<Cron>().is_err());
+
+        // 0 与 7 都表示周日
+        let sunday: Cron = "0 0 * * 7".parse().unwrap();
+        assert_eq!(values(sunday.weekdays), [0]);
+        let weekly: Cron = "@weekly".parse().unwrap();
+        assert_eq!(values(weekly.weekdays), [0]);
+    }
+
+    #[test]
+    fn next_after_finds_the_next_matching_minute() {
+        let cron: Cron = "*/15 * * * *".parse().unwrap();
+        assert_eq!(
+            cron.next_after(at(2024, 1, 1, 10, 0)),
+            Some(at(2024, 1, 1, 10, 15))
+        );
+        // 秒数被忽略，下一次触发总是晚于当前分钟
+        let after = at(2024, 1, 1, 10, 14).with_second(59).unwrap();
+        assert_eq!(cron.next_after(after), Some(at(2024, 1, 1, 10, 15)));
+
+        let cron: Cron = "0 0 * * *".parse().unwrap();
+        assert_eq!(
+            cron.next_after(at(2024, 12, 31, 23, 59)),
+            Some(at(2025, 1, 1, 0, 0))
+        );
+
+        // 2024-01-06 为周六，工作日的任务在下周一触发
+        let cron: Cron = "30 9 * * 1-5".parse().unwrap();
+        assert_eq!(
+            cron.next_after(at(2024, 1, 6, 12, 0)),
+            Some(at(2024, 1, 8, 9, 30))
+        );
+    }
+
+    #[test]
+    fn next_after_handles_month_boundaries() {
+        let cron: Cron = "0 0 29 2 *".parse().unwrap();
+        assert_eq!(
+            cron.next_after(at(2025, 3, 1, 0, 0)),
+            Some(at(2028, 2, 29, 0, 0))
+        );
+
+        let cron: Cron = "0 0 31 * *".parse().unwrap();
+        assert_eq!(
+            cron.next_after(at(2024, 4, 1, 0, 0)),
+            Some(at(2024, 5, 31, 0, 0))
+        );
+
+        // 2 月 30 日不存在，不会再触发
+        let cron: Cron = "0 0 30 2 *".parse().unwrap();
+        assert_eq!(cron.next_after(at(2024, 1, 1, 0, 0)), None);
+    }
+
+    #[test]
+    fn next_after_matches_day_or_weekday_when_both_are_restricted() {
+        // 每月 13 日或每个周五，2024-09-06 为周五
+        let cron: Cron = "0 0 13 * 5".parse().unwrap();
+        assert_eq!(
+            cron.next_after(at(2024, 9, 1, 0, 0)),
+            Some(at(2024, 9, 6, 0, 0))
+        );
+        assert_eq!(
+            cron.next_after(at(2024, 9, 6, 0, 0)),
+            Some(at(2024, 9, 13, 0, 0))
+        );
+
+        // 只限制周时，日字段的 * 不会放宽条件
+        let cron: Cron = "0 0 * * 5".parse().unwrap();
+        assert_eq!(
+            cron.next_after(at(2024, 9, 6, 0, 0)),
+            Some(at(2024, 9, 13, 0, 0))
+        );
+    }
+
+    #[test]
+    fn next_in_skips_missing_times_and_fires_once_on_repeated_times() {
+        // 中欧时间，2024-03-31 02:00 跳到 03:00，2024-10-27 03:00 回到 02:00
+        let cron: Cron = "30 2 * * *".parse().unwrap();
+
+        let now = Berlin.from_local_datetime(&at(2024, 3, 30, 12, 0)).unwrap();
+        let next = cron.next_in(&now).unwrap();
+        assert_eq!(next.naive_local(), at(2024, 4, 1, 2, 30));
+
+        let now = Berlin
+            .from_local_datetime(&at(2024, 10, 26, 12, 0))
+            .unwrap();
+        let next = cron.next_in(&now).unwrap();
+        assert_eq!(next.naive_local(), at(2024, 10, 27, 2, 30));
+        assert_eq!(next.offset().fix().local_minus_utc(), 2 * 3600);
+        // 重复的 02:30 只在第一次出现时触发
+        let later = cron.next_in(&next).unwrap();
+        assert_eq!(later.naive_local(), at(2024, 10, 28, 2, 30));
+        let repeated = Berlin
+            .from_local_datetime(&at(2024, 10, 27, 2, 30))
+            .latest()
+            .unwrap();
+        assert_eq!(cron.next_in(&repeated), Some(later));
+    }
+}
@@ -0,0 +1,296 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context};
+use rusqlite::hooks::{AuthAction, AuthContext, Authorization};
+use rusqlite::limits::Limit;
+use rusqlite::types::Value;
+use rusqlite::{params_from_iter, Connection};
+
+// 保存在宿主为 Plugin 管理的数据目录下的数据库文件名
+const FILE_NAME: &str = "plat.db";
+// 其他请求持有写锁时等待的时间，超时后返回错误
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+// 每个请求最多同时持有的语句数量
+const MAX_STATEMENTS: usize = 256;
+// 单次查询最多返回的行数，超出时需要在 SQL 中使用 LIMIT 分页
+const MAX_QUERY_ROWS: usize = 10_000;
+// 带参数但只读取信息的 PRAGMA，其余带值的 PRAGMA 视为修改设置
+const READ_ONLY_PRAGMAS: [&str; 8] = [
+    "table_info",
+    "table_xinfo",
+    "table_list",
+    "index_list",
+    "index_info",
+    "index_xinfo",
+    "foreign_key_list",
+    "foreign_key_check",
+];
+
+// 查询结果，列名与每一行的值
+pub(crate) struct QueryResult {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+// Plugin 的 SQLite 数据库，每个 Store 在首次使用时打开自己的连接；
+// 同一 Plugin 的并发请求通过 SQLite 的文件锁互斥写入
+pub struct SqlDatabase {
+    path: PathBuf,
+    // 数据库文件的大小上限，来自 plugin.json 的 sql_max_size
+    max_size: u64,
+}
+
+impl SqlDatabase {
+    pub fn new(data_path: PathBuf, max_size: u64) -> Self {
+        SqlDatabase {
+            path: data_path.join(FILE_NAME),
+            max_size,
+        }
+    }
+
+    fn connect(&self) -> anyhow::Result<Connection> {
+        let connection = Connection::open(&self.path).context("打开数据库失败")?;
+        connection.busy_timeout(BUSY_TIMEOUT)?;
+        connection.pragma_update(None, "journal_mode", "WAL")?;
+        connection.pragma_update(None, "foreign_keys", true)?;
+        // 数据库达到大小上限后写入返回 SQLITE_FULL
+        let page_size: u64 = connection.pragma_query_value(None, "page_size", |row| row.get(0))?;
+        connection.pragma_update(None, "max_page_count", (self.max_size / page_size).max(1))?;
+        // 宿主完成设置后限制 Guest 的语句，Guest 不能附加其他数据库文件或修改上述设置
+        connection.set_limit(Limit::SQLITE_LIMIT_ATTACHED, 0);
+        connection.authorizer(Some(authorize));
+        Ok(connection)
+    }
+}
+
+// 拒绝 ATTACH、DETACH 以及修改设置的 PRAGMA；VACUUM 与 VACUUM INTO 内部同样通过 ATTACH 执行，
+// 因此也被拒绝
+fn authorize(context: AuthContext<'_>) -> Authorization {
+    match context.action {
+        AuthAction::Attach { .. } | AuthAction::Detach { .. } => Authorization::Deny,
+        AuthAction::Pragma {
+            pragma_name,
+            pragma_value: Some(_),
+        } if !READ_ONLY_PRAGMAS.contains(&pragma_name.to_ascii_lowercase().as_str()) => {
+            Authorization::Deny
+        }
+        _ => Authorization::Allow,
+    }
+}
+
+// 一个 Store 内的数据库会话，持有连接与已准备的语句；
+// Store 释放时连接随之关闭，未提交的事务会被回滚
+pub(crate) struct SqlSession {
+    database: Arc<SqlDatabase>,
+    inner: Arc<Mutex<SessionInner>>,
+}
+
+#[derive(Default)]
+struct SessionInner {
+    connection: Option<Connection>,
+    // 语句句柄对应的 SQL，执行时从连接的语句缓存中取出已编译的语句
+    statements: HashMap<u32, String>,
+    next_id: u32,
+}
+
+impl SessionInner {
+    fn connection(&mut self, database: &SqlDatabase) -> anyhow::Result<&mut Connection> {
+        if self.connection.is_none() {
+            let connection = database.connect()?;
+            connection.set_prepared_statement_cache_capacity(MAX_STATEMENTS);
+            self.connection = Some(connection);
+        }
+        Ok(self.connection.as_mut().unwrap())
+    }
+
+    fn statement(&self, id: u32) -> anyhow::Result<String> {
+        self.statements
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| anyhow!("语句不存在或已释放：{}", id))
+    }
+}
+
+impl SqlSession {
+    pub fn new(database: Arc<SqlDatabase>) -> Self {
+        SqlSession {
+            database,
+            inner: Arc::new(Mutex::new(SessionInner::default())),
+        }
+    }
+
+    // SQLite 的调用会阻塞，放到阻塞线程中执行
+    async fn run<T, F>(&self, f: F) -> anyhow::Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut SessionInner, &SqlDatabase) -> anyhow::Result<T> + Send + 'static,
+    {
+        let database = self.database.clone();
+        let inner = self.inner.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut inner = match inner.lock() {
+                Ok(inner) => inner,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+            f(&mut inner, &database)
+        })
+        .await?
+    }
+
+    // 编译一条 SQL 语句并返回句柄
+    pub async fn prepare(&self, sql: String) -> anyhow::Result<u32> {
+        self.run(move |inner, database| {
+            if inner.statements.len() >= MAX_STATEMENTS {
+                bail!(
+                    "已准备的语句超过 {} 条，请先释放不再使用的语句",
+                    MAX_STATEMENTS
+                );
+            }
+            inner.connection(database)?.prepare_cached(&sql)?;
+            inner.next_id = inner.next_id.wrapping_add(1);
+            let id = inner.next_id;
+            inner.statements.insert(id, sql);
+            Ok(id)
+        })
+        .await
+    }
+
+    // 执行不返回行的语句，返回受影响的行数
+    pub async fn execute(&self, id: u32, params: Vec<Value>) -> anyhow::Result<u64> {
+        self.run(move |inner, database| {
+            let sql = inner.statement(id)?;
+            let mut statement = inner.connection(database)?.prepare_cached(&sql)?;
+            Ok(statement.execute(params_from_iter(params))? as u64)
+        })
+        .await
+    }
+
+    pub async fn query(&self, id: u32, params: Vec<Value>) -> anyhow::Result<QueryResult> {
+        self.run(move |inner, database| {
+            let sql = inner.statement(id)?;
+            let mut statement = inner.connection(database)?.prepare_cached(&sql)?;
+            let columns: Vec<String> = statement
+                .column_names()
+                .into_iter()
+                .map(String::from)
+                .collect();
+            let mut rows = statement.query(params_from_iter(params))?;
+            let mut values = Vec::new();
+            while let Some(row) = rows.next()? {
+                if values.len() >= MAX_QUERY_ROWS {
+                    bail!("查询结果超过 {} 行，请使用 LIMIT 分页", MAX_QUERY_ROWS);
+                }
+                values.push(
+                    (0..columns.len())
+                        .map(|index| row.get::<_, Value>(index))
+                        .collect::<rusqlite::Result<Vec<_>>>()?,
+                );
+            }
+            Ok(QueryResult {
+                columns,
+                rows: values,
+            })
+        })
+        .await
+    }
+
+    pub async fn finalize(&self, id: u32) -> anyhow::Result<()> {
+        self.run(move |inner, _| {
+            inner.statements.remove(&id);
+            Ok(())
+        })
+        .await
+    }
+
+    // 开始事务时立即获取写锁，避免事务中途升级写锁时与其他请求冲突
+    pub async fn begin(&self) -> anyhow::Result<()> {
+        self.batch("BEGIN IMMEDIATE").await
+    }
+
+    pub async fn commit(&self) -> anyhow::Result<()> {
+        self.batch("COMMIT").await
+    }
+
+    pub async fn rollback(&self) -> anyhow::Result<()> {
+        self.batch("ROLLBACK").await
+    }
+
+    async fn batch(&self, sql: &'static str) -> anyhow::Result<()> {
+        self.run(move |inner, database| Ok(inner.connection(database)?.execute_batch(sql)?))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+
+    use super::*;
+
+    fn temp_database(name: &str, max_size: u64) -> SqlDatabase {
+        let dir = env::temp_dir().join(format!("plat-sql-{}-{}", name, rand::random::<u64>()));
+        fs::create_dir_all(&dir).unwrap();
+        SqlDatabase::new(dir, max_size)
+    }
+
+    #[test]
+    fn connection_allows_ordinary_statements() {
+        let connection = temp_database("ordinary", 1024 * 1024).connect().unwrap();
+        connection
+            .execute_batch(
+                "CREATE TABLE t (id INTEGER PRIMARY KEY, name TEXT);
+                 INSERT INTO t (name) VALUES ('a');
+                 PRAGMA table_info(t);
+                 PRAGMA foreign_keys;
+                 PRAGMA user_version;",
+            )
+            .unwrap();
+        let count: i64 = connection
+            .query_row("SELECT count(*) FROM t", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 1);
+    }
+
+    #[test]
+    fn connection_denies_attach_pragma_writes_and_vacuum() {
+        let database = temp_database("deny", 1024 * 1024);
+        let connection = database.connect().unwrap();
+        let other = database.path.with_file_name("other.db");
+        for sql in [
+            format!("ATTACH '{}' AS other", other.display()),
+            "ATTACH ':memory:' AS other".to_string(),
+            "DETACH main".to_string(),
+            "PRAGMA foreign_keys = OFF".to_string(),
+            "PRAGMA max_page_count = 1000000000".to_string(),
+            "PRAGMA main.journal_mode = DELETE".to_string(),
+            format!("VACUUM INTO '{}'", other.display()),
+            "VACUUM".to_string(),
+        ] {
+            assert!(connection.execute_batch(&sql).is_err(), "{}", sql);
+        }
+        assert!(!other.exists());
+
+        let foreign_keys: bool = connection
+            .pragma_query_value(None, "foreign_keys", |row| row.get(0))
+            .unwrap();
+        assert!(foreign_keys);
+    }
+
+    #[test]
+    fn connection_enforces_max_size() {
+        let connection = temp_database("full", 64 * 1024).connect().unwrap();
+        connection
+            .execute_batch("CREATE TABLE t (value BLOB)")
+            .unwrap();
+        let result = (0..64).try_for_each(|_| {
+            connection
+                .execute("INSERT INTO t VALUES (zeroblob(4096))", [])
+                .map(|_| ())
+        });
+        assert!(result.is_err());
+    }
+}
@@ -1,5 +1,66 @@
+use std::time::Duration;
+
+use anyhow::bail;
+
+use crate::regist::RegistKey;
+use crate::tls::ServerTls;
+
 pub struct Options {
     pub port: u16,
     pub daemon_address: String,
     pub regist_address: Option<String>,
+    // 启用 TLS 时 Plugin 服务使用的证书配置
+    pub tls: Option<ServerTls>,
+    // 连接使用自签名证书的 Daemon 时需要固定其证书指纹
+    pub daemon_fingerprint: Option<String>,
+    // 向 Daemon 证明注册权限的令牌或密钥
+    pub regist_token: Option<String>,
+    pub regist_key: Option<RegistKey>,
+    pub limits: ServerLimits,
+    // 错误响应中包含错误详情与 wasm 调用栈
+    pub debug: bool,
+}
+
+// Plugin 服务的连接与请求限制，只作用于 platx plugin serve 独立运行的 Plugin 服务；
+// 在 Daemon 进程内运行的 Plugin 由 Daemon 的 HTTP 服务接收连接，请求体上限由 Daemon 的 plugin.max_body_size 配置
+#[derive(Debug, Clone, Copy)]
+pub struct ServerLimits {
+    // 请求首部的最大字节数
+    pub max_header_size: usize,
+    // 请求体的最大字节数，超出时返回 413
+    pub max_body_size: u64,
+    // 连接在该时间内没有任何读写时关闭
+    pub idle_timeout: Duration,
+    // 同时保持的最大连接数，超出的连接等待已有连接关闭后再处理
+    pub max_connections: usize,
+}
+
+impl Default for ServerLimits {
+    fn default() -> Self {
+        ServerLimits {
+            max_header_size: 64 * 1024,
+            max_body_size: 64 * 1024 * 1024,
+            idle_timeout: Duration::from_secs(60),
+            max_connections: 512,
+        }
+    }
+}
+
+impl ServerLimits {
+    // 任一限制为 0 时服务无法处理请求：连接数为 0 时不再接受连接，空闲超时为 0 时连接立即关闭
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.max_connections == 0 {
+            bail!("max_connections 必须大于 0");
+        }
+        if self.idle_timeout.is_zero() {
+            bail!("idle_timeout 必须大于 0");
+        }
+        if self.max_header_size == 0 {
+            bail!("max_header_size 必须大于 0");
+        }
+        if self.max_body_size == 0 {
+            bail!("max_body_size 必须大于 0");
+        }
+        Ok(())
+    }
 }
@@ -1,33 +1,109 @@
 use std::fs;
-use std::path::PathBuf;
-use std::time::Duration;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use anyhow::{anyhow, Context};
-use futures_util::{SinkExt, StreamExt};
-use hyper::body::Incoming;
-use hyper::Request;
-use tokio::sync::broadcast::Sender;
-use tokio_tungstenite::tungstenite::Message;
-use url::Url;
-use wasmtime::component::{Component, Linker};
-use wasmtime::{Config, Engine, Result, Store};
-use wasmtime_wasi_http::bindings::http::types::Scheme;
-use wasmtime_wasi_http::body::HyperOutgoingBody;
+use base64::prelude::{Engine as _, BASE64_URL_SAFE_NO_PAD};
+use http_body_util::{BodyExt, Full};
+use hyper::body::{Body, Bytes};
+use hyper::header::{HeaderMap, HeaderValue};
+use hyper::{Request, Response, StatusCode};
+use rand::RngCore;
+use tokio::sync::broadcast::{self, Receiver, Sender};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::task::{AbortHandle, JoinHandle};
+use tracing::Instrument;
+use wasmtime::component::{Component, ComponentExportIndex, Linker};
+use wasmtime::{Config, Engine, Result, Store, Trap, UpdateDeadline, WasmBacktrace};
+use wasmtime_wasi_http::bindings::http::types::{ErrorCode, Scheme};
+use wasmtime_wasi_http::body::{HostIncomingBody, HyperOutgoingBody};
+use wasmtime_wasi_http::types::HostIncomingRequest;
 use wasmtime_wasi_http::WasiHttpView;
 
-use crate::models::Plugin;
+use crate::metrics::{MetricsSnapshot, PluginMetrics};
+use crate::models::{GuestError, Notification, Plugin, RequestLimits, Schedule, ScheduleRun};
 use crate::plat_bindings;
+use crate::server::kv::KvStore;
+use crate::server::logs::PluginLog;
+use crate::server::scheduler::Trigger;
+use crate::server::sql::SqlDatabase;
+use crate::server::websocket::{self, PendingUpgrade};
+
+// 推送消息在转发给 Daemon 之前最多缓存的数量，超出时丢弃较早的消息
+const NOTIFICATION_CAPACITY: usize = 256;
+// 错误记录在转发给 Daemon 之前最多缓存的数量
+const ERROR_CAPACITY: usize = 64;
+// 请求 ID 所在的首部，客户端未提供时由宿主生成并传给 Guest
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+// Guest 可选导出的健康检查接口
+const HEALTH_INTERFACE: &str = "plat:plugin/health";
+// 独立运行的 Plugin 服务在 /.plat/health 中等待 Guest 健康检查返回的时间，
+// 本地 Plugin 使用 Daemon 配置的 health.timeout
+pub(crate) const HEALTH_TIMEOUT: Duration = Duration::from_secs(5);
+// Guest 导出的定时任务接口，plugin.json 声明了 schedules 时必须导出
+const SCHEDULER_INTERFACE: &str = "plat:plugin/scheduler";
+// 定时任务执行记录在转发给 Daemon 之前最多缓存的数量
+const SCHEDULE_RUN_CAPACITY: usize = 64;
+// Engine 的 epoch 递增间隔，Guest 每执行这么久就让出一次线程
+const EPOCH_TICK: Duration = Duration::from_millis(10);
+
+// Guest 执行超过截止时间
+#[derive(Debug)]
+pub(crate) struct DeadlineExceeded;
+
+impl std::fmt::Display for DeadlineExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "执行超过截止时间")
+    }
+}
+
+impl std::error::Error for DeadlineExceeded {}
 
 pub struct PlatServer {
     pub pre: plat_bindings::PlatWorldPre<plat_bindings::Component>,
-    pub daemon_public_key: String,
+    daemon_public_key: RwLock<String>,
+    // Guest 通过 notify.send 发出的消息
+    notifications: Sender<Notification>,
+    // Guest 处理请求失败的记录
+    errors: Sender<GuestError>,
+    // Guest 的输出与通过 log 接口写入的日志
+    pub log: Arc<PluginLog>,
+    // Guest 通过 kv 接口访问的键值存储
+    pub(crate) kv: Arc<KvStore>,
+    // Guest 通过 sql 接口访问的数据库
+    pub(crate) sql: Arc<SqlDatabase>,
     pub plugin_config: Plugin,
     pub plugin_config_directory: PathBuf,
     pub daemon_address: String,
+    // Plugin 服务是否通过 HTTPS 提供
+    pub https: bool,
+    // 连接 Daemon 时固定的证书指纹
+    pub daemon_fingerprint: Option<String>,
+    // 调试模式下错误响应包含错误详情与 wasm 调用栈
+    pub debug: bool,
+    request_limits: RequestLimits,
+    // 正在由 Guest 处理的请求，返回响应后写入响应体期间仍然占用，WebSocket 连接在升级完成后释放
+    in_flight: Arc<Semaphore>,
+    pub metrics: Arc<PluginMetrics>,
+    // Guest 导出的 health.check，未导出时只由宿主判断
+    health_check: Option<ComponentExportIndex>,
+    // Guest 导出的 scheduler.on-tick
+    on_tick: Option<ComponentExportIndex>,
+    // plugin.json 中声明的定时任务
+    pub(crate) schedules: Vec<(Schedule, Trigger)>,
+    // 定时任务的执行记录
+    schedule_runs: Sender<ScheduleRun>,
+    // 递增 Engine epoch 的任务，PlatServer 释放时结束
+    _epoch_ticker: AbortOnDrop,
 }
 
 impl PlatServer {
-    pub fn new(plugin_config_path: PathBuf, daemon_address: String) -> anyhow::Result<Self> {
+    pub fn new(
+        plugin_config_path: PathBuf,
+        daemon_address: String,
+        data_dir: PathBuf,
+    ) -> anyhow::Result<Self> {
         if !plugin_config_path.is_absolute() {
             return Err(anyhow!(
                 "plugin_config_path 必须为绝对路径，但它的值为：{}",
@@ -40,9 +116,27 @@ impl PlatServer {
         let plugin_config: Plugin =
             serde_json::from_slice(&plugin_config_bytes).context("序列化 plugin.json 失败")?;
 
+        let request_limits = plugin_config.request_limits.unwrap_or_default();
+        if request_limits.timeout == 0 || request_limits.max_in_flight == 0 {
+            return Err(anyhow!(
+                "request_limits 中的 timeout 与 max_in_flight 必须大于 0"
+            ));
+        }
+
         let mut config = Config::new();
         config.async_support(true);
+        config.epoch_interruption(true);
         let engine = Engine::new(&config).context("创建 WASI Engine 失败")?;
+        let epoch_ticker = tokio::task::spawn({
+            let engine = engine.clone();
+            async move {
+                let mut interval = tokio::time::interval(EPOCH_TICK);
+                loop {
+                    interval.tick().await;
+                    engine.increment_epoch();
+                }
+            }
+        });
 
         let component = Component::from_file(
             &engine,
@@ -67,6 +161,26 @@ impl PlatServer {
             |state: &mut plat_bindings::Component| state,
         )
         .context("添加 Plat Channel 链接失败")?;
+        plat_bindings::notify::add_to_linker(
+            &mut linker,
+            |state: &mut plat_bindings::Component| state,
+        )
+        .context("添加 Plat Notify 链接失败")?;
+        plat_bindings::log::add_to_linker(&mut linker, |state: &mut plat_bindings::Component| {
+            state
+        })
+        .context("添加 Plat Log 链接失败")?;
+        plat_bindings::websocket::add_to_linker(
+            &mut linker,
+            |state: &mut plat_bindings::Component| state,
+        )
+        .context("添加 Plat WebSocket 链接失败")?;
+        plat_bindings::kv::add_to_linker(&mut linker, |state: &mut plat_bindings::Component| state)
+            .context("添加 Plat KV 链接失败")?;
+        plat_bindings::sql::add_to_linker(&mut linker, |state: &mut plat_bindings::Component| {
+            state
+        })
+        .context("添加 Plat SQL 链接失败")?;
 
         let pre = plat_bindings::PlatWorldPre::new(
             linker
@@ -74,115 +188,430 @@ impl PlatServer {
                 .context("构建 instance_pre 失败")?,
         )
         .context("构建 plat_world_pre 失败")?;
+        let health_check = component
+            .export_index(None, HEALTH_INTERFACE)
+            .and_then(|(_, health)| component.export_index(Some(&health), "check"))
+            .map(|(_, check)| check);
+        let on_tick = component
+            .export_index(None, SCHEDULER_INTERFACE)
+            .and_then(|(_, scheduler)| component.export_index(Some(&scheduler), "on-tick"))
+            .map(|(_, on_tick)| on_tick);
+        let schedules = Trigger::parse_all(plugin_config.schedules.as_deref().unwrap_or_default())
+            .context("解析 schedules 失败")?;
+        if !schedules.is_empty() && on_tick.is_none() {
+            return Err(anyhow!(
+                "plugin.json 声明了 schedules，但 Guest 没有导出 {}",
+                SCHEDULER_INTERFACE
+            ));
+        }
+        // 宿主管理的数据不能放在 Guest 可以直接读写的 storage_root 中
+        let storage_path = plugin_config_directory.join(&plugin_config.storage_root);
+        if data_dir.starts_with(&storage_path) {
+            return Err(anyhow!("Plugin 的数据目录不能位于 storage_root 中"));
+        }
+        fs::create_dir_all(&data_dir).context("创建 Plugin 数据目录失败")?;
+        move_legacy_data(&storage_path, &data_dir)?;
+        let kv = KvStore::shared(data_dir.clone());
+        let sql = Arc::new(SqlDatabase::new(
+            data_dir.clone(),
+            plugin_config.sql_max_size(),
+        ));
         Ok(PlatServer {
+            _epoch_ticker: AbortOnDrop(Some(epoch_ticker.abort_handle())),
+            health_check,
+            on_tick,
+            schedules,
+            schedule_runs: broadcast::channel(SCHEDULE_RUN_CAPACITY).0,
             pre,
+            request_limits,
+            in_flight: Arc::new(Semaphore::new(request_limits.max_in_flight)),
+            metrics: Arc::new(PluginMetrics::default()),
             plugin_config,
-            plugin_config_directory,
             daemon_address,
-            daemon_public_key: String::new(),
+            daemon_public_key: RwLock::new(String::new()),
+            notifications: broadcast::channel(NOTIFICATION_CAPACITY).0,
+            errors: broadcast::channel(ERROR_CAPACITY).0,
+            log: Arc::new(PluginLog::new(data_dir)),
+            kv,
+            sql,
+            plugin_config_directory,
+            https: false,
+            daemon_fingerprint: None,
+            debug: false,
         })
     }
 
-    pub async fn handle_request(
+    // 请求体可以来自 Plugin 服务的 TCP 连接，也可以由 Daemon 在进程内直接传入；
+    // Guest 处理失败时返回带有请求 ID 的 500 响应，并记录错误
+    pub async fn handle_request<B>(
         &self,
-        req: Request<Incoming>,
-    ) -> Result<hyper::Response<HyperOutgoingBody>> {
-        let mut store = Store::new(self.pre.engine(), plat_bindings::Component::new(&self));
+        mut req: Request<B>,
+    ) -> Result<Response<HyperOutgoingBody>>
+    where
+        B: Body<Data = Bytes> + Send + Unpin + 'static,
+        B::Error: std::fmt::Display,
+    {
+        let request_id = request_id(req.headers());
+        req.headers_mut()
+            .insert(REQUEST_ID_HEADER, HeaderValue::from_str(&request_id)?);
+        let context = ErrorContext {
+            request_id: request_id.clone(),
+            method: req.method().to_string(),
+            path: req.uri().path().to_string(),
+            errors: self.errors.clone(),
+            metrics: self.metrics.clone(),
+        };
+
+        let mut res = match self.call_guest(req, &context).await {
+            Ok(res) => res,
+            Err(e) => {
+                let error = context.record(&e);
+                let mut message = format!("Plugin 处理请求失败，请求 ID：{}", request_id);
+                if self.debug {
+                    message.push_str(&format!("\n\n{}", error.message));
+                    if let Some(backtrace) = error.backtrace.as_ref() {
+                        message.push_str(&format!("\n\n{}", backtrace));
+                    }
+                }
+                text_response(StatusCode::INTERNAL_SERVER_ERROR, message)?
+            }
+        };
+        res.headers_mut()
+            .insert(REQUEST_ID_HEADER, HeaderValue::from_str(&request_id)?);
+        Ok(res)
+    }
+
+    async fn call_guest<B>(
+        &self,
+        req: Request<B>,
+        context: &ErrorContext,
+    ) -> Result<Response<HyperOutgoingBody>>
+    where
+        B: Body<Data = Bytes> + Send + Unpin + 'static,
+        B::Error: std::fmt::Display,
+    {
+        let Ok(permit) = self.in_flight.clone().try_acquire_owned() else {
+            tracing::warn!("Plugin 正在处理的请求过多");
+            return status_response(StatusCode::SERVICE_UNAVAILABLE, "Plugin 正在处理的请求过多");
+        };
+
+        let mut store = self.new_store();
         let (sender, receiver) = tokio::sync::oneshot::channel();
-        let req = store.data_mut().new_incoming_request(Scheme::Http, req)?;
+        let scheme = match self.https {
+            true => Scheme::Https,
+            false => Scheme::Http,
+        };
+        let (mut parts, body) = req.into_parts();
+        // WebSocket 升级请求交给 Guest 决定是否接受
+        let upgrade =
+            PendingUpgrade::from_request(&parts.method, &parts.headers, &mut parts.extensions);
+        let (accept_key, mut accepted) = match upgrade {
+            Some((pending, accepted)) => {
+                let accept_key = pending.accept_key();
+                store.data_mut().websockets.set_pending(pending);
+                (Some(accept_key), Some(accepted))
+            }
+            None => (None, None),
+        };
+        let body = SyncBody(std::sync::Mutex::new(body))
+            .map_err(|e| ErrorCode::InternalError(Some(e.to_string())))
+            .boxed();
+        let body = HostIncomingBody::new(body, Duration::from_secs(600));
+        let req = HostIncomingRequest::new(store.data_mut(), parts, scheme, Some(body))?;
+        let req = store.data_mut().table().push(req)?;
         let out = store.data_mut().new_response_outparam(sender)?;
         let pre = self.pre.clone();
+        let metrics = self.metrics.clone();
 
-        let task = tokio::task::spawn(async move {
-            let proxy = pre.instantiate_async(&mut store).await?;
+        let task = tokio::task::spawn(
+            async move {
+                let start = Instant::now();
+                let proxy = pre.instantiate_async(&mut store).await?;
+                metrics.observe_instantiation(start.elapsed());
 
-            if let Err(e) = proxy
-                .wasi_http_incoming_handler()
-                .call_handle(store, req, out)
-                .await
-            {
-                return Err(e);
-            }
+                proxy
+                    .wasi_http_incoming_handler()
+                    .call_handle(store, req, out)
+                    .await?;
 
-            Ok(())
-        });
+                Ok::<(), wasmtime::Error>(())
+            }
+            .in_current_span(),
+        );
 
-        match receiver.await {
-            Ok(Ok(resp)) => Ok(resp),
+        // 返回响应之前客户端断开或超时时终止 Guest，返回响应后 Guest 继续写入响应体
+        let mut guard = AbortOnDrop(Some(task.abort_handle()));
+        let timeout = Duration::from_secs(self.request_limits.timeout);
+        let result = tokio::select! {
+            result = receiver => result,
+            Some(Ok(protocol)) = async { Some(accepted.as_mut()?.await) } => {
+                guard.disarm();
+                drop(permit);
+                context.watch(task, None);
+                return websocket::switching_protocols(&accept_key.unwrap_or_default(), protocol);
+            }
+            _ = tokio::time::sleep(timeout) => {
+                tracing::warn!(timeout = timeout.as_secs(), "Plugin 处理请求超时");
+                return status_response(StatusCode::GATEWAY_TIMEOUT, "Plugin 处理请求超时");
+            }
+        };
+        match result {
+            Ok(Ok(resp)) => {
+                guard.disarm();
+                context.watch(task, Some(permit));
+                Ok(resp)
+            }
             Ok(Err(e)) => Err(e.into()),
 
             Err(_) => {
                 let e = match task.await {
-                    Ok(r) => r.unwrap_err(),
+                    Ok(Ok(())) => anyhow!("Guest 返回时未设置响应"),
+                    Ok(Err(e)) => e,
                     Err(e) => e.into(),
                 };
-                anyhow::bail!("guest never invoked `response-outparam::set` method: {e:?}")
+                Err(e.context("guest never invoked `response-outparam::set` method"))
             }
         }
     }
 
-    pub async fn create_regist_plugin_handle(&mut self) -> anyhow::Result<Sender<()>> {
-        let mut regist_plugin_address = Url::parse(&self.daemon_address)?.join("api/regist")?;
-        match regist_plugin_address.scheme() {
-            "http" => {
-                regist_plugin_address.set_scheme("ws").unwrap();
-            }
-            "https" => {
-                regist_plugin_address.set_scheme("wss").unwrap();
-            }
-            _ => {}
-        };
+    // 每经过一个 epoch 让出一次线程，Guest 长时间计算时超时与取消同样能够生效
+    pub(crate) fn new_store(&self) -> Store<plat_bindings::Component> {
+        let mut store = Store::new(self.pre.engine(), plat_bindings::Component::new(self));
+        store.set_epoch_deadline(1);
+        store.epoch_deadline_async_yield_and_update(1);
+        store
+    }
 
-        let (ws_stream, _response) =
-            tokio_tungstenite::connect_async(regist_plugin_address.to_string()).await?;
-        let (mut write, mut read) = ws_stream.split();
+    pub fn notifications(&self) -> Sender<Notification> {
+        self.notifications.clone()
+    }
 
-        match read.next().await.unwrap()? {
-            Message::Text(daemon_public_key) => {
-                self.daemon_public_key = daemon_public_key;
-            }
-            _ => return Err(anyhow!("注册 Plugin 失败")),
+    pub fn subscribe_notifications(&self) -> Receiver<Notification> {
+        self.notifications.subscribe()
+    }
+
+    pub fn subscribe_errors(&self) -> Receiver<GuestError> {
+        self.errors.subscribe()
+    }
+
+    // 调用 Guest 导出的健康检查，返回不健康的原因；Guest 未导出时视为健康
+    pub async fn check_health(&self, timeout: Duration) -> std::result::Result<(), String> {
+        let Some(check) = self.health_check.as_ref() else {
+            return Ok(());
+        };
+        let mut store = self.new_store();
+        let call = async {
+            let instance = self
+                .pre
+                .instance_pre()
+                .instantiate_async(&mut store)
+                .await?;
+            let func = instance
+                .get_typed_func::<(), (std::result::Result<(), String>,)>(&mut store, check)?;
+            let (result,) = func.call_async(&mut store, ()).await?;
+            func.post_return_async(&mut store).await?;
+            Ok::<_, wasmtime::Error>(result)
+        };
+        match tokio::time::timeout(timeout, call).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(e)) => Err(format!("调用健康检查失败：{}", e)),
+            Err(_) => Err("健康检查超时".to_string()),
         }
+    }
 
-        write
-            .send(Message::text(
-                serde_json::to_string(&self.plugin_config).unwrap(),
-            ))
+    // 实例化组件并调用 Guest 导出的 on-tick，外层错误表示 Guest trap 或调用失败；
+    // 超过 timeout 后 Guest 在下一个 epoch 以 DeadlineExceeded 中止
+    pub(crate) async fn call_on_tick(
+        &self,
+        name: &str,
+        timeout: Duration,
+    ) -> Result<std::result::Result<(), String>> {
+        let on_tick = self
+            .on_tick
+            .as_ref()
+            .ok_or_else(|| anyhow!("Guest 没有导出 {}", SCHEDULER_INTERFACE))?;
+        let mut store = self.new_store();
+        let deadline = Instant::now() + timeout;
+        store.epoch_deadline_callback(move |_| match Instant::now() >= deadline {
+            true => Err(DeadlineExceeded.into()),
+            false => Ok(UpdateDeadline::Yield(1)),
+        });
+        let start = Instant::now();
+        let instance = self
+            .pre
+            .instance_pre()
+            .instantiate_async(&mut store)
             .await?;
+        self.metrics.observe_instantiation(start.elapsed());
+        let func = instance
+            .get_typed_func::<(&str,), (std::result::Result<(), String>,)>(&mut store, on_tick)?;
+        let (result,) = func.call_async(&mut store, (name,)).await?;
+        func.post_return_async(&mut store).await?;
+        Ok(result)
+    }
+
+    pub(crate) fn record_schedule_run(&self, run: ScheduleRun) {
+        let _ = self.schedule_runs.send(run);
+    }
+
+    pub fn subscribe_schedule_runs(&self) -> Receiver<ScheduleRun> {
+        self.schedule_runs.subscribe()
+    }
+
+    pub fn metrics_snapshot(&self) -> MetricsSnapshot {
+        let in_flight = self.request_limits.max_in_flight - self.in_flight.available_permits();
+        self.metrics.snapshot(in_flight)
+    }
+
+    pub fn daemon_public_key(&self) -> String {
+        match self.daemon_public_key.read() {
+            Ok(key) => key.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    // 重新注册时 Daemon 可能已轮换密钥
+    pub fn set_daemon_public_key(&self, daemon_public_key: String) {
+        match self.daemon_public_key.write() {
+            Ok(mut key) => *key = daemon_public_key,
+            Err(poisoned) => *poisoned.into_inner() = daemon_public_key,
+        }
+    }
+}
+
+// 记录错误时需要的请求信息
+#[derive(Clone)]
+struct ErrorContext {
+    request_id: String,
+    method: String,
+    path: String,
+    errors: Sender<GuestError>,
+    metrics: Arc<PluginMetrics>,
+}
+
+impl ErrorContext {
+    fn record(&self, e: &anyhow::Error) -> GuestError {
+        let backtrace = e.downcast_ref::<WasmBacktrace>().map(|b| b.to_string());
+        self.metrics
+            .record_error(e.downcast_ref::<Trap>().is_some());
+        let error = GuestError {
+            request_id: self.request_id.clone(),
+            time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|time| time.as_millis() as u64)
+                .unwrap_or_default(),
+            method: self.method.clone(),
+            path: self.path.clone(),
+            message: e
+                .chain()
+                .map(|cause| cause.to_string())
+                .filter(|cause| Some(cause) != backtrace.as_ref())
+                .collect::<Vec<_>>()
+                .join(": "),
+            backtrace,
+        };
+        tracing::error!(
+            request_id = %error.request_id,
+            "Guest 处理请求失败：{}",
+            error.message
+        );
+        let _ = self.errors.send(error.clone());
+        error
+    }
 
-        let (tx, _rx) = tokio::sync::broadcast::channel::<()>(4);
-        tokio::task::spawn({
-            let tx = tx.clone();
+    // 返回响应后 Guest 仍可能在写入响应体或处理 WebSocket 时 trap，此时只记录错误；
+    // permit 在 Guest 结束后释放
+    fn watch(&self, task: JoinHandle<Result<()>>, permit: Option<OwnedSemaphorePermit>) {
+        let context = self.clone();
+        tokio::task::spawn(
             async move {
-                let mut sub = tx.subscribe();
-                loop {
-                    tokio::select! {
-                        _ = tokio::time::sleep(Duration::from_secs(10)) => break,
-                        _ = sub.recv() => break,
-                        recv = read.next() => {
-                            match recv {
-                                Some(msg) => {
-                                    match msg {
-                                        Err(_) => break,
-                                        Ok(msg) => {
-                                            match msg {
-                                                Message::Ping(inner) => {
-                                                    write.send(Message::Pong(inner)).await.unwrap();
-                                                },
-                                                _ => (),
-                                            }
-                                        },
-                                    }
-                                },
-                                None => break,
-                            }
-                        },
-                    }
+                let result = task.await;
+                drop(permit);
+                if let Ok(Err(e)) = result {
+                    context.record(&e);
                 }
-                let _ = tx.send(());
             }
-        });
+            .in_current_span(),
+        );
+    }
+}
+
+// 早期版本将键值存储与数据库保存在 storage_root 中，数据目录中没有时移动过来
+fn move_legacy_data(storage_path: &Path, data_dir: &Path) -> anyhow::Result<()> {
+    for file_name in ["plat.kv", "plat.db", "plat.db-wal", "plat.db-shm"] {
+        let legacy = storage_path.join(file_name);
+        let target = data_dir.join(file_name);
+        if legacy.exists() && !target.exists() {
+            fs::rename(&legacy, &target)
+                .with_context(|| format!("移动 {} 到数据目录失败", legacy.display()))?;
+        }
+    }
+    Ok(())
+}
+
+// 使用客户端提供的请求 ID，没有或无效时生成新的 ID
+pub fn request_id(headers: &HeaderMap) -> String {
+    match headers
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+    {
+        Some(id) if !id.is_empty() && id.len() <= 128 => id.to_string(),
+        _ => {
+            let mut id = [0u8; 12];
+            rand::thread_rng().fill_bytes(&mut id);
+            BASE64_URL_SAFE_NO_PAD.encode(id)
+        }
+    }
+}
+
+fn status_response(
+    status: StatusCode,
+    message: &'static str,
+) -> Result<Response<HyperOutgoingBody>> {
+    text_response(status, message.to_string())
+}
+
+fn text_response(status: StatusCode, message: String) -> Result<Response<HyperOutgoingBody>> {
+    let body = Full::new(Bytes::from(message))
+        .map_err(|never| match never {})
+        .boxed();
+    Ok(Response::builder().status(status).body(body)?)
+}
+
+struct AbortOnDrop(Option<AbortHandle>);
+
+impl AbortOnDrop {
+    fn disarm(&mut self) {
+        self.0.take();
+    }
+}
 
-        Ok(tx)
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        if let Some(task) = self.0.take() {
+            task.abort();
+        }
+    }
+}
+
+// WASI HTTP 要求请求体与响应体满足 Sync，而 Daemon 传入的请求体与静态文件的响应体只满足 Send
+pub(crate) struct SyncBody<B>(pub(crate) std::sync::Mutex<B>);
+
+impl<B> Body for SyncBody<B>
+where
+    B: Body + Unpin,
+{
+    type Data = B::Data;
+    type Error = B::Error;
+
+    fn poll_frame(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Result<hyper::body::Frame<Self::Data>, Self::Error>>> {
+        let body = match self.get_mut().0.get_mut() {
+            Ok(body) => body,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        std::pin::Pin::new(body).poll_frame(cx)
     }
 }
@@ -0,0 +1,219 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, bail};
+use futures_util::{SinkExt, StreamExt};
+use http_body_util::{BodyExt, Empty};
+use hyper::header::{self, HeaderMap, HeaderValue};
+use hyper::upgrade::OnUpgrade;
+use hyper::{Method, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use tokio::sync::{mpsc, oneshot};
+use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::protocol::{CloseFrame, Role};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
+use tracing::Instrument;
+use wasmtime_wasi_http::body::HyperOutgoingBody;
+
+// Guest 未及时读取时最多缓存的消息数量，超出后暂停读取客户端消息
+const INCOMING_CAPACITY: usize = 64;
+
+// 等待 Guest 接受的 WebSocket 升级请求
+pub struct PendingUpgrade {
+    on_upgrade: OnUpgrade,
+    key: HeaderValue,
+    // 客户端通过 Sec-WebSocket-Protocol 请求的子协议
+    protocols: Vec<String>,
+    accepted: oneshot::Sender<Option<String>>,
+}
+
+impl PendingUpgrade {
+    // 请求为 WebSocket 升级请求时取出其中的 OnUpgrade，返回的 Receiver 在 Guest 接受后收到子协议
+    pub fn from_request(
+        method: &Method,
+        headers: &HeaderMap,
+        extensions: &mut hyper::http::Extensions,
+    ) -> Option<(Self, oneshot::Receiver<Option<String>>)> {
+        let is_websocket = method == Method::GET
+            && headers
+                .get(header::UPGRADE)
+                .and_then(|value| value.to_str().ok())
+                .is_some_and(|value| value.eq_ignore_ascii_case("websocket"));
+        if !is_websocket {
+            return None;
+        }
+        let key = headers.get(header::SEC_WEBSOCKET_KEY)?.clone();
+        let protocols = headers
+            .get_all(header::SEC_WEBSOCKET_PROTOCOL)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(|protocol| protocol.trim().to_string())
+            .filter(|protocol| !protocol.is_empty())
+            .collect();
+        let on_upgrade = extensions.remove::<OnUpgrade>()?;
+
+        let (accepted, receiver) = oneshot::channel();
+        Some((
+            PendingUpgrade {
+                on_upgrade,
+                key,
+                protocols,
+                accepted,
+            },
+            receiver,
+        ))
+    }
+
+    // 通知宿主返回 101 响应，并在连接升级后开始转发消息
+    fn accept(self, protocol: Option<String>) -> anyhow::Result<HostWebSocket> {
+        if let Some(protocol) = protocol.as_ref() {
+            if !self.protocols.contains(protocol) {
+                bail!("客户端未请求子协议：{}", protocol);
+            }
+        }
+
+        let (outgoing, outgoing_receiver) = mpsc::unbounded_channel();
+        let (incoming_sender, incoming) = mpsc::channel(INCOMING_CAPACITY);
+
+        self.accepted
+            .send(protocol)
+            .map_err(|_| anyhow!("请求已经结束"))?;
+        let span = tracing::info_span!("websocket");
+        tokio::task::spawn(
+            pump(self.on_upgrade, outgoing_receiver, incoming_sender).instrument(span),
+        );
+
+        Ok(HostWebSocket { outgoing, incoming })
+    }
+
+    pub fn accept_key(&self) -> String {
+        derive_accept_key(self.key.as_bytes())
+    }
+}
+
+// 升级成功后返回给客户端的 101 响应
+pub fn switching_protocols(
+    accept_key: &str,
+    protocol: Option<String>,
+) -> anyhow::Result<Response<HyperOutgoingBody>> {
+    let mut response = Response::builder()
+        .status(StatusCode::SWITCHING_PROTOCOLS)
+        .header(header::CONNECTION, "upgrade")
+        .header(header::UPGRADE, "websocket")
+        .header(header::SEC_WEBSOCKET_ACCEPT, accept_key);
+    if let Some(protocol) = protocol {
+        response = response.header(header::SEC_WEBSOCKET_PROTOCOL, protocol);
+    }
+
+    Ok(response.body(Empty::new().map_err(|never| match never {}).boxed())?)
+}
+
+// Guest 持有的 WebSocket 连接
+pub struct HostWebSocket {
+    outgoing: mpsc::UnboundedSender<Message>,
+    incoming: mpsc::Receiver<Message>,
+}
+
+// 每次请求的 Store 中保存的 WebSocket 状态
+#[derive(Default)]
+pub struct WebSockets {
+    pending: Option<PendingUpgrade>,
+    sockets: HashMap<u32, HostWebSocket>,
+    next_id: u32,
+}
+
+impl WebSockets {
+    pub fn set_pending(&mut self, pending: PendingUpgrade) {
+        self.pending = Some(pending);
+    }
+
+    pub fn accept(&mut self, protocol: Option<String>) -> anyhow::Result<u32> {
+        let pending = self
+            .pending
+            .take()
+            .ok_or_else(|| anyhow!("当前请求不是 WebSocket 升级请求或已被接受"))?;
+        let socket = pending.accept(protocol)?;
+
+        self.next_id += 1;
+        self.sockets.insert(self.next_id, socket);
+        Ok(self.next_id)
+    }
+
+    pub fn send(&mut self, id: u32, message: Message) -> anyhow::Result<()> {
+        self.socket(id)?
+            .outgoing
+            .send(message)
+            .map_err(|_| anyhow!("WebSocket 连接已关闭"))
+    }
+
+    pub async fn recv(&mut self, id: u32) -> anyhow::Result<Option<Message>> {
+        Ok(self.socket(id)?.incoming.recv().await)
+    }
+
+    pub fn close(&mut self, id: u32, code: u16, reason: String) {
+        if let Some(socket) = self.sockets.remove(&id) {
+            let _ = socket.outgoing.send(Message::Close(Some(CloseFrame {
+                code: CloseCode::from(code),
+                reason: reason.into(),
+            })));
+        }
+    }
+
+    fn socket(&mut self, id: u32) -> anyhow::Result<&mut HostWebSocket> {
+        self.sockets
+            .get_mut(&id)
+            .ok_or_else(|| anyhow!("WebSocket 连接不存在：{}", id))
+    }
+}
+
+// 在客户端连接与 Guest 之间转发消息，任意一方关闭后结束；
+// Guest 释放连接（请求结束）时向客户端发送关闭帧
+async fn pump(
+    on_upgrade: OnUpgrade,
+    mut outgoing: mpsc::UnboundedReceiver<Message>,
+    incoming: mpsc::Sender<Message>,
+) {
+    let upgraded = match on_upgrade.await {
+        Ok(upgraded) => upgraded,
+        Err(e) => {
+            tracing::warn!("WebSocket 升级失败：{:?}", e);
+            return;
+        }
+    };
+    let socket = WebSocketStream::from_raw_socket(TokioIo::new(upgraded), Role::Server, None).await;
+    let (mut sink, mut stream) = socket.split();
+    tracing::debug!("WebSocket 连接已建立");
+
+    loop {
+        tokio::select! {
+            message = stream.next() => match message {
+                Some(Ok(message @ (Message::Text(_) | Message::Binary(_)))) => {
+                    if incoming.send(message).await.is_err() {
+                        break;
+                    }
+                }
+                Some(Ok(Message::Close(_))) => {
+                    let _ = sink.close().await;
+                    break;
+                }
+                Some(Err(_)) | None => break,
+                Some(Ok(_)) => (),
+            },
+            message = outgoing.recv() => match message {
+                Some(message) => {
+                    let is_close = matches!(message, Message::Close(_));
+                    if sink.send(message).await.is_err() || is_close {
+                        break;
+                    }
+                }
+                None => {
+                    let _ = sink.send(Message::Close(None)).await;
+                    break;
+                }
+            },
+        }
+    }
+    tracing::debug!("WebSocket 连接已关闭");
+}
@@ -0,0 +1,196 @@
+use std::{
+    fs,
+    io::Write,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{anyhow, Context};
+use base64::prelude::*;
+use rustls::{
+    client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+    crypto::{self, CryptoProvider},
+    pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime},
+    ClientConfig, DigitallySignedStruct, RootCertStore, ServerConfig, SignatureScheme,
+};
+use sha2::{Digest, Sha256};
+
+pub use rustls;
+
+pub fn provider() -> Arc<CryptoProvider> {
+    Arc::new(crypto::ring::default_provider())
+}
+
+// 私钥文件只允许当前用户读写，文件已存在时同样收紧权限
+pub fn write_private(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    let mut file = options.open(path)?;
+    #[cfg(unix)]
+    file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+    file.write_all(contents)
+}
+
+// 证书指纹：DER 编码证书的 SHA-256 摘要，使用 Base64 URL 编码
+pub fn fingerprint(cert: &CertificateDer<'_>) -> String {
+    BASE64_URL_SAFE.encode(Sha256::digest(cert.as_ref()))
+}
+
+pub fn read_pem(
+    cert_path: &Path,
+    key_path: &Path,
+) -> anyhow::Result<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)> {
+    let cert_bytes = fs::read(cert_path)
+        .with_context(|| format!("读取证书文件失败：{}", cert_path.display()))?;
+    let certs = rustls_pemfile::certs(&mut cert_bytes.as_slice())
+        .collect::<Result<Vec<_>, _>>()
+        .context("解析证书文件失败")?;
+    if certs.is_empty() {
+        return Err(anyhow!("证书文件中不包含证书：{}", cert_path.display()));
+    }
+
+    let key_bytes =
+        fs::read(key_path).with_context(|| format!("读取私钥文件失败：{}", key_path.display()))?;
+    let key = rustls_pemfile::private_key(&mut key_bytes.as_slice())
+        .context("解析私钥文件失败")?
+        .ok_or(anyhow!("私钥文件中不包含私钥：{}", key_path.display()))?;
+
+    Ok((certs, key))
+}
+
+// Daemon 与 Plugin 服务都同时支持 HTTP/2 与 HTTP/1.1，通过 ALPN 协商
+pub fn server_config(
+    certs: Vec<CertificateDer<'static>>,
+    key: PrivateKeyDer<'static>,
+) -> anyhow::Result<Arc<ServerConfig>> {
+    let mut config = ServerConfig::builder_with_provider(provider())
+        .with_safe_default_protocol_versions()?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .context("构建 TLS 配置失败")?;
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+    Ok(Arc::new(config))
+}
+
+// 服务端的 TLS 配置及其证书指纹
+#[derive(Clone)]
+pub struct ServerTls {
+    pub config: Arc<ServerConfig>,
+    pub fingerprint: String,
+}
+
+impl ServerTls {
+    pub fn load(cert_path: &Path, key_path: &Path) -> anyhow::Result<Self> {
+        let (certs, key) = read_pem(cert_path, key_path)?;
+        let fingerprint = fingerprint(&certs[0]);
+
+        Ok(ServerTls {
+            config: server_config(certs, key)?,
+            fingerprint,
+        })
+    }
+}
+
+// 指定指纹时只信任该证书，否则使用 WebPKI 根证书校验
+pub fn client_config(pinned_fingerprint: Option<&str>) -> anyhow::Result<Arc<ClientConfig>> {
+    let builder =
+        ClientConfig::builder_with_provider(provider()).with_safe_default_protocol_versions()?;
+
+    let config = match pinned_fingerprint {
+        Some(fingerprint) => builder
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(FingerprintVerifier::new(Some(
+                fingerprint.to_string(),
+            ))))
+            .with_no_client_auth(),
+        None => {
+            let roots = RootCertStore {
+                roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+            };
+            builder.with_root_certificates(roots).with_no_client_auth()
+        }
+    };
+
+    Ok(Arc::new(config))
+}
+
+// 基于证书指纹的校验器，未指定指纹时接受任意证书并记录其指纹，由调用方自行校验
+#[derive(Debug)]
+pub struct FingerprintVerifier {
+    expected: Option<String>,
+    presented: Mutex<Option<String>>,
+    provider: Arc<CryptoProvider>,
+}
+
+impl FingerprintVerifier {
+    pub fn new(expected: Option<String>) -> Self {
+        FingerprintVerifier {
+            expected,
+            presented: Mutex::new(None),
+            provider: provider(),
+        }
+    }
+
+    pub fn presented(&self) -> Option<String> {
+        self.presented.lock().unwrap().clone()
+    }
+}
+
+impl ServerCertVerifier for FingerprintVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let presented = fingerprint(end_entity);
+        if let Some(expected) = self.expected.as_ref() {
+            if expected != &presented {
+                return Err(rustls::Error::General(String::from("证书指纹不匹配")));
+            }
+        }
+        self.presented.lock().unwrap().replace(presented);
+
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}